@@ -26,6 +26,22 @@ struct RawOperand {
     immediate: bool,
 }
 
+/// Turns a ROM path into a valid, reasonably short test function name.
+fn rom_test_name(path: &str, prefix: &str) -> String {
+    let name = path
+        .replace("/", "_")
+        .replace(")", "_")
+        .replace("(", "_")
+        .replace(",", "_")
+        .replace(" ", "_")
+        .replace("-", "_")
+        .replace(".", "_")
+        .replace("__", "_"); // Fixes a linting issue in naming after previous replacements.
+
+    // Reduce name length for simplicity.
+    name.strip_prefix(prefix).unwrap_or(&name).to_string()
+}
+
 fn generate_rom_tests() {
     let out_dir = std::env::var("OUT_DIR").unwrap();
     let destination = Path::new(&out_dir).join("generated_rom_tests.rs");
@@ -35,22 +51,12 @@ fn generate_rom_tests() {
 
     for entry in roms.filter_map(Result::ok) {
         let path = entry.to_str().unwrap();
-        let name = entry
-            .to_str()
-            .unwrap()
-            .replace("/", "_")
-            .replace(")", "_")
-            .replace("(", "_")
-            .replace(",", "_")
-            .replace(" ", "_")
-            .replace("-", "_")
-            .replace(".", "_")
-            .replace("__", "_"); // Fixes a linting issue in naming after previous replacements.
-
-        // Reduce name length for simplicity.
-        let name = name
-            .strip_prefix("tests_tools_gb_test_roms_")
-            .unwrap_or(&name);
+        let name = rom_test_name(path, "tests_tools_gb_test_roms_");
+
+        // Mooneye ROMs report through registers, they get their own harness.
+        if name.contains("mooneye") {
+            continue;
+        }
 
         // This test is a bit annoying to cover, we have other coverage for it.
         // It won't finish on time out or other issues.
@@ -72,6 +78,61 @@ fn generate_rom_tests() {
     fs::write(destination, test_code).unwrap();
 }
 
+/// Mooneye ROMs the MBC1 coverage relies on, they get a test even when they
+/// are missing so that a checkout without them fails instead of passing.
+const MOONEYE_MBC1_ROMS: [&str; 13] = [
+    "bits_bank1",
+    "bits_bank2",
+    "bits_mode",
+    "bits_ramg",
+    "multicart_rom_8Mb",
+    "ram_256kb",
+    "ram_64kb",
+    "rom_16Mb",
+    "rom_1Mb",
+    "rom_2Mb",
+    "rom_4Mb",
+    "rom_512kb",
+    "rom_8Mb",
+];
+
+/// Mooneye test suite ROMs, e.g. `emulator-only/mbc1/*.gb`.
+fn generate_mooneye_tests() {
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    let destination = Path::new(&out_dir).join("generated_mooneye_tests.rs");
+
+    let mut test_code = String::new();
+    let mut paths: Vec<String> = MOONEYE_MBC1_ROMS
+        .iter()
+        .map(|rom| {
+            format!(
+                "tests/tools/mooneye-test-suite/emulator-only/mbc1/{}.gb",
+                rom
+            )
+        })
+        .collect();
+    let roms =
+        glob::glob("tests/tools/mooneye-test-suite/**/*.gb").expect("Failed to read glob pattern");
+    for entry in roms.filter_map(Result::ok) {
+        let path = entry.to_str().unwrap().replace('\\', "/");
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+
+    for path in &paths {
+        // `rom_8Mb.gb` has to become a snake case test name.
+        let name = rom_test_name(path, "tests_tools_mooneye_test_suite_").to_lowercase();
+
+        test_code.push_str(&format!(
+            "#[test] fn {}() {{ run_test(r#\"{}\"#); }}\n",
+            name, path
+        ));
+    }
+
+    fs::write(destination, test_code).unwrap();
+}
+
 fn map_target(operand: &RawOperand, op_code: u8) -> String {
     // 1. Handle specialized Bit targets first
     if let Ok(bit) = operand.name.parse::<u8>() {
@@ -302,8 +363,10 @@ fn main() {
     fs::write(&dest_path, code).unwrap();
 
     generate_rom_tests();
+    generate_mooneye_tests();
     println!("wrote generated opcodes to: {:?}", dest_path);
     println!("cargo:rerun-if-changed=src/opcodes/data/opcodes.json");
     println!("cargo:rerun-if-changed=src/instruction.rs");
     println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=tests/tools/mooneye-test-suite");
 }
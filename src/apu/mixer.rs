@@ -0,0 +1,57 @@
+/*
+Source: https://gbdev.io/pandocs/Audio_details.html
+
+Register,Bits,Content
+NR51,0-3,Channels 1-4 to the right output
+NR51,4-7,Channels 1-4 to the left output
+NR50,0-2,Right volume, 0 is 1/8 and 7 is full
+NR50,4-6,Left volume
+NR50,3/7,VIN to right/left, no cartridge here drives it
+
+Each DAC turns the digital 0-15 of its channel into 1.0 down to -1.0, a DAC that
+is off outputs 0.0. Four channels sum up to -4.0..4.0, which is scaled by the
+master volume and by 1/4 to stay within -1.0..1.0.
+*/
+
+use crate::apu::Apu;
+
+fn dac(enabled: bool, digital: u8) -> f32 {
+    if enabled {
+        1.0 - digital as f32 / 7.5
+    } else {
+        0.0
+    }
+}
+
+impl Apu {
+    /// DAC outputs of channels 1-4, before panning and master volume.
+    pub fn channel_outputs(&self) -> [f32; 4] {
+        if !self.enabled {
+            return [0.0; 4];
+        }
+        [
+            dac(self.ch1.envelope.dac_enabled(), self.ch1.output()),
+            dac(self.ch2.envelope.dac_enabled(), self.ch2.output()),
+            dac(self.ch3.enabled, self.ch3.output()),
+            dac(self.ch4.envelope.dac_enabled(), self.ch4.output()),
+        ]
+    }
+
+    /// The stereo output right now, left and right in -1.0..=1.0.
+    pub fn mix(&self) -> (f32, f32) {
+        let outputs = self.channel_outputs();
+        let side = |pan: u8, volume: u8| {
+            let sum: f32 = outputs
+                .iter()
+                .enumerate()
+                .filter(|(ch, _)| (pan >> ch) & 1 != 0)
+                .map(|(_, out)| out)
+                .sum();
+            sum * ((volume & 0x07) + 1) as f32 / 8.0 / 4.0
+        };
+        (
+            side(self.nr51 >> 4, self.nr50 >> 4),
+            side(self.nr51 & 0x0F, self.nr50),
+        )
+    }
+}
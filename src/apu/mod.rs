@@ -1,4 +1,13 @@
+mod mixer;
+mod recorder;
+mod resampler;
+
+pub use recorder::{AudioRecorder, WavWriter};
+pub use resampler::{APU_CLOCK, DEFAULT_SAMPLE_RATE, Resampler, Sample};
+
 use crate::constants::*;
+use crate::model::Model;
+use crate::state::{SaveState, StateError, StateReader, StateWriter};
 
 // --- Sub-Components ---
 
@@ -58,6 +67,33 @@ impl VolumeEnvelope {
         self.timer = if self.period > 0 { self.period } else { 8 };
         self.current_volume = self.initial_volume;
     }
+
+    /// The DAC is off while NRx2 bits 3-7 are all clear.
+    pub fn dac_enabled(&self) -> bool {
+        self.initial_volume != 0 || self.direction
+    }
+}
+
+/// Runs a frequency timer for `cycles` T-cycles, returns how often it expired.
+fn clock_timer(timer: &mut u32, period: u32, cycles: u32) -> u32 {
+    if *timer > cycles {
+        *timer -= cycles;
+        return 0;
+    }
+    let late = cycles - *timer;
+    *timer = period - late % period;
+    1 + late / period
+}
+
+/// Duty waveforms, one bit per step, step 0 is the MSB.
+const DUTY_PATTERNS: [u8; 4] = [0b0000_0001, 0b1000_0001, 0b1000_0111, 0b0111_1110];
+
+fn square_output(duty: u8, duty_pos: u8, volume: u8) -> u8 {
+    if (DUTY_PATTERNS[duty as usize] >> (7 - duty_pos)) & 1 != 0 {
+        volume
+    } else {
+        0
+    }
 }
 
 #[derive(Default)]
@@ -129,6 +165,24 @@ pub struct Channel1 {
     pub envelope: VolumeEnvelope,
     pub duty: u8,
     pub frequency: u16,
+    pub freq_timer: u32, // T-cycles to the next duty step
+    pub duty_pos: u8,    // Internal 0-7
+}
+
+impl Channel1 {
+    fn clock(&mut self, cycles: u32) {
+        let period = (2048 - self.frequency as u32) * 4;
+        let steps = clock_timer(&mut self.freq_timer, period, cycles);
+        self.duty_pos = ((self.duty_pos as u32 + steps) % 8) as u8;
+    }
+
+    /// Digital output, 0-15.
+    pub fn output(&self) -> u8 {
+        if !self.length.channel_enabled {
+            return 0;
+        }
+        square_output(self.duty, self.duty_pos, self.envelope.current_volume)
+    }
 }
 
 #[derive(Default)]
@@ -137,16 +191,61 @@ pub struct Channel2 {
     pub envelope: VolumeEnvelope,
     pub duty: u8,
     pub frequency: u16,
+    pub freq_timer: u32,
+    pub duty_pos: u8,
+}
+
+impl Channel2 {
+    fn clock(&mut self, cycles: u32) {
+        let period = (2048 - self.frequency as u32) * 4;
+        let steps = clock_timer(&mut self.freq_timer, period, cycles);
+        self.duty_pos = ((self.duty_pos as u32 + steps) % 8) as u8;
+    }
+
+    pub fn output(&self) -> u8 {
+        if !self.length.channel_enabled {
+            return 0;
+        }
+        square_output(self.duty, self.duty_pos, self.envelope.current_volume)
+    }
 }
 
 #[derive(Default)]
 pub struct Channel3 {
-    pub enabled: bool, // NR30 Bit 7
+    pub enabled: bool, // NR30 Bit 7, the DAC
     pub length: LengthCounter,
     pub output_level: u8, // NR32 Bits 5-6
     pub frequency: u16,
     pub wave_ram: [u8; 16],
     pub position_counter: u8, // Internal 0-31
+    pub freq_timer: u32,
+    pub sample_buffer: u8, // Last nibble read from wave RAM
+}
+
+impl Channel3 {
+    fn clock(&mut self, cycles: u32) {
+        let period = (2048 - self.frequency as u32) * 2;
+        let steps = clock_timer(&mut self.freq_timer, period, cycles);
+        if steps > 0 {
+            self.position_counter = ((self.position_counter as u32 + steps) % 32) as u8;
+            let byte = self.wave_ram[(self.position_counter / 2) as usize];
+            self.sample_buffer = if self.position_counter.is_multiple_of(2) {
+                byte >> 4
+            } else {
+                byte & 0x0F
+            };
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if !self.length.channel_enabled {
+            return 0;
+        }
+        match self.output_level {
+            0 => 0,
+            level => self.sample_buffer >> (level - 1),
+        }
+    }
 }
 
 #[derive(Default)]
@@ -155,6 +254,212 @@ pub struct Channel4 {
     pub envelope: VolumeEnvelope,
     pub polynomial: u8, // NR43
     pub lfsr: u16,      // Linear Feedback Shift Register
+    pub freq_timer: u32,
+}
+
+impl Channel4 {
+    /// NR43: shift in bits 4-7, 7-bit mode in bit 3, divisor code in bits 0-2.
+    fn period(&self) -> Option<u32> {
+        let shift = self.polynomial >> 4;
+        let divisor = match self.polynomial & 0x07 {
+            0 => 8,
+            code => code as u32 * 16,
+        };
+        // Shifts 14 and 15 never clock the LFSR.
+        (shift < 14).then_some(divisor << shift)
+    }
+
+    fn clock(&mut self, cycles: u32) {
+        let Some(period) = self.period() else {
+            return;
+        };
+        for _ in 0..clock_timer(&mut self.freq_timer, period, cycles) {
+            let xor = (self.lfsr ^ (self.lfsr >> 1)) & 1;
+            self.lfsr = (self.lfsr >> 1) | (xor << 14);
+            if (self.polynomial & 0x08) != 0 {
+                self.lfsr = (self.lfsr & !(1 << 6)) | (xor << 6);
+            }
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if !self.length.channel_enabled || (self.lfsr & 1) != 0 {
+            return 0;
+        }
+        self.envelope.current_volume
+    }
+}
+
+// --- Save States ---
+
+impl SaveState for LengthCounter {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u16(self.counter);
+        w.bool(self.enabled);
+        w.bool(self.channel_enabled);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.counter = r.u16()?;
+        self.enabled = r.bool()?;
+        self.channel_enabled = r.bool()?;
+        Ok(())
+    }
+}
+
+impl SaveState for VolumeEnvelope {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.initial_volume);
+        w.bool(self.direction);
+        w.u8(self.period);
+        w.u8(self.timer);
+        w.u8(self.current_volume);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.initial_volume = r.u8()?;
+        self.direction = r.bool()?;
+        self.period = r.u8()?;
+        self.timer = r.u8()?;
+        self.current_volume = r.u8()?;
+        Ok(())
+    }
+}
+
+impl SaveState for FrequencySweep {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.period);
+        w.bool(self.negate);
+        w.u8(self.shift);
+        w.u8(self.timer);
+        w.u16(self.shadow_freq);
+        w.bool(self.enabled);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.period = r.u8()?;
+        self.negate = r.bool()?;
+        self.shift = r.u8()?;
+        self.timer = r.u8()?;
+        self.shadow_freq = r.u16()?;
+        self.enabled = r.bool()?;
+        Ok(())
+    }
+}
+
+impl SaveState for Channel1 {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.sweep.save_state(w);
+        self.length.save_state(w);
+        self.envelope.save_state(w);
+        w.u8(self.duty);
+        w.u16(self.frequency);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.sweep.load_state(r)?;
+        self.length.load_state(r)?;
+        self.envelope.load_state(r)?;
+        self.duty = r.u8()?;
+        self.frequency = r.u16()?;
+        Ok(())
+    }
+}
+
+impl SaveState for Channel2 {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.length.save_state(w);
+        self.envelope.save_state(w);
+        w.u8(self.duty);
+        w.u16(self.frequency);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.length.load_state(r)?;
+        self.envelope.load_state(r)?;
+        self.duty = r.u8()?;
+        self.frequency = r.u16()?;
+        Ok(())
+    }
+}
+
+impl SaveState for Channel3 {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.enabled);
+        self.length.save_state(w);
+        w.u8(self.output_level);
+        w.u16(self.frequency);
+        w.bytes(&self.wave_ram);
+        w.u8(self.position_counter);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.enabled = r.bool()?;
+        self.length.load_state(r)?;
+        self.output_level = r.u8()?;
+        self.frequency = r.u16()?;
+        r.bytes_into(&mut self.wave_ram)?;
+        self.position_counter = r.u8()?;
+        Ok(())
+    }
+}
+
+impl SaveState for Channel4 {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.length.save_state(w);
+        self.envelope.save_state(w);
+        w.u8(self.polynomial);
+        w.u16(self.lfsr);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.length.load_state(r)?;
+        self.envelope.load_state(r)?;
+        self.polynomial = r.u8()?;
+        self.lfsr = r.u16()?;
+        Ok(())
+    }
+}
+
+impl SaveState for Apu {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.enabled);
+        w.u32(self.fs_timer);
+        w.u8(self.fs_step);
+        self.ch1.save_state(w);
+        self.ch2.save_state(w);
+        self.ch3.save_state(w);
+        self.ch4.save_state(w);
+        w.u8(self.nr50);
+        w.u8(self.nr51);
+        w.u32(self.ch1.freq_timer);
+        w.u8(self.ch1.duty_pos);
+        w.u32(self.ch2.freq_timer);
+        w.u8(self.ch2.duty_pos);
+        w.u32(self.ch3.freq_timer);
+        w.u8(self.ch3.sample_buffer);
+        w.u32(self.ch4.freq_timer);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.enabled = r.bool()?;
+        self.fs_timer = r.u32()?;
+        self.fs_step = r.u8()?;
+        self.ch1.load_state(r)?;
+        self.ch2.load_state(r)?;
+        self.ch3.load_state(r)?;
+        self.ch4.load_state(r)?;
+        self.nr50 = r.u8()?;
+        self.nr51 = r.u8()?;
+        self.ch1.freq_timer = r.u32()?;
+        self.ch1.duty_pos = r.u8()? & 0x07;
+        self.ch2.freq_timer = r.u32()?;
+        self.ch2.duty_pos = r.u8()? & 0x07;
+        self.ch3.freq_timer = r.u32()?;
+        self.ch3.sample_buffer = r.u8()? & 0x0F;
+        self.ch4.freq_timer = r.u32()?;
+        Ok(())
+    }
 }
 
 // --- Main APU Module ---
@@ -171,6 +476,11 @@ pub struct Apu {
 
     pub nr50: u8, // Vol / Vin
     pub nr51: u8, // Panning
+
+    /// Left and right, at the host rate.
+    output: Resampler,
+    /// Channels 1-4 on their own, only kept once asked for.
+    stems: Option<Resampler>,
 }
 
 impl Apu {
@@ -185,19 +495,50 @@ impl Apu {
             ch4: Channel4::default(),
             nr50: 0,
             nr51: 0,
+            output: Resampler::new(DEFAULT_SAMPLE_RATE, 2),
+            stems: None,
         }
     }
 
+    /// Registers as left by the boot ROM, which plays the start-up sound on
+    /// channel 1. The SGB boot ROM is silent, so channel 1 is off there.
+    pub fn init_post_boot(&mut self, model: Model) {
+        let output = std::mem::replace(&mut self.output, Resampler::new(DEFAULT_SAMPLE_RATE, 2));
+        *self = Self {
+            output,
+            stems: self.stems.take(),
+            ..Self::new()
+        };
+        self.write_byte(ADDR_APU_NR52, 0x80);
+        self.write_byte(ADDR_APU_NR10, 0x80);
+        self.write_byte(ADDR_APU_NR11, 0xBF);
+        self.write_byte(ADDR_APU_NR12, 0xF3);
+        self.write_byte(ADDR_APU_NR50, 0x77);
+        self.write_byte(ADDR_APU_NR51, 0xF3);
+        self.ch1.length.channel_enabled = !model.is_sgb();
+    }
+
     // -------------------------------------------------------------------------
     //  Core Interconnect Logic
     // -------------------------------------------------------------------------
 
     /// Called by Bus every T-Cycle (approx 4 MHz)
     pub fn tick(&mut self, cycles: u32) {
-        if !self.enabled {
-            return;
+        if self.enabled {
+            self.tick_channels(cycles);
+        }
+        // Time passes for the output even while powered off.
+        let (left, right) = self.mix();
+        self.output.advance(cycles, &[left, right]);
+        if self.stems.is_some() {
+            let outputs = self.channel_outputs();
+            if let Some(stems) = &mut self.stems {
+                stems.advance(cycles, &outputs);
+            }
         }
+    }
 
+    fn tick_channels(&mut self, cycles: u32) {
         self.fs_timer += cycles;
 
         // 512 Hz Frame Sequencer (Approx every 8192 T-cycles)
@@ -206,7 +547,41 @@ impl Apu {
             self.advance_frame_sequencer();
         }
 
-        // TODO: Tick channel frequency timers here for audio generation
+        self.ch1.clock(cycles);
+        self.ch2.clock(cycles);
+        self.ch3.clock(cycles);
+        self.ch4.clock(cycles);
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.output.sample_rate()
+    }
+
+    /// Host rate `drain_samples` produces, usually 32000, 44100 or 48000.
+    pub fn set_sample_rate(&mut self, rate: u32) {
+        self.output.set_sample_rate(rate);
+        if let Some(stems) = &mut self.stems {
+            stems.set_sample_rate(rate);
+        }
+    }
+
+    /// Pulls the stereo frames produced since the last call into `out`,
+    /// left then right, as f32 or i16. Returns the number of frames.
+    pub fn drain_samples<S: Sample>(&mut self, out: &mut Vec<S>) -> usize {
+        self.output.read(out)
+    }
+
+    /// Starts keeping the output of each channel, before panning and volume.
+    pub fn enable_stems(&mut self) {
+        if self.stems.is_none() {
+            self.stems = Some(Resampler::new(self.sample_rate(), 4));
+        }
+    }
+
+    /// Like `drain_samples`, with channels 1-4 in each frame. Nothing
+    /// unless `enable_stems` was called.
+    pub fn drain_stems<S: Sample>(&mut self, out: &mut Vec<S>) -> usize {
+        self.stems.as_mut().map_or(0, |stems| stems.read(out))
     }
 
     fn advance_frame_sequencer(&mut self) {
@@ -390,6 +765,9 @@ impl Apu {
                 self.ch1.envelope.initial_volume = val >> 4;
                 self.ch1.envelope.direction = (val & 0x08) != 0;
                 self.ch1.envelope.period = val & 0x07;
+                if !self.ch1.envelope.dac_enabled() {
+                    self.ch1.length.channel_enabled = false;
+                }
             }
             ADDR_APU_NR13 => {
                 self.ch1.frequency = (self.ch1.frequency & 0xFF00) | val as u16;
@@ -398,10 +776,11 @@ impl Apu {
                 self.ch1.frequency = (self.ch1.frequency & 0x00FF) | ((val as u16 & 0x07) << 8);
                 self.ch1.length.enabled = (val & 0x40) != 0;
                 if (val & 0x80) != 0 {
-                    // Trigger
+                    // Trigger, the channel stays off while its DAC is.
                     self.ch1.length.reload(64);
-                    self.ch1.length.channel_enabled = true;
+                    self.ch1.length.channel_enabled = self.ch1.envelope.dac_enabled();
                     self.ch1.envelope.trigger();
+                    self.ch1.freq_timer = (2048 - self.ch1.frequency as u32) * 4;
                     self.ch1
                         .sweep
                         .trigger(self.ch1.frequency, &mut self.ch1.length.channel_enabled);
@@ -417,6 +796,9 @@ impl Apu {
                 self.ch2.envelope.initial_volume = val >> 4;
                 self.ch2.envelope.direction = (val & 0x08) != 0;
                 self.ch2.envelope.period = val & 0x07;
+                if !self.ch2.envelope.dac_enabled() {
+                    self.ch2.length.channel_enabled = false;
+                }
             }
             ADDR_APU_NR23 => {
                 self.ch2.frequency = (self.ch2.frequency & 0xFF00) | val as u16;
@@ -426,8 +808,9 @@ impl Apu {
                 self.ch2.length.enabled = (val & 0x40) != 0;
                 if (val & 0x80) != 0 {
                     self.ch2.length.reload(64);
-                    self.ch2.length.channel_enabled = true;
+                    self.ch2.length.channel_enabled = self.ch2.envelope.dac_enabled();
                     self.ch2.envelope.trigger();
+                    self.ch2.freq_timer = (2048 - self.ch2.frequency as u32) * 4;
                 }
             }
 
@@ -452,8 +835,9 @@ impl Apu {
                 self.ch3.length.enabled = (val & 0x40) != 0;
                 if (val & 0x80) != 0 {
                     self.ch3.length.reload(256);
-                    self.ch3.length.channel_enabled = true;
+                    self.ch3.length.channel_enabled = self.ch3.enabled;
                     self.ch3.position_counter = 0;
+                    self.ch3.freq_timer = (2048 - self.ch3.frequency as u32) * 2;
                 }
             }
 
@@ -465,6 +849,9 @@ impl Apu {
                 self.ch4.envelope.initial_volume = val >> 4;
                 self.ch4.envelope.direction = (val & 0x08) != 0;
                 self.ch4.envelope.period = val & 0x07;
+                if !self.ch4.envelope.dac_enabled() {
+                    self.ch4.length.channel_enabled = false;
+                }
             }
             ADDR_APU_NR43 => {
                 self.ch4.polynomial = val;
@@ -473,9 +860,10 @@ impl Apu {
                 self.ch4.length.enabled = (val & 0x40) != 0;
                 if (val & 0x80) != 0 {
                     self.ch4.length.reload(64);
-                    self.ch4.length.channel_enabled = true;
+                    self.ch4.length.channel_enabled = self.ch4.envelope.dac_enabled();
                     self.ch4.envelope.trigger();
                     self.ch4.lfsr = 0x7FFF; // Reset LFSR
+                    self.ch4.freq_timer = self.ch4.period().unwrap_or(0);
                 }
             }
 
@@ -516,6 +904,8 @@ impl Apu {
         self.ch3.length = LengthCounter::default();
         self.ch3.output_level = 0;
         self.ch3.frequency = 0;
+        self.ch3.freq_timer = 0;
+        self.ch3.sample_buffer = 0;
         self.ch4 = Channel4::default();
     }
 }
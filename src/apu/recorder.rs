@@ -0,0 +1,191 @@
+/*
+Source: http://soundfile.sapp.org/doc/WaveFormat/
+
+16-bit PCM WAV, all integers little endian. The sizes are unknown until the
+recording ends, they are written as 0 and filled in by `finish`.
+
+Offset,Size,Content
+0x00,4,"RIFF"
+0x04,u32,File size - 8
+0x08,4,"WAVE"
+0x0C,4,"fmt "
+0x10,u32,16
+0x14,u16,1 (PCM)
+0x16,u16,Channels
+0x18,u32,Sample rate
+0x1C,u32,Bytes per second
+0x20,u16,Bytes per frame
+0x22,u16,Bits per sample (16)
+0x24,4,"data"
+0x28,u32,Data size
+0x2C,...,Interleaved samples
+*/
+
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::apu::Apu;
+
+const HEADER_LEN: u32 = 44;
+
+pub struct WavWriter<W: Write + Seek> {
+    out: W,
+    data_len: u32,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut out: W, channels: u16, sample_rate: u32) -> io::Result<Self> {
+        let frame_len = channels * 2;
+        out.write_all(b"RIFF")?;
+        out.write_all(&0u32.to_le_bytes())?;
+        out.write_all(b"WAVEfmt ")?;
+        out.write_all(&16u32.to_le_bytes())?;
+        out.write_all(&1u16.to_le_bytes())?;
+        out.write_all(&channels.to_le_bytes())?;
+        out.write_all(&sample_rate.to_le_bytes())?;
+        out.write_all(&(sample_rate * frame_len as u32).to_le_bytes())?;
+        out.write_all(&frame_len.to_le_bytes())?;
+        out.write_all(&16u16.to_le_bytes())?;
+        out.write_all(b"data")?;
+        out.write_all(&0u32.to_le_bytes())?;
+        Ok(Self { out, data_len: 0 })
+    }
+
+    /// Interleaved samples, whole frames only.
+    pub fn write_samples(&mut self, samples: &[i16]) -> io::Result<()> {
+        let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        // The RIFF size field has to fit the header too.
+        let data_len = u32::try_from(bytes.len())
+            .ok()
+            .and_then(|len| self.data_len.checked_add(len))
+            .filter(|len| len.checked_add(HEADER_LEN - 8).is_some())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::FileTooLarge, "WAV file is full"))?;
+        self.out.write_all(&bytes)?;
+        self.data_len = data_len;
+        Ok(())
+    }
+
+    /// Fills in the sizes, returns the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.seek(SeekFrom::Start(4))?;
+        self.out
+            .write_all(&(HEADER_LEN - 8 + self.data_len).to_le_bytes())?;
+        self.out.seek(SeekFrom::Start(40))?;
+        self.out.write_all(&self.data_len.to_le_bytes())?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Writes the mixed APU output to a WAV file, and with stems each channel
+/// to its own mono file next to it.
+pub struct AudioRecorder {
+    path: PathBuf,
+    mix: WavWriter<BufWriter<File>>,
+    stems: Vec<WavWriter<BufWriter<File>>>,
+    buffer: Vec<i16>,
+}
+
+impl AudioRecorder {
+    pub fn create(path: &Path, stems: bool, apu: &mut Apu) -> io::Result<Self> {
+        let rate = apu.sample_rate();
+        let wav = |path: &Path, channels| {
+            WavWriter::new(BufWriter::new(File::create(path)?), channels, rate)
+        };
+        let mix = wav(path, 2)?;
+        let stems = if stems {
+            apu.enable_stems();
+            (1..=4)
+                .map(|ch| wav(&Self::stem_path(path, ch), 1))
+                .collect::<io::Result<_>>()?
+        } else {
+            Vec::new()
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            mix,
+            stems,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// `out.wav` puts channel 1 in `out.ch1.wav`.
+    pub fn stem_path(path: &Path, channel: u8) -> PathBuf {
+        path.with_extension(format!("ch{}.wav", channel))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes what the APU produced since the last call.
+    pub fn record(&mut self, apu: &mut Apu) -> io::Result<()> {
+        self.buffer.clear();
+        apu.drain_samples(&mut self.buffer);
+        self.mix.write_samples(&self.buffer)?;
+        if self.stems.is_empty() {
+            return Ok(());
+        }
+        self.buffer.clear();
+        apu.drain_stems(&mut self.buffer);
+        for (ch, stem) in self.stems.iter_mut().enumerate() {
+            let samples: Vec<i16> = self.buffer.iter().skip(ch).step_by(4).copied().collect();
+            stem.write_samples(&samples)?;
+        }
+        Ok(())
+    }
+
+    /// Finishes every file even if one fails, returns the first error.
+    pub fn finish(self) -> io::Result<()> {
+        let mut result = self.mix.finish().map(drop);
+        for stem in self.stems {
+            result = result.and(stem.finish().map(drop));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_header_sizes_are_filled_in() {
+        let mut wav = WavWriter::new(Cursor::new(Vec::new()), 2, 48_000).unwrap();
+        wav.write_samples(&[1, -1, 2, -2]).unwrap();
+        let data = wav.finish().unwrap().into_inner();
+
+        assert_eq!(data.len(), HEADER_LEN as usize + 8);
+        assert_eq!(&data[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(data[4..8].try_into().unwrap()), 44);
+        assert_eq!(u16::from_le_bytes(data[22..24].try_into().unwrap()), 2);
+        assert_eq!(
+            u32::from_le_bytes(data[28..32].try_into().unwrap()),
+            192_000
+        );
+        assert_eq!(u32::from_le_bytes(data[40..44].try_into().unwrap()), 8);
+        assert_eq!(&data[44..46], &1i16.to_le_bytes());
+        assert_eq!(&data[46..48], &(-1i16).to_le_bytes());
+    }
+
+    #[test]
+    fn test_full_wav_is_an_error() {
+        let mut wav = WavWriter::new(Cursor::new(Vec::new()), 2, 48_000).unwrap();
+        wav.data_len = u32::MAX - 40;
+        let err = wav.write_samples(&[1, -1, 2, -2]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::FileTooLarge);
+        assert_eq!(wav.data_len, u32::MAX - 40, "Nothing was counted");
+
+        // The header still gets the sizes of what was written.
+        let data = wav.finish().unwrap().into_inner();
+        assert_eq!(data.len(), HEADER_LEN as usize);
+    }
+
+    #[test]
+    fn test_stem_path() {
+        let path = AudioRecorder::stem_path(Path::new("out/run.wav"), 3);
+        assert_eq!(path, PathBuf::from("out/run.ch3.wav"));
+    }
+}
@@ -0,0 +1,268 @@
+/*
+Source: http://slack.net/~ant/bl-synth/ (band-limited sound synthesis),
+https://gbdev.io/pandocs/Audio_details.html#mixer
+
+The mixed output is a step function that changes at most once per M-cycle.
+Point sampling it at the host rate aliases every edge, so each change is added
+as a band-limited step instead: a windowed sinc impulse, picked from a table by
+the fractional position of the change, is summed into a buffer of deltas.
+Integrating the deltas gives the samples, TAPS / 2 samples late.
+
+Stage,Content
+Deltas,One band-limited step per output change
+Integrator,Running sum of the deltas, the output level
+High-pass,"The DMG output capacitor: out = in - cap, cap = in - out * charge"
+
+The capacitor charges by 0.999958 per T-cycle on the DMG, it removes the DC
+offset of enabled DACs.
+*/
+
+use std::sync::OnceLock;
+
+/// T-cycles per second.
+pub const APU_CLOCK: u32 = 4_194_304;
+pub const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+
+/// Samples each step is spread over.
+const TAPS: usize = 16;
+/// Fractional positions the kernel table is computed for.
+const PHASE_BITS: u32 = 6;
+const PHASES: usize = 1 << PHASE_BITS;
+/// Fraction of the output Nyquist frequency let through.
+const CUTOFF: f64 = 0.9;
+const CAPACITOR_CHARGE: f64 = 0.999958;
+/// Undrained seconds kept, older samples are dropped when nobody listens.
+const MAX_BUFFERED_SECONDS: usize = 1;
+/// Bits of fraction in the fixed point sample time.
+const FRAC_BITS: u32 = 32;
+
+/// Output sample formats.
+pub trait Sample: Copy {
+    /// From -1.0..=1.0.
+    fn from_f32(val: f32) -> Self;
+}
+
+impl Sample for f32 {
+    fn from_f32(val: f32) -> Self {
+        val.clamp(-1.0, 1.0)
+    }
+}
+
+impl Sample for i16 {
+    fn from_f32(val: f32) -> Self {
+        (val.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+    }
+}
+
+/// Windowed sinc impulses, each phase sums to 1 so a step settles exactly.
+fn kernel() -> &'static [[f32; TAPS]; PHASES] {
+    static KERNEL: OnceLock<[[f32; TAPS]; PHASES]> = OnceLock::new();
+    KERNEL.get_or_init(|| {
+        let mut table = [[0.0; TAPS]; PHASES];
+        let half = (TAPS / 2) as f64;
+        for (phase, taps) in table.iter_mut().enumerate() {
+            let frac = phase as f64 / PHASES as f64;
+            let mut impulse = [0.0f64; TAPS];
+            for (k, val) in impulse.iter_mut().enumerate() {
+                let x = k as f64 - half - frac + 1.0;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    let t = std::f64::consts::PI * x * CUTOFF;
+                    t.sin() / t
+                };
+                // Blackman window over -half..half
+                let w = (x + half) / (2.0 * half);
+                let window = 0.42 - 0.5 * (2.0 * std::f64::consts::PI * w).cos()
+                    + 0.08 * (4.0 * std::f64::consts::PI * w).cos();
+                *val = sinc * window.max(0.0);
+            }
+            let sum: f64 = impulse.iter().sum();
+            for (tap, val) in taps.iter_mut().zip(impulse) {
+                *tap = (val / sum) as f32;
+            }
+        }
+        table
+    })
+}
+
+/// One output stream: its deltas, integrator and capacitor.
+#[derive(Default)]
+struct BlipBuffer {
+    deltas: Vec<f32>,
+    /// Level the last change went to.
+    level: f32,
+    sum: f32,
+    capacitor: f32,
+}
+
+impl BlipBuffer {
+    fn add(&mut self, time: u64, level: f32) {
+        let delta = level - self.level;
+        if delta == 0.0 {
+            return;
+        }
+        self.level = level;
+        let index = (time >> FRAC_BITS) as usize;
+        let phase = ((time >> (FRAC_BITS - PHASE_BITS)) & (PHASES as u64 - 1)) as usize;
+        if self.deltas.len() < index + TAPS {
+            self.deltas.resize(index + TAPS, 0.0);
+        }
+        for (slot, tap) in self.deltas[index..index + TAPS]
+            .iter_mut()
+            .zip(kernel()[phase])
+        {
+            *slot += delta * tap;
+        }
+    }
+
+    /// Integrates and filters the next sample.
+    fn next(&mut self, i: usize, charge: f32) -> f32 {
+        self.sum += self.deltas.get(i).copied().unwrap_or(0.0);
+        let out = self.sum - self.capacitor;
+        self.capacitor = self.sum - out * charge;
+        out
+    }
+
+    fn remove(&mut self, count: usize) {
+        self.deltas.drain(..count.min(self.deltas.len()));
+    }
+}
+
+/// Turns levels that change at T-cycle times into samples at the host rate.
+pub struct Resampler {
+    sample_rate: u32,
+    /// Output samples per T-cycle, fixed point.
+    step: u64,
+    /// Position of now in the output, fixed point, from the first undrained sample.
+    time: u64,
+    charge: f32,
+    streams: Vec<BlipBuffer>,
+}
+
+impl Resampler {
+    pub fn new(sample_rate: u32, streams: usize) -> Self {
+        let mut resampler = Self {
+            sample_rate: 0,
+            step: 0,
+            time: 0,
+            charge: 0.0,
+            streams: (0..streams).map(|_| BlipBuffer::default()).collect(),
+        };
+        resampler.set_sample_rate(sample_rate);
+        resampler
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Drops what is buffered, the rate applies from here on.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        let sample_rate = sample_rate.max(1);
+        self.sample_rate = sample_rate;
+        self.step = ((sample_rate as u64) << FRAC_BITS) / APU_CLOCK as u64;
+        self.charge = CAPACITOR_CHARGE.powf(APU_CLOCK as f64 / sample_rate as f64) as f32;
+        self.time = 0;
+        for stream in self.streams.iter_mut() {
+            *stream = BlipBuffer {
+                level: stream.level,
+                sum: stream.level,
+                capacitor: stream.capacitor,
+                ..Default::default()
+            };
+        }
+    }
+
+    /// Moves on by `cycles` T-cycles, `levels` holds where each stream is now.
+    pub fn advance(&mut self, cycles: u32, levels: &[f32]) {
+        self.time += cycles as u64 * self.step;
+        for (stream, level) in self.streams.iter_mut().zip(levels) {
+            stream.add(self.time, *level);
+        }
+        let limit = self.sample_rate as usize * MAX_BUFFERED_SECONDS;
+        let available = self.available();
+        // Down to half the limit, so this doesn't run on every call.
+        if available > limit {
+            self.skip(available - limit / 2);
+        }
+    }
+
+    /// Frames that no later change can touch anymore.
+    pub fn available(&self) -> usize {
+        (self.time >> FRAC_BITS) as usize
+    }
+
+    fn skip(&mut self, count: usize) {
+        let charge = self.charge;
+        for stream in self.streams.iter_mut() {
+            for i in 0..count {
+                stream.next(i, charge);
+            }
+            stream.remove(count);
+        }
+        self.time -= (count as u64) << FRAC_BITS;
+    }
+
+    /// Appends the available frames to `out`, streams interleaved, returns the frame count.
+    pub fn read<S: Sample>(&mut self, out: &mut Vec<S>) -> usize {
+        let count = self.available();
+        out.reserve(count * self.streams.len());
+        for i in 0..count {
+            for stream in self.streams.iter_mut() {
+                out.push(S::from_f32(stream.next(i, self.charge)));
+            }
+        }
+        for stream in self.streams.iter_mut() {
+            stream.remove(count);
+        }
+        self.time -= (count as u64) << FRAC_BITS;
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kernel_phases_sum_to_one() {
+        for taps in kernel() {
+            let sum: f32 = taps.iter().sum();
+            assert!((sum - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_step_settles_and_decays() {
+        let mut resampler = Resampler::new(48_000, 1);
+        resampler.advance(1000, &[0.5]);
+        let mut out: Vec<f32> = Vec::new();
+        for _ in 0..200 {
+            resampler.advance(1000, &[0.5]);
+        }
+        resampler.read(&mut out);
+        // The step is around sample 11, and TAPS / 2 samples late.
+        assert!(out[..10].iter().all(|s| s.abs() < 1e-6));
+        assert!(
+            (0.45..=0.5).contains(&out[40]),
+            "Settled, barely discharged"
+        );
+        assert!(
+            out.last().unwrap().abs() < 0.01,
+            "The capacitor pulls DC back to 0"
+        );
+    }
+
+    #[test]
+    fn test_output_rate() {
+        let mut resampler = Resampler::new(44_100, 2);
+        let mut out: Vec<i16> = Vec::new();
+        for _ in 0..APU_CLOCK / 4 {
+            resampler.advance(4, &[0.0, 0.0]);
+        }
+        let frames = resampler.read(&mut out);
+        assert!((44_099..=44_100).contains(&frames), "{frames} frames");
+        assert_eq!(out.len(), frames * 2);
+    }
+}
@@ -3,6 +3,8 @@ use std::path::PathBuf;
 use clap::Parser;
 use log::Level;
 
+use crate::model::Model;
+
 /// Game Boy Emulator
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -25,6 +27,47 @@ pub struct Args {
     #[arg(long)]
     pub log_path: Option<PathBuf>,
 
+    // Hardware model to emulate, decides the post-boot register values.
+    // sgb and cgb need --boot-rom, their post-boot DIV isn't known.
+    #[arg(long, value_enum, default_value_t = Model::Dmg)]
+    pub model: Model,
+
+    // Run this DMG/MGB/CGB boot ROM from 0x0000 instead of starting in the post-boot state.
+    #[arg(long)]
+    pub boot_rom: Option<PathBuf>,
+
+    // Where battery-backed cartridge RAM is persisted, defaults to the rom path with a .sav extension.
+    #[arg(long)]
+    pub save_path: Option<PathBuf>,
+
+    // Load the save file but never write it back, useful for tests.
+    #[arg(long)]
+    pub save_read_only: bool,
+
+    // Start from a machine state written by --save-state, instead of from power on.
+    #[arg(long)]
+    pub load_state: Option<PathBuf>,
+
+    // Write the full machine state to this path on exit, to share a reproduction.
+    #[arg(long)]
+    pub save_state: Option<PathBuf>,
+
+    // Memory cap in MiB for the rewind buffer, 0 disables rewinding.
+    #[arg(long, default_value_t = 0)]
+    pub rewind_buffer_mb: usize,
+
+    // Capture a rewind state every N frames.
+    #[arg(long, default_value_t = 1)]
+    pub rewind_interval: u32,
+
+    // Write the mixed audio output to this WAV file.
+    #[arg(long)]
+    pub record_audio: Option<PathBuf>,
+
+    // With --record-audio, also write each channel to its own file, out.ch1.wav to out.ch4.wav.
+    #[arg(long, requires = "record_audio")]
+    pub record_stems: bool,
+
     // Run for a predeterminate amount of instructions for Game Boy Doctor emulator test.
     // Provide the number of log lines, or CPU instructions the game expects to verify.
     #[command(flatten)]
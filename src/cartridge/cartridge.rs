@@ -1,21 +1,49 @@
 use crate::cartridge::*;
 
 pub struct Cartridge {
+    pub headers: Headers,
     pub mbc: Box<dyn Mbc>, // Use a Box to hold any struct that implements Mbc
 }
 
 impl Cartridge {
     pub fn new(content: Vec<u8>) -> Self {
+        let headers = Headers::new(&content);
         // Logic to check byte 0x0147 in the ROM header
         // to see which MBC chip the game uses.
-        let mbc_type = content[0x0147];
+        // Images too small to carry a header are treated as plain ROM.
+        let mbc_type = headers.cart_type;
 
         let mbc: Box<dyn Mbc> = match mbc_type {
-            0x00 => Box::new(RomOnly::new(content)),
-            0x01..=0x03 => todo!("Implement MBC1"),
+            0x00 | 0x08 | 0x09 => Box::new(RomOnly::new(content)),
+            0x01..=0x03 => Box::new(Mbc1::new(content, headers.ram_size())),
+            0x05 | 0x06 => Box::new(Mbc2::new(content)),
+            0x0F..=0x13 => {
+                let has_rtc = matches!(mbc_type, 0x0F | 0x10);
+                Box::new(Mbc3::new(content, headers.ram_size(), has_rtc))
+            }
+            0x19..=0x1E => {
+                let has_rumble = mbc_type >= 0x1C;
+                Box::new(Mbc5::new(content, headers.ram_size(), has_rumble))
+            }
             _ => panic!("Unsupported MBC type: 0x{:02X}", mbc_type),
         };
 
-        Cartridge { mbc }
+        Cartridge { headers, mbc }
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        self.mbc.read(addr)
+    }
+
+    pub fn write(&mut self, addr: u16, val: u8) {
+        self.mbc.write(addr, val);
+    }
+
+    pub fn rumble(&self) -> bool {
+        self.mbc.rumble()
+    }
+
+    pub fn has_battery(&self) -> bool {
+        self.headers.has_battery()
     }
 }
@@ -0,0 +1,6 @@
+/// Wall-clock time source for cartridge real-time clocks.
+/// Swapped out in tests so that time can be driven deterministically.
+pub trait ClockSource {
+    /// Seconds since the UNIX epoch.
+    fn now(&self) -> u64;
+}
@@ -1,3 +1,4 @@
+use crate::constants::{BOOT_ROM_SIZE_CGB, BOOT_ROM_SIZE_DMG};
 use std::fmt;
 use std::io;
 
@@ -9,8 +10,11 @@ pub enum LoadError {
         expected: &'static str,
         found: String,
     },
+    InvalidBootRomSize(usize),
 }
 
+impl std::error::Error for LoadError {}
+
 impl fmt::Display for LoadError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -21,6 +25,11 @@ impl fmt::Display for LoadError {
                 "Invalid ROM file extension: expected '{}', found '{}'",
                 expected, found
             ),
+            LoadError::InvalidBootRomSize(size) => write!(
+                f,
+                "Boot ROM is {} bytes, expected {} (DMG) or {} (CGB)",
+                size, BOOT_ROM_SIZE_DMG, BOOT_ROM_SIZE_CGB
+            ),
         }
     }
 }
@@ -5,8 +5,9 @@ Address Range,Name,Purpose
 0x0100–0x0103,Entry Point,Usually contains a NOP followed by a JP 0x0150. This is the first code the CPU runs.
 0x0104–0x0133,Nintendo Logo,"A bitmap of the Nintendo logo. The Boot ROM compares this to its own copy; if it doesn't match, the GB won't boot."
 0x0134–0x0143,Title,Uppercase ASCII text of the game's name.
+0x0143,CGB Flag,Overlaps the title: 0x80 works on both, 0xC0 is CGB only.
 0x0144–0x0145,New Licensee Code,Two characters used to identify the game publisher.
-0x0146,SGB Flag,Indicates if the game supports Super Game Boy features.
+0x0146,SGB Flag,0x03 if the game supports Super Game Boy features, with old licensee 0x33.
 0x0147,Cartridge Type,Crucial: Tells you which MBC (if any) is inside the cart.
 0x0148,ROM Size,Indicates how many banks the ROM has.
 0x0149,RAM Size,Indicates how much external Save RAM is on the cart.
@@ -22,6 +23,7 @@ use log::{debug, error};
 #[derive(Debug, Default)]
 pub struct Headers {
     pub title: Option<String>,
+    pub cgb_flag: u8,         // 0x0143
     pub licensee_new: u16,    // 0x0144-0x0145
     pub sgb_flag: u8,         // 0x0146
     pub cart_type: u8,        // 0x0147
@@ -44,6 +46,7 @@ impl Headers {
 
         let mut headers = Self {
             title: extract_title(content),
+            cgb_flag: content[0x0143],
             licensee_new: u16::from_be_bytes([content[0x0144], content[0x0145]]),
             sgb_flag: content[0x0146],
             cart_type: content[0x0147],
@@ -95,9 +98,39 @@ impl Headers {
         // Spec: 32KB << rom_size_raw (where 0 is 32KB/2 banks)
         2 << self.rom_size_raw
     }
+
+    /// Size of the external cartridge RAM in bytes.
+    pub fn ram_size(&self) -> usize {
+        match self.ram_size_raw {
+            0x01 => 0x800, // 2KB, unofficial, only seen in homebrew.
+            0x02 => 0x2000,
+            0x03 => 0x8000,
+            0x04 => 0x20000,
+            0x05 => 0x10000,
+            _ => 0,
+        }
+    }
+
+    /// Whether the game has CGB functions, a CGB only runs it in CGB mode if so.
+    pub fn supports_cgb(&self) -> bool {
+        (self.cgb_flag & 0x80) != 0
+    }
+
+    /// Whether the game uses SGB functions, an SGB ignores packets otherwise.
+    pub fn supports_sgb(&self) -> bool {
+        self.sgb_flag == 0x03 && self.licensee_old == 0x33
+    }
+
+    /// Whether the cartridge keeps its RAM (and clock) alive with a battery.
+    pub fn has_battery(&self) -> bool {
+        matches!(
+            self.cart_type,
+            0x03 | 0x06 | 0x09 | 0x0D | 0x0F | 0x10 | 0x13 | 0x1B | 0x1E | 0x22 | 0xFF
+        )
+    }
 }
 
-fn verify_nintendo_logo(content: &[u8]) -> bool {
+pub(super) fn verify_nintendo_logo(content: &[u8]) -> bool {
     const NINTENDO_LOGO: [u8; 48] = [
         0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00,
         0x0D, 0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD,
@@ -204,4 +237,54 @@ mod tests {
         h.rom_size_raw = 0x05; // 1MB
         assert_eq!(h.rom_banks(), 64);
     }
+
+    #[test]
+    #[allow(clippy::field_reassign_with_default)]
+    fn test_ram_size_calculation() {
+        let mut h = Headers::default();
+
+        h.ram_size_raw = 0x00;
+        assert_eq!(h.ram_size(), 0);
+
+        h.ram_size_raw = 0x02; // 8KB
+        assert_eq!(h.ram_size(), 0x2000);
+
+        h.ram_size_raw = 0x03; // 32KB, 4 banks
+        assert_eq!(h.ram_size(), 0x8000);
+    }
+
+    #[test]
+    fn test_has_battery() {
+        let mut h = Headers::default();
+        for cart_type in [0x00, 0x01, 0x02, 0x05, 0x11, 0x19, 0x1C] {
+            h.cart_type = cart_type;
+            assert!(!h.has_battery(), "Cart type {:02X}", cart_type);
+        }
+        for cart_type in [0x03, 0x06, 0x0F, 0x10, 0x13, 0x1B, 0x1E] {
+            h.cart_type = cart_type;
+            assert!(h.has_battery(), "Cart type {:02X}", cart_type);
+        }
+    }
+
+    #[test]
+    fn test_supports_cgb() {
+        let mut data = create_valid_header_buffer();
+        assert!(!Headers::new(&data).supports_cgb());
+        for flag in [0x80, 0xC0] {
+            data[0x0143] = flag;
+            assert!(Headers::new(&data).supports_cgb(), "Flag {:02X}", flag);
+        }
+    }
+
+    #[test]
+    fn test_supports_sgb() {
+        let mut data = create_valid_header_buffer();
+        data[0x0146] = 0x03;
+        assert!(
+            !Headers::new(&data).supports_sgb(),
+            "Needs old licensee 0x33"
+        );
+        data[0x014B] = 0x33;
+        assert!(Headers::new(&data).supports_sgb());
+    }
 }
@@ -1,5 +1,6 @@
 use super::error::LoadError;
 use crate::cartridge::validation::validate_extension;
+use crate::constants::{BOOT_ROM_SIZE_CGB, BOOT_ROM_SIZE_DMG};
 use std::fs;
 use std::path::Path;
 
@@ -12,6 +13,15 @@ pub fn load_rom(path: &Path) -> Result<Vec<u8>, LoadError> {
     Ok(buffer)
 }
 
+/// Reads a DMG/MGB (256 bytes) or CGB (2304 bytes) boot ROM dump.
+pub fn load_boot_rom(path: &Path) -> Result<Vec<u8>, LoadError> {
+    let buffer = fs::read(path)?;
+    match buffer.len() {
+        BOOT_ROM_SIZE_DMG | BOOT_ROM_SIZE_CGB => Ok(buffer),
+        size => Err(LoadError::InvalidBootRomSize(size)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
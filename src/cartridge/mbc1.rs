@@ -0,0 +1,249 @@
+/*
+MBC1, source: https://gbdev.io/pandocs/MBC1.html
+
+Address Range,Register,Purpose
+0x0000–0x1FFF,RAM Enable,Any value with 0xA in the lower nibble enables external RAM.
+0x2000–0x3FFF,ROM Bank (BANK1),5-bit register, selects the bank mapped at 0x4000–0x7FFF. 0 is treated as 1.
+0x4000–0x5FFF,RAM Bank / Upper ROM Bank (BANK2),2-bit register, either selects the RAM bank or bits 5-6 of the ROM bank.
+0x6000–0x7FFF,Banking Mode Select,0 = BANK2 only affects 0x4000–0x7FFF, 1 = BANK2 also affects 0x0000–0x3FFF and RAM.
+*/
+
+use crate::cartridge::header::verify_nintendo_logo;
+use crate::cartridge::mbc_trait::Mbc;
+use crate::state::{SaveState, StateError, StateReader, StateWriter};
+
+pub(super) const ROM_BANK_SIZE: usize = 0x4000;
+pub(super) const RAM_BANK_SIZE: usize = 0x2000;
+
+/// Multicart collections (MBC1M) wire BANK1 as a 4-bit register, they are
+/// always 1 MiB and carry a second header in bank 0x10.
+const MULTICART_ROM_SIZE: usize = 0x10_0000;
+
+pub struct Mbc1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    bank1: u8,
+    bank2: u8,
+    mode: bool,
+    multicart: bool,
+}
+
+impl Mbc1 {
+    pub fn new(mut content: Vec<u8>, ram_size: usize) -> Self {
+        // Round up to a whole number of banks, so the bank number can simply
+        // be wrapped by the bank count just like the unconnected pins would.
+        let rom_size = content.len().max(2 * ROM_BANK_SIZE).next_power_of_two();
+        content.resize(rom_size, 0xFF);
+
+        let multicart = rom_size == MULTICART_ROM_SIZE
+            && verify_nintendo_logo(&content[0x10 * ROM_BANK_SIZE..]);
+
+        Self {
+            rom: content,
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            bank1: 1,
+            bank2: 0,
+            mode: false,
+            multicart,
+        }
+    }
+
+    fn rom_banks(&self) -> usize {
+        self.rom.len() / ROM_BANK_SIZE
+    }
+
+    /// How far BANK2 is shifted when forming a ROM bank number.
+    fn bank2_shift(&self) -> u8 {
+        if self.multicart { 4 } else { 5 }
+    }
+
+    /// Bank mapped at 0x0000–0x3FFF, only switchable in mode 1.
+    fn low_rom_bank(&self) -> usize {
+        let bank = if self.mode {
+            (self.bank2 as usize) << self.bank2_shift()
+        } else {
+            0
+        };
+        bank % self.rom_banks()
+    }
+
+    /// Bank mapped at 0x4000–0x7FFF.
+    fn high_rom_bank(&self) -> usize {
+        // The zero check happens on the full 5 bits, so on a multicart
+        // writing 0x10 really does map bank 0 here.
+        let bank1 = if self.multicart {
+            self.bank1 & 0x0F
+        } else {
+            self.bank1
+        };
+        let bank = ((self.bank2 as usize) << self.bank2_shift()) | bank1 as usize;
+        bank % self.rom_banks()
+    }
+
+    fn rom_offset(&self, addr: u16) -> usize {
+        match addr {
+            0x0000..=0x3FFF => self.low_rom_bank() * ROM_BANK_SIZE + addr as usize,
+            _ => self.high_rom_bank() * ROM_BANK_SIZE + (addr as usize - ROM_BANK_SIZE),
+        }
+    }
+
+    /// Returns None when RAM is disabled or missing, reads then float to 0xFF.
+    fn ram_offset(&self, addr: u16) -> Option<usize> {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return None;
+        }
+        let bank = if self.mode { self.bank2 as usize } else { 0 };
+        let offset = bank * RAM_BANK_SIZE + (addr - 0xA000) as usize;
+        // Carts with 2 KiB or 8 KiB of RAM ignore the bank and mirror.
+        Some(offset % self.ram.len())
+    }
+}
+
+impl SaveState for Mbc1 {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.ram);
+        w.bool(self.ram_enabled);
+        w.u8(self.bank1);
+        w.u8(self.bank2);
+        w.bool(self.mode);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        r.bytes_into(&mut self.ram)?;
+        self.ram_enabled = r.bool()?;
+        self.bank1 = r.u8()?;
+        self.bank2 = r.u8()?;
+        self.mode = r.bool()?;
+        Ok(())
+    }
+}
+
+impl Mbc for Mbc1 {
+    fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x7FFF => self.rom[self.rom_offset(addr)],
+            0xA000..=0xBFFF => match self.ram_offset(addr) {
+                Some(offset) => self.ram[offset],
+                None => 0xFF,
+            },
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = (val & 0x0F) == 0x0A,
+            0x2000..=0x3FFF => {
+                // Bank 0 quirk: a 0 in the 5-bit register selects bank 1.
+                let bank = val & 0x1F;
+                self.bank1 = if bank == 0 { 1 } else { bank };
+            }
+            0x4000..=0x5FFF => self.bank2 = val & 0x03,
+            0x6000..=0x7FFF => self.mode = (val & 0x01) != 0,
+            0xA000..=0xBFFF => {
+                if let Some(offset) = self.ram_offset(addr) {
+                    self.ram[offset] = val;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn force_write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x7FFF => {
+                let offset = self.rom_offset(addr);
+                self.rom[offset] = val;
+            }
+            _ => self.write(addr, val),
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::banked_rom;
+
+    #[test]
+    fn test_default_banks() {
+        let mbc = Mbc1::new(banked_rom(4), 0);
+        assert_eq!(mbc.read(0x0000), 0);
+        assert_eq!(mbc.read(0x4000), 1);
+    }
+
+    #[test]
+    fn test_rom_bank_switching() {
+        let mut mbc = Mbc1::new(banked_rom(8), 0);
+        mbc.write(0x2000, 0x05);
+        assert_eq!(mbc.read(0x4000), 5);
+
+        // Bank numbers wrap around the number of banks on the cart.
+        mbc.write(0x2000, 0x0B);
+        assert_eq!(mbc.read(0x4000), 3);
+    }
+
+    #[test]
+    fn test_bank_zero_quirk() {
+        let mut mbc = Mbc1::new(banked_rom(128), 0);
+        mbc.write(0x2000, 0x00);
+        assert_eq!(mbc.read(0x4000), 1, "Bank 0 should map to bank 1");
+
+        // Only the 5-bit register is checked, so 0x20 becomes 0x21.
+        mbc.write(0x4000, 0x01);
+        mbc.write(0x2000, 0x00);
+        assert_eq!(mbc.read(0x4000), 0x21);
+    }
+
+    #[test]
+    fn test_mode_1_remaps_low_area() {
+        let mut mbc = Mbc1::new(banked_rom(128), 0);
+        mbc.write(0x4000, 0x02);
+        assert_eq!(mbc.read(0x0000), 0, "Mode 0 always maps bank 0 low");
+
+        mbc.write(0x6000, 0x01);
+        assert_eq!(mbc.read(0x0000), 0x40);
+        assert_eq!(mbc.read(0x4000), 0x41);
+    }
+
+    #[test]
+    fn test_ram_enable_gate() {
+        let mut mbc = Mbc1::new(banked_rom(2), 0x2000);
+        mbc.write(0xA000, 0x42);
+        assert_eq!(mbc.read(0xA000), 0xFF, "Disabled RAM should read 0xFF");
+
+        mbc.write(0x0000, 0x0A);
+        mbc.write(0xA000, 0x42);
+        assert_eq!(mbc.read(0xA000), 0x42);
+
+        mbc.write(0x0000, 0x00);
+        assert_eq!(mbc.read(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn test_ram_banking_requires_mode_1() {
+        let mut mbc = Mbc1::new(banked_rom(2), 0x8000);
+        mbc.write(0x0000, 0x0A);
+        mbc.write(0xA000, 0x11);
+
+        mbc.write(0x4000, 0x02);
+        assert_eq!(mbc.read(0xA000), 0x11, "Mode 0 always maps RAM bank 0");
+
+        mbc.write(0x6000, 0x01);
+        mbc.write(0xA000, 0x22);
+        assert_eq!(mbc.read(0xA000), 0x22);
+
+        mbc.write(0x4000, 0x00);
+        assert_eq!(mbc.read(0xA000), 0x11);
+    }
+}
@@ -0,0 +1,170 @@
+/*
+MBC2, source: https://gbdev.io/pandocs/MBC2.html
+
+Address Range,Register,Purpose
+0x0000–0x3FFF,RAM Enable / ROM Bank,Address bit 8 clear: 0xA in the lower nibble enables RAM. Set: 4-bit ROM bank, 0 is treated as 1.
+0xA000–0xA1FF,Built-in RAM,512 half-bytes, only the lower nibble is stored.
+0xA200–0xBFFF,Echo,The built-in RAM repeats every 512 bytes.
+*/
+
+use crate::cartridge::mbc_trait::Mbc;
+use crate::cartridge::mbc1::ROM_BANK_SIZE;
+use crate::state::{SaveState, StateError, StateReader, StateWriter};
+
+const RAM_SIZE: usize = 0x200;
+
+pub struct Mbc2 {
+    rom: Vec<u8>,
+    /// Only the lower nibble of each cell exists on the chip.
+    ram: [u8; RAM_SIZE],
+    ram_enabled: bool,
+    rom_bank: u8,
+}
+
+impl Mbc2 {
+    pub fn new(mut content: Vec<u8>) -> Self {
+        let rom_size = content.len().max(2 * ROM_BANK_SIZE).next_power_of_two();
+        content.resize(rom_size, 0xFF);
+
+        Self {
+            rom: content,
+            ram: [0; RAM_SIZE],
+            ram_enabled: false,
+            rom_bank: 1,
+        }
+    }
+
+    fn rom_offset(&self, addr: u16) -> usize {
+        match addr {
+            0x0000..=0x3FFF => addr as usize,
+            _ => {
+                let bank = self.rom_bank as usize % (self.rom.len() / ROM_BANK_SIZE);
+                bank * ROM_BANK_SIZE + (addr as usize - ROM_BANK_SIZE)
+            }
+        }
+    }
+
+    /// Only the bottom 9 address bits are decoded, hence the echoes.
+    fn ram_offset(addr: u16) -> usize {
+        (addr as usize - 0xA000) % RAM_SIZE
+    }
+}
+
+impl SaveState for Mbc2 {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.ram);
+        w.bool(self.ram_enabled);
+        w.u8(self.rom_bank);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        r.bytes_into(&mut self.ram)?;
+        self.ram_enabled = r.bool()?;
+        self.rom_bank = r.u8()?;
+        Ok(())
+    }
+}
+
+impl Mbc for Mbc2 {
+    fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x7FFF => self.rom[self.rom_offset(addr)],
+            // The upper nibble isn't wired up and floats high.
+            0xA000..=0xBFFF if self.ram_enabled => self.ram[Self::ram_offset(addr)] | 0xF0,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x3FFF => {
+                if addr & 0x0100 == 0 {
+                    self.ram_enabled = (val & 0x0F) == 0x0A;
+                } else {
+                    let bank = val & 0x0F;
+                    self.rom_bank = if bank == 0 { 1 } else { bank };
+                }
+            }
+            0xA000..=0xBFFF if self.ram_enabled => {
+                self.ram[Self::ram_offset(addr)] = val & 0x0F;
+            }
+            _ => {}
+        }
+    }
+
+    fn force_write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x7FFF => {
+                let offset = self.rom_offset(addr);
+                self.rom[offset] = val;
+            }
+            _ => self.write(addr, val),
+        }
+    }
+
+    /// One byte per cell, the format other emulators use for MBC2 saves.
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::banked_rom;
+
+    #[test]
+    fn test_register_select_by_address_bit_8() {
+        let mut mbc = Mbc2::new(banked_rom(16));
+        mbc.write(0x2100, 0x05);
+        assert_eq!(mbc.read(0x4000), 5);
+
+        // Bit 8 clear goes to RAM enable, the bank stays put.
+        mbc.write(0x2000, 0x0A);
+        assert_eq!(mbc.read(0x4000), 5);
+        mbc.write(0xA000, 0x03);
+        assert_eq!(mbc.read(0xA000), 0xF3);
+
+        mbc.write(0x0100, 0x00);
+        assert_eq!(mbc.read(0x4000), 1, "Bank 0 should map to bank 1");
+    }
+
+    #[test]
+    fn test_half_byte_ram() {
+        let mut mbc = Mbc2::new(banked_rom(2));
+        mbc.write(0x0000, 0x0A);
+        mbc.write(0xA010, 0xAB);
+        assert_eq!(
+            mbc.read(0xA010),
+            0xFB,
+            "Upper nibble should read back as 1s"
+        );
+    }
+
+    #[test]
+    fn test_ram_echo() {
+        let mut mbc = Mbc2::new(banked_rom(2));
+        mbc.write(0x0000, 0x0A);
+        mbc.write(0xA005, 0x07);
+        for base in (0xA000..0xC000).step_by(RAM_SIZE) {
+            assert_eq!(mbc.read(base as u16 + 5), 0xF7, "Echo at {:04X}", base);
+        }
+
+        mbc.write(0xBFFF, 0x0C);
+        assert_eq!(mbc.read(0xA1FF), 0xFC);
+    }
+
+    #[test]
+    fn test_ram_disabled() {
+        let mut mbc = Mbc2::new(banked_rom(2));
+        mbc.write(0xA000, 0x05);
+        assert_eq!(mbc.read(0xA000), 0xFF);
+
+        mbc.write(0x0000, 0x0A);
+        assert_eq!(mbc.read(0xA000), 0xF0, "Disabled writes should be dropped");
+    }
+}
@@ -0,0 +1,253 @@
+/*
+MBC3, source: https://gbdev.io/pandocs/MBC3.html
+
+Address Range,Register,Purpose
+0x0000–0x1FFF,RAM and Timer Enable,0x0A enables both external RAM and the RTC registers.
+0x2000–0x3FFF,ROM Bank Number,7-bit register, selects the bank mapped at 0x4000–0x7FFF. 0 is treated as 1.
+0x4000–0x5FFF,RAM Bank Number / RTC Register Select,0x00-0x03 maps a RAM bank, 0x08-0x0C maps an RTC register.
+0x6000–0x7FFF,Latch Clock Data,Writing 0x00 then 0x01 copies the live clock into the readable registers.
+*/
+
+use crate::cartridge::clock_trait::ClockSource;
+use crate::cartridge::mbc_trait::Mbc;
+use crate::cartridge::mbc1::{RAM_BANK_SIZE, ROM_BANK_SIZE};
+use crate::cartridge::rtc::{Rtc, SystemClock};
+use crate::state::{SaveState, StateError, StateReader, StateWriter};
+
+pub struct Mbc3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    pub rtc: Option<Rtc>,
+    ram_enabled: bool,
+    rom_bank: u8,
+    /// Either a RAM bank (0x00-0x03) or an RTC register (0x08-0x0C).
+    ram_select: u8,
+}
+
+impl Mbc3 {
+    pub fn new(mut content: Vec<u8>, ram_size: usize, has_rtc: bool) -> Self {
+        let rom_size = content.len().max(2 * ROM_BANK_SIZE).next_power_of_two();
+        content.resize(rom_size, 0xFF);
+
+        Self {
+            rom: content,
+            ram: vec![0; ram_size],
+            rtc: has_rtc.then(|| Rtc::new(Box::new(SystemClock))),
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_select: 0,
+        }
+    }
+
+    /// Replaces the time source of the RTC, e.g. to drive it from a test.
+    /// Does nothing for carts without a clock.
+    pub fn with_clock(mut self, clock: Box<dyn ClockSource>) -> Self {
+        if self.rtc.is_some() {
+            self.rtc = Some(Rtc::new(clock));
+        }
+        self
+    }
+
+    fn rom_offset(&self, addr: u16) -> usize {
+        match addr {
+            0x0000..=0x3FFF => addr as usize,
+            _ => {
+                let bank = self.rom_bank as usize % (self.rom.len() / ROM_BANK_SIZE);
+                bank * ROM_BANK_SIZE + (addr as usize - ROM_BANK_SIZE)
+            }
+        }
+    }
+
+    fn ram_offset(&self, addr: u16) -> Option<usize> {
+        if !self.ram_enabled || self.ram.is_empty() || self.ram_select > 0x03 {
+            return None;
+        }
+        let offset = self.ram_select as usize * RAM_BANK_SIZE + (addr - 0xA000) as usize;
+        Some(offset % self.ram.len())
+    }
+
+    fn rtc_selected(&self) -> bool {
+        (0x08..=0x0C).contains(&self.ram_select)
+    }
+}
+
+impl SaveState for Mbc3 {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.ram);
+        w.bool(self.ram_enabled);
+        w.u8(self.rom_bank);
+        w.u8(self.ram_select);
+        if let Some(rtc) = &self.rtc {
+            rtc.save_state(w);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        r.bytes_into(&mut self.ram)?;
+        self.ram_enabled = r.bool()?;
+        self.rom_bank = r.u8()?;
+        self.ram_select = r.u8()?;
+        if let Some(rtc) = &mut self.rtc {
+            rtc.load_state(r)?;
+        }
+        Ok(())
+    }
+}
+
+impl Mbc for Mbc3 {
+    fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x7FFF => self.rom[self.rom_offset(addr)],
+            0xA000..=0xBFFF => {
+                if self.ram_enabled && self.rtc_selected() {
+                    return match &self.rtc {
+                        Some(rtc) => rtc.read(self.ram_select),
+                        None => 0xFF,
+                    };
+                }
+                match self.ram_offset(addr) {
+                    Some(offset) => self.ram[offset],
+                    None => 0xFF,
+                }
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = (val & 0x0F) == 0x0A,
+            0x2000..=0x3FFF => {
+                let bank = val & 0x7F;
+                self.rom_bank = if bank == 0 { 1 } else { bank };
+            }
+            0x4000..=0x5FFF => self.ram_select = val,
+            0x6000..=0x7FFF => {
+                if let Some(rtc) = &mut self.rtc {
+                    rtc.write_latch(val);
+                }
+            }
+            0xA000..=0xBFFF => {
+                if self.ram_enabled && self.rtc_selected() {
+                    if let Some(rtc) = &mut self.rtc {
+                        rtc.write(self.ram_select, val);
+                    }
+                } else if let Some(offset) = self.ram_offset(addr) {
+                    self.ram[offset] = val;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn force_write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x7FFF => {
+                let offset = self.rom_offset(addr);
+                self.rom[offset] = val;
+            }
+            _ => self.write(addr, val),
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn rtc(&self) -> Option<&Rtc> {
+        self.rtc.as_ref()
+    }
+
+    fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        self.rtc.as_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::banked_rom;
+    use crate::cartridge::rtc::tests::ManualClock;
+
+    #[test]
+    fn test_seven_bit_rom_bank() {
+        let mut mbc = Mbc3::new(banked_rom(128), 0, false);
+        mbc.write(0x2000, 0x45);
+        assert_eq!(mbc.read(0x4000), 0x45);
+
+        mbc.write(0x2000, 0x00);
+        assert_eq!(mbc.read(0x4000), 1, "Bank 0 should map to bank 1");
+        assert_eq!(mbc.read(0x0000), 0, "Low area is always bank 0");
+    }
+
+    #[test]
+    fn test_ram_banks() {
+        let mut mbc = Mbc3::new(banked_rom(2), 0x8000, false);
+        mbc.write(0x0000, 0x0A);
+        for bank in 0..4 {
+            mbc.write(0x4000, bank);
+            mbc.write(0xA000, 0x10 + bank);
+        }
+        for bank in 0..4 {
+            mbc.write(0x4000, bank);
+            assert_eq!(mbc.read(0xA000), 0x10 + bank);
+        }
+    }
+
+    #[test]
+    fn test_rtc_register_mapping() {
+        let clock = ManualClock::default();
+        let mut mbc = Mbc3::new(banked_rom(2), 0x2000, true).with_clock(Box::new(clock.clone()));
+        mbc.write(0x0000, 0x0A);
+
+        // 1 day, 2 hours, 3 minutes and 4 seconds.
+        clock.advance(((24 + 2) * 60 + 3) * 60 + 4);
+        mbc.write(0x6000, 0x00);
+        mbc.write(0x6000, 0x01);
+
+        let expected = [(0x08, 4), (0x09, 3), (0x0A, 2), (0x0B, 1), (0x0C, 0)];
+        for (reg, val) in expected {
+            mbc.write(0x4000, reg);
+            assert_eq!(mbc.read(0xA000), val, "RTC register {:02X}", reg);
+        }
+
+        // Selecting a RAM bank again gets us back to RAM.
+        mbc.write(0x4000, 0x00);
+        mbc.write(0xA000, 0x77);
+        assert_eq!(mbc.read(0xA000), 0x77);
+    }
+
+    #[test]
+    fn test_rtc_write_through_bus_window() {
+        let clock = ManualClock::default();
+        let mut mbc = Mbc3::new(banked_rom(2), 0, true).with_clock(Box::new(clock.clone()));
+        mbc.write(0x0000, 0x0A);
+
+        mbc.write(0x4000, 0x0A);
+        mbc.write(0xA000, 23);
+        mbc.write(0x4000, 0x09);
+        mbc.write(0xA000, 59);
+        mbc.write(0x4000, 0x08);
+        mbc.write(0xA000, 59);
+
+        clock.advance(1);
+        mbc.write(0x6000, 0x00);
+        mbc.write(0x6000, 0x01);
+
+        mbc.write(0x4000, 0x0A);
+        assert_eq!(mbc.read(0xA000), 0, "Hours should roll over");
+        mbc.write(0x4000, 0x0B);
+        assert_eq!(mbc.read(0xA000), 1, "Day counter should increment");
+    }
+
+    #[test]
+    fn test_rtc_disabled_reads_open_bus() {
+        let mut mbc = Mbc3::new(banked_rom(2), 0, true);
+        mbc.write(0x4000, 0x08);
+        assert_eq!(mbc.read(0xA000), 0xFF);
+    }
+}
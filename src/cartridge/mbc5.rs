@@ -0,0 +1,194 @@
+/*
+MBC5, source: https://gbdev.io/pandocs/MBC5.html
+
+Address Range,Register,Purpose
+0x0000–0x1FFF,RAM Enable,0x0A enables external RAM, anything else disables it.
+0x2000–0x2FFF,ROM Bank (low),Lower 8 bits of the 9-bit ROM bank number. Bank 0 is allowed.
+0x3000–0x3FFF,ROM Bank (high),Bit 8 of the ROM bank number.
+0x4000–0x5FFF,RAM Bank,0x00-0x0F, on rumble carts bit 3 drives the motor instead.
+*/
+
+use crate::cartridge::mbc_trait::Mbc;
+use crate::cartridge::mbc1::{RAM_BANK_SIZE, ROM_BANK_SIZE};
+use crate::state::{SaveState, StateError, StateReader, StateWriter};
+
+const RUMBLE_BIT: u8 = 0x08;
+
+pub struct Mbc5 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    rom_bank: u16,
+    ram_bank: u8,
+    has_rumble: bool,
+    rumble_active: bool,
+}
+
+impl Mbc5 {
+    pub fn new(mut content: Vec<u8>, ram_size: usize, has_rumble: bool) -> Self {
+        let rom_size = content.len().max(2 * ROM_BANK_SIZE).next_power_of_two();
+        content.resize(rom_size, 0xFF);
+
+        Self {
+            rom: content,
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            has_rumble,
+            rumble_active: false,
+        }
+    }
+
+    fn rom_offset(&self, addr: u16) -> usize {
+        match addr {
+            0x0000..=0x3FFF => addr as usize,
+            _ => {
+                let bank = self.rom_bank as usize % (self.rom.len() / ROM_BANK_SIZE);
+                bank * ROM_BANK_SIZE + (addr as usize - ROM_BANK_SIZE)
+            }
+        }
+    }
+
+    fn ram_offset(&self, addr: u16) -> Option<usize> {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return None;
+        }
+        let offset = self.ram_bank as usize * RAM_BANK_SIZE + (addr - 0xA000) as usize;
+        Some(offset % self.ram.len())
+    }
+}
+
+impl SaveState for Mbc5 {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.ram);
+        w.bool(self.ram_enabled);
+        w.u16(self.rom_bank);
+        w.u8(self.ram_bank);
+        w.bool(self.rumble_active);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        r.bytes_into(&mut self.ram)?;
+        self.ram_enabled = r.bool()?;
+        self.rom_bank = r.u16()?;
+        self.ram_bank = r.u8()?;
+        self.rumble_active = r.bool()?;
+        Ok(())
+    }
+}
+
+impl Mbc for Mbc5 {
+    fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x7FFF => self.rom[self.rom_offset(addr)],
+            0xA000..=0xBFFF => match self.ram_offset(addr) {
+                Some(offset) => self.ram[offset],
+                None => 0xFF,
+            },
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = val == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | val as u16,
+            0x3000..=0x3FFF => self.rom_bank = (self.rom_bank & 0xFF) | ((val as u16 & 0x01) << 8),
+            0x4000..=0x5FFF => {
+                if self.has_rumble {
+                    // The motor is wired to bit 3, leaving 8 selectable RAM banks.
+                    self.rumble_active = (val & RUMBLE_BIT) != 0;
+                    self.ram_bank = val & 0x07;
+                } else {
+                    self.ram_bank = val & 0x0F;
+                }
+            }
+            0xA000..=0xBFFF => {
+                if let Some(offset) = self.ram_offset(addr) {
+                    self.ram[offset] = val;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn force_write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x7FFF => {
+                let offset = self.rom_offset(addr);
+                self.rom[offset] = val;
+            }
+            _ => self.write(addr, val),
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn rumble(&self) -> bool {
+        self.rumble_active
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::banked_rom;
+
+    #[test]
+    fn test_nine_bit_rom_bank() {
+        let mut mbc = Mbc5::new(banked_rom(512), 0, false);
+        mbc.write(0x2000, 0x23);
+        mbc.write(0x3000, 0x01);
+        assert_eq!(mbc.read(0x4000), 0x23);
+        assert_eq!(mbc.read(0x4001), 0x01, "Bit 8 should select the upper half");
+    }
+
+    #[test]
+    fn test_bank_zero_is_allowed() {
+        let mut mbc = Mbc5::new(banked_rom(4), 0, false);
+        mbc.write(0x2000, 0x00);
+        assert_eq!(mbc.read(0x4000), 0, "MBC5 has no bank 0 quirk");
+    }
+
+    #[test]
+    fn test_sixteen_ram_banks() {
+        let mut mbc = Mbc5::new(banked_rom(2), 0x20000, false);
+        mbc.write(0x0000, 0x0A);
+        for bank in 0..16 {
+            mbc.write(0x4000, bank);
+            mbc.write(0xA000, 0x80 | bank);
+        }
+        for bank in 0..16 {
+            mbc.write(0x4000, bank);
+            assert_eq!(mbc.read(0xA000), 0x80 | bank);
+        }
+    }
+
+    #[test]
+    fn test_rumble_motor() {
+        let mut mbc = Mbc5::new(banked_rom(2), 0x8000, true);
+        mbc.write(0x0000, 0x0A);
+        mbc.write(0xA000, 0x11);
+
+        mbc.write(0x4000, RUMBLE_BIT);
+        assert!(mbc.rumble());
+        assert_eq!(mbc.read(0xA000), 0x11, "Rumble bit must not switch banks");
+
+        mbc.write(0x4000, 0x00);
+        assert!(!mbc.rumble());
+    }
+
+    #[test]
+    fn test_no_rumble_without_motor() {
+        let mut mbc = Mbc5::new(banked_rom(2), 0x20000, false);
+        mbc.write(0x4000, RUMBLE_BIT);
+        assert!(!mbc.rumble());
+    }
+}
@@ -1,4 +1,39 @@
-pub trait Mbc {
+use crate::cartridge::Rtc;
+use crate::state::SaveState;
+
+/// A Memory Bank Controller sits between the CPU and the cartridge ROM/RAM.
+/// The bus routes 0x0000–0x7FFF (ROM + bank registers) and 0xA000–0xBFFF
+/// (external RAM) through it. The ROM itself is not part of a save state,
+/// only the bank registers and RAM are.
+pub trait Mbc: SaveState {
     fn read(&self, addr: u16) -> u8;
     fn write(&mut self, addr: u16, val: u8);
+
+    /// Writes straight into the ROM image (as currently mapped), bypassing
+    /// the bank registers. Used by tests and tooling to plant code in the
+    /// cartridge area, writes to RAM behave like a normal write.
+    fn force_write(&mut self, addr: u16, val: u8);
+
+    /// Whether the cartridge's rumble motor is currently spinning.
+    fn rumble(&self) -> bool {
+        false
+    }
+
+    /// External RAM, in the layout it is persisted to a `.sav` file.
+    fn ram(&self) -> &[u8] {
+        &[]
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
+
+    /// The cartridge's real-time clock, if it has one.
+    fn rtc(&self) -> Option<&Rtc> {
+        None
+    }
+
+    fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        None
+    }
 }
@@ -1,14 +1,40 @@
 mod cartridge;
+mod clock_trait;
 mod error;
 mod header;
 mod loader;
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
 mod mbc_trait;
 mod rom;
+mod rtc;
+mod save;
 mod validation;
 
 pub use cartridge::Cartridge;
+pub use clock_trait::ClockSource;
 pub use error::LoadError;
 pub use header::Headers;
-pub use loader::load_rom;
+pub use loader::{load_boot_rom, load_rom};
 pub use mbc_trait::Mbc;
+pub use mbc1::Mbc1;
+pub use mbc2::Mbc2;
+pub use mbc3::Mbc3;
+pub use mbc5::Mbc5;
 pub use rom::RomOnly;
+pub use rtc::{RTC_FOOTER_LEN, Rtc, RtcRegisters, SystemClock};
+pub use save::SaveFile;
+
+/// Builds a ROM where the first two bytes of every bank hold its bank number,
+/// little endian.
+#[cfg(test)]
+pub(crate) fn banked_rom(banks: usize) -> Vec<u8> {
+    let mut rom = vec![0; banks * mbc1::ROM_BANK_SIZE];
+    for bank in 0..banks {
+        let start = bank * mbc1::ROM_BANK_SIZE;
+        rom[start..start + 2].copy_from_slice(&(bank as u16).to_le_bytes());
+    }
+    rom
+}
@@ -1,22 +1,67 @@
 use crate::cartridge::mbc_trait::Mbc;
+use crate::state::{SaveState, StateError, StateReader, StateWriter};
+
+/// Smallest cartridge: two fixed 16 KiB banks.
+const ROM_SIZE: usize = 0x8000;
+/// Carts without an MBC may still wire up to 8 KiB of RAM directly.
+const RAM_SIZE: usize = 0x2000;
 
 /// Holds the content of the rom, As to load it in to memory.
 pub struct RomOnly {
     data: Vec<u8>,
+    ram: Vec<u8>,
 }
 
 impl RomOnly {
-    pub fn new(content: Vec<u8>) -> Self {
-        Self { data: content }
+    pub fn new(mut content: Vec<u8>) -> Self {
+        // Pad small (test) images, so every address in the ROM area is readable.
+        if content.len() < ROM_SIZE {
+            content.resize(ROM_SIZE, 0);
+        }
+        Self {
+            data: content,
+            ram: vec![0; RAM_SIZE],
+        }
+    }
+}
+
+impl SaveState for RomOnly {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.ram);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        r.bytes_into(&mut self.ram)?;
+        Ok(())
     }
 }
 
 impl Mbc for RomOnly {
     fn read(&self, addr: u16) -> u8 {
-        self.data[addr as usize]
+        match addr {
+            0x0000..=0x7FFF => self.data[addr as usize],
+            0xA000..=0xBFFF => self.ram[(addr - 0xA000) as usize],
+            _ => 0xFF,
+        }
     }
-    fn write(&mut self, _addr: u16, _val: u8) {
+    fn write(&mut self, addr: u16, val: u8) {
         // You can't write to ROM!
-        // Maybe panic?
+        if let 0xA000..=0xBFFF = addr {
+            self.ram[(addr - 0xA000) as usize] = val;
+        }
+    }
+    fn force_write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x7FFF => self.data[addr as usize] = val,
+            _ => self.write(addr, val),
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
     }
 }
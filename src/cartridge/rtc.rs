@@ -0,0 +1,402 @@
+/*
+MBC3 Real Time Clock, source: https://gbdev.io/pandocs/MBC3.html
+
+Register,Name,Range
+0x08,RTC S,Seconds 0-59
+0x09,RTC M,Minutes 0-59
+0x0A,RTC H,Hours 0-23
+0x0B,RTC DL,Lower 8 bits of the day counter
+0x0C,RTC DH,Bit 0: day counter bit 8, Bit 6: halt, Bit 7: day counter carry
+
+Save footer, appended to the RAM in .sav files (the VBA-M / BGB layout), all little endian:
+
+Offset,Size,Content
+0x00,5 x u32,Live S M H DL DH
+0x14,5 x u32,Latched S M H DL DH
+0x28,u64,Unix timestamp of the save, older emulators write a u32 for a 44 byte footer
+*/
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::cartridge::clock_trait::ClockSource;
+use crate::state::{SaveState, StateError, StateReader, StateWriter};
+
+const DH_DAY_HIGH: u8 = 0x01;
+const DH_HALT: u8 = 0x40;
+const DH_CARRY: u8 = 0x80;
+
+pub const RTC_FOOTER_LEN: usize = 48;
+/// Older footer with a 32-bit timestamp, still accepted on load.
+pub const RTC_FOOTER_LEN_LEGACY: usize = 44;
+const FOOTER_REGS_LEN: usize = 40;
+
+/// Reads the host's wall clock.
+#[derive(Default)]
+pub struct SystemClock;
+
+impl ClockSource for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+}
+
+/// The five clock registers as seen by the game.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RtcRegisters {
+    pub seconds: u8,
+    pub minutes: u8,
+    pub hours: u8,
+    pub days_low: u8,
+    pub days_high: u8,
+}
+
+impl RtcRegisters {
+    pub fn read(&self, reg: u8) -> u8 {
+        match reg {
+            0x08 => self.seconds,
+            0x09 => self.minutes,
+            0x0A => self.hours,
+            0x0B => self.days_low,
+            0x0C => self.days_high,
+            _ => 0xFF,
+        }
+    }
+
+    fn to_footer(self) -> [u32; 5] {
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.days_low,
+            self.days_high,
+        ]
+        .map(u32::from)
+    }
+
+    fn from_footer(words: &[u32]) -> Self {
+        Self {
+            seconds: words[0] as u8 & 0x3F,
+            minutes: words[1] as u8 & 0x3F,
+            hours: words[2] as u8 & 0x1F,
+            days_low: words[3] as u8,
+            days_high: words[4] as u8 & (DH_DAY_HIGH | DH_HALT | DH_CARRY),
+        }
+    }
+
+    fn days(&self) -> u16 {
+        (((self.days_high & DH_DAY_HIGH) as u16) << 8) | self.days_low as u16
+    }
+
+    /// Advances the clock, carrying over into minutes, hours and days.
+    /// The day counter is 9 bits, overflowing sets the sticky carry bit.
+    fn advance(&mut self, secs: u64) {
+        let total = self.seconds as u64 + secs;
+        self.seconds = (total % 60) as u8;
+
+        let total = self.minutes as u64 + total / 60;
+        self.minutes = (total % 60) as u8;
+
+        let total = self.hours as u64 + total / 60;
+        self.hours = (total % 24) as u8;
+
+        let days = self.days() as u64 + total / 24;
+        if days > 0x1FF {
+            self.days_high |= DH_CARRY;
+        }
+        let days = (days & 0x1FF) as u16;
+        self.days_low = days as u8;
+        self.days_high = (self.days_high & !DH_DAY_HIGH) | (days >> 8) as u8;
+    }
+}
+
+impl SaveState for RtcRegisters {
+    fn save_state(&self, w: &mut StateWriter) {
+        for reg in [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.days_low,
+            self.days_high,
+        ] {
+            w.u8(reg);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        for reg in [
+            &mut self.seconds,
+            &mut self.minutes,
+            &mut self.hours,
+            &mut self.days_low,
+            &mut self.days_high,
+        ] {
+            *reg = r.u8()?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Rtc {
+    /// Live counter, keeps ticking unless halted.
+    pub live: RtcRegisters,
+    /// Snapshot copied on latch, this is what the game reads.
+    pub latched: RtcRegisters,
+    /// Clock time the live registers were last brought up to date.
+    pub last_update: u64,
+    /// Last value written to the latch register, latching needs 0x00 -> 0x01.
+    latch_write: u8,
+    clock: Box<dyn ClockSource>,
+}
+
+impl Rtc {
+    pub fn new(clock: Box<dyn ClockSource>) -> Self {
+        Self {
+            live: RtcRegisters::default(),
+            latched: RtcRegisters::default(),
+            last_update: clock.now(),
+            latch_write: 0xFF,
+            clock,
+        }
+    }
+
+    pub fn halted(&self) -> bool {
+        (self.live.days_high & DH_HALT) != 0
+    }
+
+    /// Catches the live registers up with the clock source.
+    pub fn update(&mut self) {
+        let now = self.clock.now();
+        if !self.halted() {
+            self.live.advance(now.saturating_sub(self.last_update));
+        }
+        self.last_update = now;
+    }
+
+    pub fn read(&self, reg: u8) -> u8 {
+        self.latched.read(reg)
+    }
+
+    pub fn write(&mut self, reg: u8, val: u8) {
+        self.update();
+        let regs = &mut self.live;
+        match reg {
+            0x08 => regs.seconds = val & 0x3F,
+            0x09 => regs.minutes = val & 0x3F,
+            0x0A => regs.hours = val & 0x1F,
+            0x0B => regs.days_low = val,
+            0x0C => regs.days_high = val & (DH_DAY_HIGH | DH_HALT | DH_CARRY),
+            _ => {}
+        }
+        // Games commonly write then read back without latching in between.
+        self.latched = self.live;
+    }
+
+    pub fn write_latch(&mut self, val: u8) {
+        if self.latch_write == 0x00 && val == 0x01 {
+            self.update();
+            self.latched = self.live;
+        }
+        self.latch_write = val;
+    }
+
+    /// The live registers are valid as of `last_update`, so they can be
+    /// saved as-is without asking the clock for the time.
+    pub fn footer(&self) -> [u8; RTC_FOOTER_LEN] {
+        let mut footer = [0; RTC_FOOTER_LEN];
+        let words = self
+            .live
+            .to_footer()
+            .into_iter()
+            .chain(self.latched.to_footer());
+        for (chunk, word) in footer.chunks_exact_mut(4).zip(words) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        footer[FOOTER_REGS_LEN..].copy_from_slice(&self.last_update.to_le_bytes());
+        footer
+    }
+
+    /// Restores the clock from a save footer and catches it up with the
+    /// time that passed since the save was written. Returns false if the
+    /// footer has an unknown length.
+    pub fn load_footer(&mut self, footer: &[u8]) -> bool {
+        let timestamp = match footer.len() {
+            RTC_FOOTER_LEN => u64::from_le_bytes(footer[FOOTER_REGS_LEN..].try_into().unwrap()),
+            RTC_FOOTER_LEN_LEGACY => {
+                u32::from_le_bytes(footer[FOOTER_REGS_LEN..].try_into().unwrap()) as u64
+            }
+            _ => return false,
+        };
+        let words: Vec<u32> = footer[..FOOTER_REGS_LEN]
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+
+        self.live = RtcRegisters::from_footer(&words[..5]);
+        self.latched = RtcRegisters::from_footer(&words[5..]);
+        self.last_update = timestamp;
+        self.update();
+        true
+    }
+}
+
+/// The wall clock timestamp is kept, so a loaded state catches up with
+/// the time that passed since it was taken, like the cartridge would.
+impl SaveState for Rtc {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.live.save_state(w);
+        self.latched.save_state(w);
+        w.u64(self.last_update);
+        w.u8(self.latch_write);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.live.load_state(r)?;
+        self.latched.load_state(r)?;
+        self.last_update = r.u64()?;
+        self.latch_write = r.u8()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// Clock that only moves when the test says so.
+    #[derive(Clone, Default)]
+    pub struct ManualClock(pub Rc<Cell<u64>>);
+
+    impl ManualClock {
+        pub fn advance(&self, secs: u64) {
+            self.0.set(self.0.get() + secs);
+        }
+    }
+
+    impl ClockSource for ManualClock {
+        fn now(&self) -> u64 {
+            self.0.get()
+        }
+    }
+
+    fn latch(rtc: &mut Rtc) {
+        rtc.write_latch(0x00);
+        rtc.write_latch(0x01);
+    }
+
+    #[test]
+    fn test_latch_snapshots_time() {
+        let clock = ManualClock::default();
+        let mut rtc = Rtc::new(Box::new(clock.clone()));
+
+        clock.advance(61);
+        assert_eq!(rtc.read(0x08), 0, "Reads should not move before latching");
+
+        latch(&mut rtc);
+        assert_eq!(rtc.read(0x08), 1);
+        assert_eq!(rtc.read(0x09), 1);
+
+        clock.advance(5);
+        assert_eq!(rtc.read(0x08), 1, "Latched value should stay frozen");
+    }
+
+    #[test]
+    fn test_latch_requires_zero_then_one() {
+        let clock = ManualClock::default();
+        let mut rtc = Rtc::new(Box::new(clock.clone()));
+
+        clock.advance(10);
+        rtc.write_latch(0x01);
+        assert_eq!(rtc.read(0x08), 0);
+    }
+
+    #[test]
+    fn test_halt_stops_the_clock() {
+        let clock = ManualClock::default();
+        let mut rtc = Rtc::new(Box::new(clock.clone()));
+
+        rtc.write(0x0C, DH_HALT);
+        clock.advance(3600);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(0x0A), 0, "Halted clock should not advance");
+
+        rtc.write(0x0C, 0x00);
+        clock.advance(3600);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(0x0A), 1);
+    }
+
+    #[test]
+    fn test_day_counter_carry() {
+        let clock = ManualClock::default();
+        let mut rtc = Rtc::new(Box::new(clock.clone()));
+
+        rtc.write(0x0B, 0xFF);
+        rtc.write(0x0C, DH_DAY_HIGH); // Day 511
+        clock.advance(24 * 60 * 60);
+        latch(&mut rtc);
+
+        assert_eq!(rtc.read(0x0B), 0x00);
+        assert_eq!(rtc.read(0x0C), DH_CARRY, "Day 512 wraps and sets carry");
+
+        // The carry bit is sticky until the game clears it.
+        clock.advance(24 * 60 * 60);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(0x0C), DH_CARRY);
+        assert_eq!(rtc.read(0x0B), 0x01);
+    }
+
+    #[test]
+    fn test_footer_round_trip() {
+        let clock = ManualClock::default();
+        clock.advance(1_000_000);
+        let mut rtc = Rtc::new(Box::new(clock.clone()));
+        rtc.write(0x0A, 5);
+        rtc.write(0x0B, 0x20);
+
+        let footer = rtc.footer();
+        assert_eq!(footer.len(), RTC_FOOTER_LEN);
+        assert_eq!(&footer[0x08..0x0C], &[5, 0, 0, 0], "Live hours");
+        assert_eq!(&footer[0x28..], &1_000_000u64.to_le_bytes());
+
+        let mut restored = Rtc::new(Box::new(clock.clone()));
+        assert!(restored.load_footer(&footer));
+        assert_eq!(restored.live, rtc.live);
+        assert_eq!(restored.latched, rtc.latched);
+    }
+
+    #[test]
+    fn test_footer_advances_by_elapsed_time() {
+        let clock = ManualClock::default();
+        let rtc = Rtc::new(Box::new(clock.clone()));
+        let footer = rtc.footer();
+
+        // The emulator was closed for 2 hours.
+        clock.advance(2 * 60 * 60);
+        let mut restored = Rtc::new(Box::new(clock.clone()));
+        assert!(restored.load_footer(&footer));
+        latch(&mut restored);
+        assert_eq!(restored.read(0x0A), 2);
+    }
+
+    #[test]
+    fn test_legacy_footer() {
+        let clock = ManualClock::default();
+        clock.advance(500);
+        let mut footer = [0u8; RTC_FOOTER_LEN_LEGACY];
+        footer[0x00] = 30; // Live seconds
+        footer[0x28..].copy_from_slice(&490u32.to_le_bytes());
+
+        let mut rtc = Rtc::new(Box::new(clock.clone()));
+        assert!(rtc.load_footer(&footer));
+        assert_eq!(rtc.live.seconds, 40);
+        assert!(
+            !rtc.load_footer(&footer[..40]),
+            "Unknown footer sizes are rejected"
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+
+use crate::cartridge::{Cartridge, RtcRegisters};
+
+/// Persists battery-backed cartridge RAM to a `.sav` file next to the ROM.
+/// Carts with a clock get the RTC footer appended after the RAM.
+pub struct SaveFile {
+    path: PathBuf,
+    /// Loads the save but never writes it back, so test runs can't clobber it.
+    read_only: bool,
+    /// What is on disk, used to skip flushes when the game hasn't saved.
+    last_written: Vec<u8>,
+    last_clock: Option<(RtcRegisters, RtcRegisters)>,
+}
+
+impl SaveFile {
+    pub fn new(path: PathBuf, read_only: bool) -> Self {
+        Self {
+            path,
+            read_only,
+            last_written: Vec::new(),
+            last_clock: None,
+        }
+    }
+
+    /// `game.gb` saves to `game.sav`.
+    pub fn default_path(rom_path: &Path) -> PathBuf {
+        rom_path.with_extension("sav")
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Fills the cartridge RAM from the save file. A missing file is not an
+    /// error, the game simply starts without a save.
+    pub fn load(&mut self, cartridge: &mut Cartridge) -> io::Result<()> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                info!("No save file at {:?}", self.path);
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+
+        let ram = cartridge.mbc.ram_mut();
+        if data.len() < ram.len() {
+            warn!(
+                "Save file {:?} is {} bytes, expected {}",
+                self.path,
+                data.len(),
+                ram.len()
+            );
+        }
+        let len = data.len().min(ram.len());
+        ram[..len].copy_from_slice(&data[..len]);
+
+        let footer = &data[len..];
+        if let Some(rtc) = cartridge.mbc.rtc_mut()
+            && !footer.is_empty()
+            && !rtc.load_footer(footer)
+        {
+            warn!("Ignoring RTC footer of {} bytes", footer.len());
+        }
+
+        self.last_written = cartridge.mbc.ram().to_vec();
+        self.last_clock = Self::clock(cartridge);
+        info!("Loaded save file {:?}", self.path);
+        Ok(())
+    }
+
+    /// Writes the cartridge RAM to disk if it, or the clock registers,
+    /// changed since the last flush. Returns whether anything was written.
+    pub fn flush(&mut self, cartridge: &Cartridge) -> io::Result<bool> {
+        let ram = cartridge.mbc.ram();
+        let clock = Self::clock(cartridge);
+        // The footer timestamp is paired with the registers, so an unchanged
+        // clock on disk still catches up correctly on the next load.
+        let unchanged = ram == self.last_written.as_slice() && clock == self.last_clock;
+        if self.read_only || (ram.is_empty() && clock.is_none()) || unchanged {
+            return Ok(false);
+        }
+
+        let mut contents = ram.to_vec();
+        if let Some(rtc) = cartridge.mbc.rtc() {
+            contents.extend_from_slice(&rtc.footer());
+        }
+
+        // Write to the side and rename, so a crash mid-write keeps the old save.
+        let tmp_path = self.path.with_extension("sav.tmp");
+        fs::write(&tmp_path, &contents)?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        self.last_written = ram.to_vec();
+        self.last_clock = clock;
+        Ok(true)
+    }
+
+    fn clock(cartridge: &Cartridge) -> Option<(RtcRegisters, RtcRegisters)> {
+        cartridge.mbc.rtc().map(|rtc| (rtc.live, rtc.latched))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::RTC_FOOTER_LEN;
+
+    /// An MBC1+RAM+BATTERY cart with 8 KiB of RAM.
+    fn battery_cart() -> Cartridge {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0147] = 0x03;
+        rom[0x0149] = 0x02;
+        let mut cartridge = Cartridge::new(rom);
+        cartridge.write(0x0000, 0x0A);
+        cartridge
+    }
+
+    fn temp_save_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("gameboy_rs_{}_{}.sav", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_default_path() {
+        let path = SaveFile::default_path(Path::new("roms/tetris.gb"));
+        assert_eq!(path, PathBuf::from("roms/tetris.sav"));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let path = temp_save_path("round_trip");
+
+        let mut cartridge = battery_cart();
+        cartridge.write(0xA000, 0x12);
+        cartridge.write(0xBFFF, 0x34);
+        let mut save = SaveFile::new(path.clone(), false);
+        assert!(save.flush(&cartridge).unwrap());
+        assert!(
+            !save.flush(&cartridge).unwrap(),
+            "Unchanged RAM shouldn't be rewritten"
+        );
+
+        let mut restored = battery_cart();
+        SaveFile::new(path.clone(), false)
+            .load(&mut restored)
+            .unwrap();
+        assert_eq!(restored.read(0xA000), 0x12);
+        assert_eq!(restored.read(0xBFFF), 0x34);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_rtc_footer_is_appended() {
+        let path = temp_save_path("rtc_footer");
+
+        let mut rom = vec![0; 0x8000];
+        rom[0x0147] = 0x10; // MBC3+TIMER+RAM+BATTERY
+        rom[0x0149] = 0x02;
+        let mut cartridge = Cartridge::new(rom.clone());
+        cartridge.write(0x0000, 0x0A);
+        cartridge.write(0x4000, 0x0A); // RTC hours
+        cartridge.write(0xA000, 7);
+
+        let mut save = SaveFile::new(path.clone(), false);
+        assert!(save.flush(&cartridge).unwrap());
+        assert_eq!(fs::read(&path).unwrap().len(), 0x2000 + RTC_FOOTER_LEN);
+
+        let mut restored = Cartridge::new(rom);
+        SaveFile::new(path.clone(), false)
+            .load(&mut restored)
+            .unwrap();
+        assert_eq!(restored.mbc.rtc().unwrap().live.hours, 7);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_missing_file_is_not_an_error() {
+        let path = temp_save_path("missing");
+        let mut cartridge = battery_cart();
+        assert!(SaveFile::new(path, false).load(&mut cartridge).is_ok());
+        assert_eq!(cartridge.read(0xA000), 0x00);
+    }
+
+    #[test]
+    fn test_read_only_never_writes() {
+        let path = temp_save_path("read_only");
+        let mut cartridge = battery_cart();
+        cartridge.write(0xA000, 0x56);
+
+        let mut save = SaveFile::new(path.clone(), true);
+        assert!(!save.flush(&cartridge).unwrap());
+        assert!(!path.exists());
+    }
+}
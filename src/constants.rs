@@ -16,6 +16,9 @@ pub const IF_ADDR: u16 = 0xFF0F;
 pub const IE_ADDR: u16 = 0xFFFF;
 
 pub const FRAME_DURATION: Duration = Duration::from_nanos(16_742_706 * 10); // ~59.7 fps
+/// How often battery-backed RAM is flushed to disk while running.
+pub const SAVE_FLUSH_INTERVAL_FRAMES: u32 = 60;
+
 // pub const FRAME_DURATION: Duration = Duration::from_nanos(16_742_706); // ~59.7 fps
 
 // --- System & Interrupts ---
@@ -23,8 +26,15 @@ pub const ADDR_SYS_JOYP: u16 = 0xFF00; // Joypad selection and button status
 pub const ADDR_SYS_SB: u16 = 0xFF01; // Serial transfer data
 pub const ADDR_SYS_SC: u16 = 0xFF02; // Serial transfer control
 pub const ADDR_SYS_IF: u16 = 0xFF0F; // Interrupt Flag
+pub const ADDR_SYS_BOOT: u16 = 0xFF50; // Boot ROM disable
+pub const ADDR_SYS_KEY1: u16 = 0xFF4D; // CGB speed switch
+pub const ADDR_SYS_SVBK: u16 = 0xFF70; // CGB WRAM bank
 pub const ADDR_SYS_IE: u16 = 0xFFFF; // Interrupt Enable
 
+// --- Boot ROM ---
+pub const BOOT_ROM_SIZE_DMG: usize = 0x100; // DMG, MGB and SGB
+pub const BOOT_ROM_SIZE_CGB: usize = 0x900; // 0x0000-0x00FF and 0x0200-0x08FF, the header shows through
+
 // --- Interrupt Vectors ---
 pub const ADDR_VEC_VBLANK: u16 = 0x0040; // V-Blank Interrupt Vector
 pub const ADDR_VEC_LCD_STAT: u16 = 0x0048; // LCD Stat Interrupt Vector
@@ -51,6 +61,13 @@ pub const ADDR_PPU_OBP0: u16 = 0xFF48; // Object Palette 0
 pub const ADDR_PPU_OBP1: u16 = 0xFF49; // Object Palette 1
 pub const ADDR_PPU_WY: u16 = 0xFF4A; // Window Y
 pub const ADDR_PPU_WX: u16 = 0xFF4B; // Window X
+pub const ADDR_PPU_VBK: u16 = 0xFF4F; // CGB VRAM bank
+pub const ADDR_PPU_HDMA1: u16 = 0xFF51; // CGB VRAM DMA source high
+pub const ADDR_PPU_HDMA5: u16 = 0xFF55; // CGB VRAM DMA length, mode and start
+pub const ADDR_PPU_BCPS: u16 = 0xFF68; // CGB Background palette index
+pub const ADDR_PPU_BCPD: u16 = 0xFF69; // CGB Background palette data
+pub const ADDR_PPU_OCPS: u16 = 0xFF6A; // CGB Object palette index
+pub const ADDR_PPU_OCPD: u16 = 0xFF6B; // CGB Object palette data
 
 // --- Memory Regions (Bounds) ---
 pub const ADDR_MEM_ROM_START: u16 = 0x0000; // Non-switchable ROM bank
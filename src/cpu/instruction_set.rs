@@ -3,6 +3,7 @@
 use crate::cpu::Cpu;
 use crate::cpu::alu::AluOutput;
 use crate::mmu::Memory;
+use crate::ppu::OamBugAccess;
 use crate::*;
 
 impl InstructionSet for Cpu {
@@ -151,10 +152,12 @@ impl InstructionSet for Cpu {
             // 16-bit Decrement (Affects NO flags)
             Target::Register16(reg) => {
                 let val = self.get_reg16(reg);
+                bus.oam_bug(val, OamBugAccess::Write);
                 self.set_reg16(reg, val.wrapping_sub(1));
             }
 
             Target::StackPointer => {
+                bus.oam_bug(self.sp, OamBugAccess::Write);
                 self.sp = self.sp.wrapping_sub(1);
             }
 
@@ -265,11 +268,7 @@ impl InstructionSet for Cpu {
         let val = self.get_reg16_from_target(src);
 
         // Stack grows downwards: Push High then Low
-        self.sp = self.sp.wrapping_sub(1);
-        bus.write_byte(self.sp, ((val >> 8) & 0xFF) as u8);
-
-        self.sp = self.sp.wrapping_sub(1);
-        bus.write_byte(self.sp, (val & 0xFF) as u8);
+        self.push_u16(bus, val);
 
         instruction.result()
     }
@@ -395,10 +394,12 @@ impl InstructionSet for Cpu {
             }
             Target::Register16(reg) => {
                 let val = self.get_reg16(reg);
+                bus.oam_bug(val, OamBugAccess::Write);
                 self.set_reg16(reg, val.wrapping_add(1));
             }
 
             Target::StackPointer => {
+                bus.oam_bug(self.sp, OamBugAccess::Write);
                 self.sp = self.sp.wrapping_add(1);
             }
 
@@ -634,7 +635,11 @@ impl InstructionSet for Cpu {
         self.write_target(target, OperandValue::U8(res), bus);
         instruction.result_with_flags(res == 0, false, false, new_c == 1)
     }
-    fn stop(&mut self, instruction: OpcodeInfo, _bus: &mut impl Memory) -> InstructionResult {
+    fn stop(&mut self, instruction: OpcodeInfo, bus: &mut impl Memory) -> InstructionResult {
+        // With KEY1 armed, STOP only switches the CGB speed.
+        if bus.speed_switch() {
+            return instruction.result();
+        }
         self.halted = true; // For now, treat like HALT
         // Real hardware would also stop the oscillator
         instruction.result()
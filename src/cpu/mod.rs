@@ -7,6 +7,9 @@ mod snapshot;
 mod step_flow_controller_enum;
 
 use crate::input::InputDevice;
+use crate::model::Model;
+use crate::ppu::OamBugAccess;
+use crate::state::{SaveState, StateError, StateReader, StateWriter};
 use crate::*;
 use crate::{input::DummyInput, mmu::Memory};
 pub use alu::{Alu, AluOutput};
@@ -49,6 +52,44 @@ impl Default for Cpu {
     }
 }
 
+impl SaveState for Cpu {
+    fn save_state(&self, w: &mut StateWriter) {
+        for reg in [
+            self.a, self.f, self.b, self.c, self.d, self.e, self.h, self.l,
+        ] {
+            w.u8(reg);
+        }
+        w.u16(self.pc);
+        w.u16(self.sp);
+        w.bool(self.halted);
+        w.bool(self.ime);
+        w.u8(self.ime_scheduled);
+        w.bool(self.halt_bug_triggered);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        for reg in [
+            &mut self.a,
+            &mut self.f,
+            &mut self.b,
+            &mut self.c,
+            &mut self.d,
+            &mut self.e,
+            &mut self.h,
+            &mut self.l,
+        ] {
+            *reg = r.u8()?;
+        }
+        self.pc = r.u16()?;
+        self.sp = r.u16()?;
+        self.halted = r.bool()?;
+        self.ime = r.bool()?;
+        self.ime_scheduled = r.u8()?;
+        self.halt_bug_triggered = r.bool()?;
+        Ok(())
+    }
+}
+
 impl Cpu {
     pub fn new() -> Self {
         debug!("Creating CPU");
@@ -70,16 +111,41 @@ impl Cpu {
             halt_bug_triggered: false,
         }
     }
-    pub fn reset_post_boot(&mut self) {
-        self.a = 0x01;
-        // F = 0xB0 -> Z:1, N:0, H:1, C:1 (Upper nibble is 1011)
-        self.f = 0xB0;
-        self.b = 0x00;
-        self.c = 0x13;
-        self.d = 0x00;
-        self.e = 0xD8;
-        self.h = 0x01;
-        self.l = 0x4D;
+    /// Power-on state, for running a boot ROM from 0x0000.
+    pub fn reset_pre_boot(&mut self) {
+        *self = Self {
+            a: 0,
+            f: 0,
+            b: 0,
+            c: 0,
+            d: 0,
+            e: 0,
+            h: 0,
+            l: 0,
+            pc: 0x0000,
+            sp: 0x0000,
+            halted: false,
+            ime: false,
+            ime_scheduled: 0,
+            halt_bug_triggered: false,
+        };
+    }
+    /// Registers as the given model's boot ROM leaves them.
+    /// On DMG/MGB the H and C flags depend on the header checksum, they are
+    /// set here as they are for any cart with a non-zero checksum.
+    pub fn reset_post_boot(&mut self, model: Model) {
+        // A, F, B, C, D, E, H, L
+        let regs: [u8; 8] = match model {
+            Model::Dmg0 => [0x01, 0x00, 0xFF, 0x13, 0x00, 0xC1, 0x84, 0x03],
+            // F = 0xB0 -> Z:1, N:0, H:1, C:1 (Upper nibble is 1011)
+            Model::Dmg => [0x01, 0xB0, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+            Model::Mgb => [0xFF, 0xB0, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+            Model::Sgb => [0x01, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60],
+            Model::Cgb => [0x11, 0x80, 0x00, 0x00, 0xFF, 0x56, 0x00, 0x0D],
+        };
+        [
+            self.a, self.f, self.b, self.c, self.d, self.e, self.h, self.l,
+        ] = regs;
         self.sp = 0xFFFE;
         self.pc = 0x0100; // The standard entry point for all cartridges
     }
@@ -384,12 +450,14 @@ impl Cpu {
             // 1. Indirect Read with Side Effects (e.g., LD A, (HL+))
             Target::AddrRegister16Increment(reg) => {
                 let addr = self.get_reg16(reg);
+                bus.oam_bug(addr, OamBugAccess::ReadIncrease);
                 let val = bus.read_byte(addr);
                 self.set_reg16(reg, addr.wrapping_add(1)); // Increment side effect
                 OperandValue::U8(val)
             }
             Target::AddrRegister16Decrement(reg) => {
                 let addr = self.get_reg16(reg);
+                bus.oam_bug(addr, OamBugAccess::ReadIncrease);
                 let val = bus.read_byte(addr);
                 self.set_reg16(reg, addr.wrapping_sub(1)); // Decrement side effect
                 OperandValue::U8(val)
@@ -436,6 +504,8 @@ impl Cpu {
 
             (Target::AddrRegister16Decrement(reg), OperandValue::U8(v)) => {
                 let addr = self.get_reg16(reg);
+                // The write and the IDU share the cycle, so it only counts once.
+                mmu.oam_bug(addr, OamBugAccess::Write);
                 mmu.write_byte(addr, v);
 
                 // The side effect: decrement the pointer
@@ -444,6 +514,8 @@ impl Cpu {
             }
             (Target::AddrRegister16Increment(reg), OperandValue::U8(v)) => {
                 let addr = self.get_reg16(reg);
+                // The write and the IDU share the cycle, so it only counts once.
+                mmu.oam_bug(addr, OamBugAccess::Write);
                 mmu.write_byte(addr, v);
 
                 // The side effect: increment the pointer
@@ -550,10 +622,12 @@ impl Cpu {
 
     /// Reads a 16-bit value from the current Stack Pointer and increments SP by 2.
     /// Little-Endian: The byte at SP is the low byte, SP+1 is the high byte.
-    pub fn pop_u16(&mut self, bus: &impl Memory) -> u16 {
+    pub fn pop_u16(&mut self, bus: &mut impl Memory) -> u16 {
+        bus.oam_bug(self.sp, OamBugAccess::ReadIncrease);
         let low = bus.read_byte(self.sp) as u16;
         self.sp = self.sp.wrapping_add(1);
 
+        bus.oam_bug(self.sp, OamBugAccess::ReadIncrease);
         let high = bus.read_byte(self.sp) as u16;
         self.sp = self.sp.wrapping_add(1);
 
@@ -566,10 +640,14 @@ impl Cpu {
         let high = (val >> 8) as u8;
         let low = (val & 0xFF) as u8;
 
+        // The SP decrement before the first write hits the bus on its own.
+        bus.oam_bug(self.sp, OamBugAccess::Write);
         self.sp = self.sp.wrapping_sub(1);
+        bus.oam_bug(self.sp, OamBugAccess::Write);
         bus.write_byte(self.sp, high);
 
         self.sp = self.sp.wrapping_sub(1);
+        bus.oam_bug(self.sp, OamBugAccess::Write);
         bus.write_byte(self.sp, low);
     }
 
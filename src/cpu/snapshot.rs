@@ -18,6 +18,8 @@ pub struct CpuSnapshot {
     pub sp: u16,
     pub pc: u16,
     pub pcmem: [u8; 4], // The 4 bytes at PC
+    /// CGB double speed, not part of the Game Boy Doctor format.
+    pub double_speed: bool,
 }
 
 #[derive(Debug)]
@@ -52,6 +54,7 @@ impl CpuSnapshot {
                 bus.read_byte(cpu.pc.wrapping_add(2)),
                 bus.read_byte(cpu.pc.wrapping_add(3)),
             ],
+            double_speed: bus.double_speed,
         }
     }
 
@@ -5,23 +5,31 @@ pub mod constants;
 pub mod cpu;
 pub mod input;
 pub mod mmu;
+pub mod model;
 pub mod opcodes;
 pub mod ppu;
+pub mod sgb;
+pub mod state;
 pub mod timer;
 pub mod utils;
 
 // use crate::cartridge::Headers;
+use crate::apu::AudioRecorder;
+use crate::cartridge::SaveFile;
 use crate::cpu::Cpu;
 use crate::input::RotaryInput;
 use crate::ppu::terminal::{display_buffer, display_frame};
+use crate::state::RewindBuffer;
 
 use constants::*;
-use log::Level;
+use log::{Level, error, info};
 use mmu::{Bus, Memory};
 use opcodes::*;
 use std::io;
 
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use std::io::Write;
 use std::time::Instant;
@@ -50,7 +58,7 @@ pub fn rom_exec(args: args::Args) -> Result<(), io::Error> {
     match cartridge::load_rom(&args.load_rom) {
         Ok(buffer) => {
             // Starts the main read loop.
-            main_loop(buffer);
+            main_loop(buffer, &args)?;
         }
         Err(e) => {
             panic!("Error: {:?}", e);
@@ -60,15 +68,62 @@ pub fn rom_exec(args: args::Args) -> Result<(), io::Error> {
     Ok(())
 }
 
-/// Reads op code forever and is the main loop for the emulation.
-/// Will only return anything if it is either done emulating, or
-/// if an error occured that made it panic.
-fn main_loop(buffer: Vec<u8>) {
+/// Reads op code until interrupted and is the main loop for the emulation.
+/// Battery-backed RAM is loaded before the first instruction, flushed
+/// periodically and once more on the way out, as is the save state if asked for.
+fn main_loop(buffer: Vec<u8>, args: &args::Args) -> Result<(), io::Error> {
     let mut cpu = Cpu::new();
     // let headers = Headers::new(&buffer);
-    let mut bus: Bus<RotaryInput> = Bus::new(buffer);
+    let mut bus: Bus<RotaryInput> = Bus::new(buffer).with_model(args.model);
+    if let Some(path) = &args.boot_rom {
+        let boot_rom = cartridge::load_boot_rom(path).map_err(io::Error::other)?;
+        bus = bus.with_boot_rom(boot_rom);
+        cpu.reset_pre_boot();
+    } else if args.model.post_boot_div().is_none() {
+        return Err(io::Error::other(format!(
+            "The {:?} post-boot state isn't known, run its boot ROM with --boot-rom",
+            args.model
+        )));
+    } else {
+        cpu.reset_post_boot(args.model);
+        bus.init_post_boot();
+    }
+    let save_path = args
+        .save_path
+        .clone()
+        .unwrap_or_else(|| SaveFile::default_path(&args.load_rom));
+    let mut save_file = SaveFile::new(save_path, args.save_read_only);
+    let battery = bus.cartridge.has_battery();
+    if battery {
+        save_file.load(&mut bus.cartridge)?;
+    }
+
+    if let Some(path) = &args.load_state {
+        let data = std::fs::read(path)?;
+        state::load_state(&mut cpu, &mut bus, &data).map_err(io::Error::other)?;
+        info!("Loaded state from {:?}", path);
+    }
+
+    // Ctrl-C ends the loop instead of killing the process, so the save gets flushed.
+    let quit = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&quit))?;
+    signal_hook::flag::register(signal_hook::consts::SIGTERM, Arc::clone(&quit))?;
+
+    let mut rewind = (args.rewind_buffer_mb > 0)
+        .then(|| RewindBuffer::new(args.rewind_interval, args.rewind_buffer_mb * 1024 * 1024));
+
+    let mut recorder = match &args.record_audio {
+        Some(path) => Some(AudioRecorder::create(
+            path,
+            args.record_stems,
+            &mut bus.apu,
+        )?),
+        None => None,
+    };
+
+    let mut frames: u32 = 0;
     let mut last_frame_time = Instant::now();
-    loop {
+    while !quit.load(Ordering::Relaxed) {
         let mut vblank_triggered = false;
         while !vblank_triggered {
             let cycles = cpu.step(&mut bus);
@@ -83,6 +138,20 @@ fn main_loop(buffer: Vec<u8>) {
         // println!("PC: {}", cpu.pc);
         // println!("{}", cpu.take_snapshot(&bus).to_doctor_string());
 
+        if let Some(rewind) = &mut rewind {
+            rewind.on_frame(&cpu, &bus);
+        }
+        // A failed write stops the recording, not the emulator.
+        if let Some(Err(err)) = recorder.as_mut().map(|r| r.record(&mut bus.apu)) {
+            error!("Audio recording stopped: {}", err);
+            finish_recording(recorder.take().unwrap());
+        }
+
+        frames = frames.wrapping_add(1);
+        if battery && frames.is_multiple_of(SAVE_FLUSH_INTERVAL_FRAMES) {
+            save_file.flush(&bus.cartridge)?;
+        }
+
         // 3. Sleep to maintain original hardware speed
         let elapsed = last_frame_time.elapsed();
         if elapsed < FRAME_DURATION {
@@ -90,4 +159,25 @@ fn main_loop(buffer: Vec<u8>) {
         }
         last_frame_time = Instant::now();
     }
+
+    if battery && save_file.flush(&bus.cartridge)? {
+        info!("Saved to {:?}", save_file.path());
+    }
+    if let Some(recorder) = recorder {
+        finish_recording(recorder);
+    }
+    if let Some(path) = &args.save_state {
+        std::fs::write(path, state::save_state(&cpu, &bus))?;
+        info!("Saved state to {:?}", path);
+    }
+    Ok(())
+}
+
+/// Fills in the WAV headers, so whatever was recorded stays playable.
+fn finish_recording(recorder: AudioRecorder) {
+    let path = recorder.path().to_path_buf();
+    match recorder.finish() {
+        Ok(()) => info!("Recorded audio to {:?}", path),
+        Err(err) => error!("Failed to finish {:?}: {}", path, err),
+    }
 }
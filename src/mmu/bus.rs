@@ -24,14 +24,28 @@ use log::{debug, trace};
 // use std::io::Write;
 
 use crate::{
-    apu::Apu, constants::*, input::InputDevice, mmu::memory_trait::Memory, ppu::Ppu, timer::Timer,
+    apu::Apu,
+    cartridge::Cartridge,
+    constants::*,
+    input::InputDevice,
+    mmu::{HDMA_BLOCK_CYCLES, HDMA_BLOCK_LEN, Hdma, HdmaMode, OamDma, memory_trait::Memory},
+    model::Model,
+    ppu::{OamBugAccess, Ppu},
+    sgb::Sgb,
+    state::{SaveState, StateError, StateReader, StateWriter},
+    timer::Timer,
 };
 
 /// 64 Kb - The standard Game Boy address space
 const MEMORY_SIZE: usize = 1024 * 64;
+/// CGB WRAM banks 2-7, banks 0 and 1 live in `data` like on the DMG.
+const WRAM_BANK_SIZE: usize = 0x1000;
+const WRAM_EXTRA_BANKS: usize = 6;
 
 pub struct Bus<I: InputDevice + Default> {
     pub timer: Timer,
+    // ROM and external RAM live on the cartridge, behind its MBC.
+    pub cartridge: Cartridge,
     // Must use a Vec since an Array would use the stack, and crash the application.
     // Using the heap is required.
     // rom_size: usize,
@@ -46,29 +60,40 @@ pub struct Bus<I: InputDevice + Default> {
     pub serial_buffer: Vec<u8>,
     serial_buffer_dirty: bool,
     pub apu: Apu,
+    pub model: Model,
+    boot_rom: Option<Vec<u8>>,
+    /// The boot ROM overlays the cartridge until 0xFF50 is written.
+    pub boot_rom_mapped: bool,
+    pub dma: OamDma,
+    /// CGB VRAM DMA, general purpose or H-Blank.
+    pub hdma: Hdma,
+    wram_banks: Box<[u8; WRAM_BANK_SIZE * WRAM_EXTRA_BANKS]>,
+    /// WRAM bank mapped at 0xD000 in CGB mode, 0 selects 1.
+    pub svbk: u8,
+    /// CGB double speed, KEY1 bit 7.
+    pub double_speed: bool,
+    /// KEY1 bit 0, the next STOP switches speed.
+    pub speed_switch_armed: bool,
+    /// The SNES side of the SGB model.
+    pub sgb: Option<Sgb>,
 }
 
 impl<I: InputDevice + Default> Bus<I> {
     pub fn new(rom_data: Vec<u8>) -> Self {
-        let rom_size = rom_data.len();
         // Create a zeroed array on the heap
-        let mut buffer = Box::new([0u8; MEMORY_SIZE]);
-
-        // Copy ROM data into the beginning
-        let copy_len = std::cmp::min(rom_size, MEMORY_SIZE);
-        buffer[..copy_len].copy_from_slice(&rom_data[..copy_len]);
+        let buffer = Box::new([0u8; MEMORY_SIZE]);
 
         debug!(
-            "Creating Bus, memory_size: {}, rom_size: {}, copy_len: {}",
+            "Creating Bus, memory_size: {}, rom_size: {}",
             MEMORY_SIZE,
             rom_data.len(),
-            copy_len
         );
 
         let ppu = Ppu::new();
         // ppu.init_post_boot();
         Bus {
             timer: Timer::new(),
+            cartridge: Cartridge::new(rom_data),
             // rom_size,
             data: buffer,
             ppu: Box::new(ppu),
@@ -77,6 +102,84 @@ impl<I: InputDevice + Default> Bus<I> {
             serial_buffer: Vec::new(),
             serial_buffer_dirty: false,
             apu: Apu::new(),
+            model: Model::default(),
+            boot_rom: None,
+            boot_rom_mapped: false,
+            dma: OamDma::default(),
+            hdma: Hdma::default(),
+            wram_banks: Box::new([0; WRAM_BANK_SIZE * WRAM_EXTRA_BANKS]),
+            svbk: 0,
+            double_speed: false,
+            speed_switch_armed: false,
+            sgb: None,
+        }
+    }
+
+    pub fn with_model(mut self, model: Model) -> Self {
+        self.model = model;
+        self.ppu.cgb_mode = self.cgb_mode();
+        self.sgb = model.is_sgb().then(Sgb::new);
+        self
+    }
+
+    /// A CGB only turns its extra hardware on for games flagged for it,
+    /// anything else runs as on a DMG.
+    pub fn cgb_mode(&self) -> bool {
+        self.model.is_cgb() && self.cartridge.headers.supports_cgb()
+    }
+
+    /// The SGB always draws its border, it only listens to packets from
+    /// games flagged for it.
+    pub fn sgb_mode(&self) -> bool {
+        self.sgb.is_some() && self.cartridge.headers.supports_sgb()
+    }
+
+    /// Offset in `wram_banks` for 0xD000-0xDFFF when SVBK maps bank 2-7 there.
+    fn wram_bank_offset(&self, addr: u16) -> Option<usize> {
+        let bank = (self.svbk & 0x07) as usize;
+        (self.cgb_mode() && addr >= 0xD000 && bank >= 2)
+            .then(|| (bank - 2) * WRAM_BANK_SIZE + (addr - 0xD000) as usize)
+    }
+
+    /// Reads 0xC000-0xDFFF.
+    fn read_wram(&self, addr: u16) -> u8 {
+        match self.wram_bank_offset(addr) {
+            Some(offset) => self.wram_banks[offset],
+            None => self.data[addr as usize],
+        }
+    }
+
+    fn write_wram(&mut self, addr: u16, val: u8) {
+        match self.wram_bank_offset(addr) {
+            Some(offset) => self.wram_banks[offset] = val,
+            None => self.data[addr as usize] = val,
+        }
+    }
+
+    /// Puts the components in the state the model's boot ROM leaves them in.
+    pub fn init_post_boot(&mut self) {
+        self.ppu.init_post_boot(self.model);
+        self.timer.init_post_boot(self.model);
+        self.apu.init_post_boot(self.model);
+    }
+
+    /// Maps a boot ROM over the start of the cartridge, the CPU should
+    /// then start at 0x0000 instead of the post-boot state.
+    pub fn with_boot_rom(mut self, boot_rom: Vec<u8>) -> Self {
+        self.boot_rom = Some(boot_rom);
+        self.boot_rom_mapped = true;
+        self
+    }
+
+    fn read_boot_rom(&self, addr: u16) -> Option<u8> {
+        let boot_rom = self.boot_rom.as_ref().filter(|_| self.boot_rom_mapped)?;
+        match addr as usize {
+            // The CGB boot ROM leaves a gap for the cartridge header.
+            addr @ 0x0000..0x0100 => Some(boot_rom[addr]),
+            addr @ 0x0200..BOOT_ROM_SIZE_CGB if boot_rom.len() == BOOT_ROM_SIZE_CGB => {
+                Some(boot_rom[addr])
+            }
+            _ => None,
         }
     }
 
@@ -97,6 +200,11 @@ impl<I: InputDevice + Default> Bus<I> {
     //     self.ppu.stat_line = new_signal;
     // }
 
+    /// Rumble motor state, for frontends that can forward it to a controller.
+    pub fn rumble_active(&self) -> bool {
+        self.cartridge.rumble()
+    }
+
     // Also marks the bus as not-dirty.
     pub fn read_if_dirty_serial_buffer(&mut self) -> Option<&Vec<u8>> {
         if self.serial_buffer_dirty {
@@ -105,34 +213,128 @@ impl<I: InputDevice + Default> Bus<I> {
             None
         }
     }
-    fn dma_transfer(&mut self, val: u8) {
-        // The value written is the high byte of the source (e.g., 0xC0 -> 0xC000)
-        let source_base = (val as u16) << 8;
-
-        for i in 0..160 {
-            // We use read_byte to ensure we respect the memory map
-            // (the data could be coming from ROM or WRAM)
-            let data = self.read_byte(source_base + i);
+    /// Copies the OAM DMA bytes due in the elapsed M-cycles.
+    fn tick_dma(&mut self, cycles: u8) {
+        for _ in 0..self.dma.m_cycles(cycles) {
+            if let Some((source, index)) = self.dma.step() {
+                // Sources from 0xE000 up see the echo of WRAM.
+                let source = if source >= ADDR_MEM_ECHO_START {
+                    source - 0x2000
+                } else {
+                    source
+                };
+                let data = self.read_byte_raw(source);
+                self.ppu.write_oam(index, data);
+            }
+        }
+        self.dma.arm();
+    }
 
-            // We push the data directly into the PPU's OAM
-            self.ppu.write_oam(i as usize, data);
+    /// Copies the VRAM DMA blocks that are due, the CPU is stopped while
+    /// they go, so the rest of the machine is ticked in its place.
+    /// Returns true if a V-Blank was triggered meanwhile.
+    fn tick_hdma(&mut self, hblank_started: bool) -> bool {
+        let blocks = match self.hdma.mode {
+            HdmaMode::General => self.hdma.remaining() / HDMA_BLOCK_LEN,
+            HdmaMode::HBlank if hblank_started => 1,
+            _ => 0,
+        };
+        for _ in 0..blocks {
+            let Some((source, dest)) = self.hdma.next_block() else {
+                break;
+            };
+            for i in 0..HDMA_BLOCK_LEN {
+                let data = self.read_byte_raw(source.wrapping_add(i));
+                self.ppu.poke(0x8000 | (dest + i), data);
+            }
+        }
+        // Copied first, so the transfer is past these blocks while stalling.
+        // A block takes as long in double speed, that is twice the CPU cycles.
+        let stall = if self.double_speed {
+            2 * HDMA_BLOCK_CYCLES
+        } else {
+            HDMA_BLOCK_CYCLES
+        };
+        let mut vblank = false;
+        for _ in 0..blocks {
+            vblank |= self.tick_components(stall);
         }
+        vblank
+    }
+}
+
+/// Only the bus' own memory and registers, the components it owns are
+/// saved as separate sections.
+impl<I: InputDevice + Default> SaveState for Bus<I> {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(self.data.as_slice());
+        w.u8(self.joypad_sel);
+        w.bool(self.boot_rom_mapped);
+        self.dma.save_state(w);
+        w.bytes(self.wram_banks.as_slice());
+        w.u8(self.svbk);
+        self.hdma.save_state(w);
+        w.bool(self.double_speed);
+        w.bool(self.speed_switch_armed);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        r.bytes_into(self.data.as_mut_slice())?;
+        self.joypad_sel = r.u8()?;
+        // Can't map a boot ROM we don't have.
+        self.boot_rom_mapped = r.bool()? && self.boot_rom.is_some();
+        self.dma.load_state(r)?;
+        r.bytes_into(self.wram_banks.as_mut_slice())?;
+        self.svbk = r.u8()? & 0x07;
+        self.hdma.load_state(r)?;
+        self.double_speed = r.bool()?;
+        self.speed_switch_armed = r.bool()?;
+        Ok(())
     }
 }
 
 impl<I: InputDevice + Default> Memory for Bus<I> {
     #[inline]
     fn force_write_byte(&mut self, addr: u16, val: u8) {
-        self.data[addr as usize] = val;
+        match addr {
+            ADDR_MEM_ROM_START..=ADDR_MEM_ROM_END | ADDR_MEM_SRAM_START..=ADDR_MEM_SRAM_END => {
+                self.cartridge.mbc.force_write(addr, val)
+            }
+            ADDR_MEM_VRAM_START..=ADDR_MEM_VRAM_END | ADDR_MEM_OAM_START..=ADDR_MEM_OAM_END => {
+                self.ppu.poke(addr, val)
+            }
+            ADDR_MEM_WRAM_START..=ADDR_MEM_WRAM_END => self.write_wram(addr, val),
+            _ => self.data[addr as usize] = val,
+        }
     }
     fn read_byte_raw(&self, addr: u16) -> u8 {
-        self.data[addr as usize]
+        if let Some(b) = self.read_boot_rom(addr) {
+            return b;
+        }
+        match addr {
+            ADDR_MEM_ROM_START..=ADDR_MEM_ROM_END | ADDR_MEM_SRAM_START..=ADDR_MEM_SRAM_END => {
+                self.cartridge.read(addr)
+            }
+            ADDR_MEM_VRAM_START..=ADDR_MEM_VRAM_END | ADDR_MEM_OAM_START..=ADDR_MEM_OAM_END => {
+                self.ppu.peek(addr)
+            }
+            ADDR_MEM_WRAM_START..=ADDR_MEM_WRAM_END => self.read_wram(addr),
+            _ => self.data[addr as usize],
+        }
     }
     fn read_byte(&self, addr: u16) -> u8 {
+        if self.dma.blocks(addr) {
+            trace!("read [{:#06X}] -> 0xFF (DMA BUSY)", addr);
+            return 0xFF;
+        }
         match addr {
             // ROM: 0x0000..=0x7FFF
             ADDR_MEM_ROM_START..=ADDR_MEM_ROM_END => {
-                let b = self.data[addr as usize];
+                if let Some(b) = self.read_boot_rom(addr) {
+                    trace!("read [{:#06X}] -> {:#04X} (BOOT ROM)", addr, b);
+                    return b;
+                }
+                let b = self.cartridge.read(addr);
                 trace!("read [{:#06X}] -> {:#04X} (ROM)", addr, b);
                 b
             }
@@ -144,16 +346,23 @@ impl<I: InputDevice + Default> Memory for Bus<I> {
                 b
             }
 
+            // External RAM: 0xA000..=0xBFFF
+            ADDR_MEM_SRAM_START..=ADDR_MEM_SRAM_END => {
+                let b = self.cartridge.read(addr);
+                trace!("read [{:#06X}] -> {:#04X} (EXT RAM)", addr, b);
+                b
+            }
+
             // WRAM: 0xC000..=0xDFFF
             ADDR_MEM_WRAM_START..=ADDR_MEM_WRAM_END => {
-                let b = self.data[addr as usize];
+                let b = self.read_wram(addr);
                 trace!("read [{:#06X}] -> {:#04X} (WRAM)", addr, b);
                 b
             }
 
             // Echo RAM: 0xE000..=0xFDFF
             ADDR_MEM_ECHO_START..=ADDR_MEM_ECHO_END => {
-                let b = self.data[(addr - 0x2000) as usize];
+                let b = self.read_wram(addr - 0x2000);
                 trace!("read [{:#06X}] -> {:#04X} (ECHO)", addr, b);
                 b
             }
@@ -174,11 +383,20 @@ impl<I: InputDevice + Default> Memory for Bus<I> {
             // Joypad: 0xFF00
             ADDR_SYS_JOYP => {
                 let mut result = 0xCF | self.joypad_sel;
-                if (self.joypad_sel & 0x10) == 0 {
-                    result &= self.input.read(self.joypad_sel);
+                let sgb = self.sgb.as_ref().filter(|_| self.sgb_mode());
+                // Only the first SGB joypad is connected.
+                if sgb.is_none_or(|sgb| sgb.player == 0) {
+                    if (self.joypad_sel & 0x10) == 0 {
+                        result &= self.input.read(self.joypad_sel);
+                    }
+                    if (self.joypad_sel & 0x20) == 0 {
+                        result &= self.input.read(self.joypad_sel);
+                    }
                 }
-                if (self.joypad_sel & 0x20) == 0 {
-                    result &= self.input.read(self.joypad_sel);
+                if let Some(sgb) = sgb
+                    && self.joypad_sel == 0x30
+                {
+                    result = (result & 0xF0) | sgb.read_joypad_id();
                 }
                 trace!("read [{:#06X}] -> {:#04X} (JOYPAD)", addr, result);
                 result
@@ -191,13 +409,52 @@ impl<I: InputDevice + Default> Memory for Bus<I> {
                 b
             }
 
-            // PPU Registers: 0xFF40..=0xFF4B
-            ADDR_PPU_LCDC..=ADDR_PPU_WX => {
+            // DMA Source: 0xFF46
+            ADDR_PPU_DMA => {
+                trace!("read [{:#06X}] -> {:#04X} (DMA)", addr, self.dma.reg);
+                self.dma.reg
+            }
+
+            // PPU Registers: 0xFF40..=0xFF4B, CGB VBK and palettes
+            ADDR_PPU_LCDC..=ADDR_PPU_WX | ADDR_PPU_VBK | ADDR_PPU_BCPS..=ADDR_PPU_OCPD => {
                 let b = self.ppu.read_byte(addr);
                 trace!("read [{:#06X}] -> {:#04X} (PPU)", addr, b);
                 b
             }
 
+            // CGB VRAM DMA: 0xFF51..=0xFF55
+            ADDR_PPU_HDMA1..=ADDR_PPU_HDMA5 => {
+                let b = if self.cgb_mode() {
+                    self.hdma.read(addr)
+                } else {
+                    0xFF
+                };
+                trace!("read [{:#06X}] -> {:#04X} (HDMA)", addr, b);
+                b
+            }
+
+            // CGB speed switch: 0xFF4D
+            ADDR_SYS_KEY1 => {
+                let b = if self.cgb_mode() {
+                    0x7E | ((self.double_speed as u8) << 7) | self.speed_switch_armed as u8
+                } else {
+                    0xFF
+                };
+                trace!("read [{:#06X}] -> {:#04X} (KEY1)", addr, b);
+                b
+            }
+
+            // CGB WRAM bank: 0xFF70
+            ADDR_SYS_SVBK => {
+                let b = if self.cgb_mode() {
+                    0xF8 | self.svbk
+                } else {
+                    0xFF
+                };
+                trace!("read [{:#06X}] -> {:#04X} (SVBK)", addr, b);
+                b
+            }
+
             // High RAM (HRAM): 0xFF80..=0xFFFE
             ADDR_MEM_HRAM_START..=ADDR_MEM_HRAM_END => {
                 let b = self.data[addr as usize];
@@ -217,6 +474,11 @@ impl<I: InputDevice + Default> Memory for Bus<I> {
                 b
             }
 
+            ADDR_SYS_BOOT => {
+                trace!("read [{:#06X}] -> 0xFF (BOOT)", addr);
+                0xFF
+            }
+
             // Audio
             0xFF10..=0xFF3F => self.apu.read_byte(addr),
 
@@ -248,6 +510,26 @@ impl<I: InputDevice + Default> Memory for Bus<I> {
         }
     }
 
+    fn speed_switch(&mut self) -> bool {
+        if !self.cgb_mode() || !self.speed_switch_armed {
+            return false;
+        }
+        self.speed_switch_armed = false;
+        self.double_speed = !self.double_speed;
+        // DIV is reset by the switch, like by a write.
+        self.write_div();
+        debug!("Speed switch, double speed: {}", self.double_speed);
+        true
+    }
+
+    /// Passes the bus cycle on to the PPU, which corrupts OAM if it is scanning it.
+    fn oam_bug(&mut self, addr: u16, access: OamBugAccess) {
+        // Fixed on CGB.
+        if !self.model.is_cgb() {
+            self.ppu.oam_bug(addr, access);
+        }
+    }
+
     /// Returns true if a V-Blank is triggered.
     fn tick_components(&mut self, cycles: u8) -> bool {
         // 1. Timer Interrupt (Bit 2)
@@ -256,15 +538,30 @@ impl<I: InputDevice + Default> Memory for Bus<I> {
             self.write_if(current_if | 0x04);
         }
 
-        self.apu.tick(cycles as u32);
+        // In double speed the CPU, timer and OAM DMA run twice as fast, the
+        // PPU and APU keep their rate. Serial transfers complete on the write
+        // to SC, so there is no serial clock to speed up.
+        let dots = if self.double_speed {
+            cycles / 2
+        } else {
+            cycles
+        };
+        self.apu.tick(dots as u32);
+        self.tick_dma(cycles);
 
         // 2. PPU Interrupts
         // Assuming ppu.tick returns (vblank_triggered, stat_triggered)
-        let (vblank, stat) = self.ppu.tick(cycles.into());
+        let was_hblank = self.ppu.get_mode() == 0;
+        let (mut vblank, stat) = self.ppu.tick(dots.into());
+        let hblank_started =
+            self.ppu.lcd_enabled() && !was_hblank && self.ppu.get_mode() == 0 && self.ppu.ly < 144;
 
         if vblank {
             let current_if = self.read_if();
             self.write_if(current_if | 0x01); // Bit 0: V-Blank
+            if let Some(sgb) = &mut self.sgb {
+                sgb.on_vblank(&self.ppu.frame_buffer);
+            }
         }
 
         if stat {
@@ -272,18 +569,24 @@ impl<I: InputDevice + Default> Memory for Bus<I> {
             self.write_if(current_if | 0x02); // Bit 1: LCD STAT
         }
 
+        if self.hdma.active() {
+            vblank |= self.tick_hdma(hblank_started);
+        }
+
         vblank // Keep returning vblank if your main loop uses it for frame timing
     }
 
     fn write_byte(&mut self, addr: u16, val: u8) {
         // println!("write_byte: 0x{:00X} = {}", addr, val);
+        if self.dma.blocks(addr) {
+            trace!("write [0x{:04X}] -- 0x{:02X} (DMA BUSY)", addr, val);
+            return;
+        }
         match addr {
-            // ROM: 0x0000..=0x7FFF (Read Only)
+            // ROM: 0x0000..=0x7FFF (Read Only, writes reach the MBC registers)
             ADDR_MEM_ROM_START..=ADDR_MEM_ROM_END => {
-                trace!(
-                    "write [0x{:04X}] -> 0x{:02X} (IGNORED: ROM is Read Only)",
-                    addr, val
-                );
+                trace!("write [0x{:04X}] <- 0x{:02X} (MBC REG)", addr, val);
+                self.cartridge.write(addr, val);
             }
 
             // VRAM: 0x8000..=0x9FFF
@@ -292,22 +595,22 @@ impl<I: InputDevice + Default> Memory for Bus<I> {
                 self.ppu.write_byte(addr, val);
             }
 
-            // External RAM: 0xA000..=0xBFFF (Assuming simple mapping for now)
+            // External RAM: 0xA000..=0xBFFF
             ADDR_MEM_SRAM_START..=ADDR_MEM_SRAM_END => {
                 trace!("write [0x{:04X}] <- 0x{:02X} (EXT RAM)", addr, val);
-                self.data[addr as usize] = val;
+                self.cartridge.write(addr, val);
             }
 
             // WRAM: 0xC000..=0xDFFF
             ADDR_MEM_WRAM_START..=ADDR_MEM_WRAM_END => {
                 trace!("write [0x{:04X}] <- 0x{:02X} (WRAM)", addr, val);
-                self.data[addr as usize] = val;
+                self.write_wram(addr, val);
             }
 
             // Echo RAM: 0xE000..=0xFDFF
             ADDR_MEM_ECHO_START..=ADDR_MEM_ECHO_END => {
                 trace!("write [0x{:04X}] <- 0x{:02X} (ECHO RAM)", addr, val);
-                self.data[(addr - 0x2000) as usize] = val;
+                self.write_wram(addr - 0x2000, val);
             }
 
             // OAM: 0xFE00..=0xFE9F
@@ -325,6 +628,11 @@ impl<I: InputDevice + Default> Memory for Bus<I> {
             ADDR_SYS_JOYP => {
                 trace!("write [0x{:04X}] <- 0x{:02X} (JOYPAD SEL)", addr, val);
                 self.joypad_sel = val & 0x30;
+                if self.sgb_mode()
+                    && let Some(sgb) = &mut self.sgb
+                {
+                    sgb.write_joypad(val);
+                }
             }
 
             // Serial Data & Control: 0xFF01..=0xFF02
@@ -376,15 +684,56 @@ impl<I: InputDevice + Default> Memory for Bus<I> {
             // DMA Transfer: 0xFF46
             ADDR_PPU_DMA => {
                 trace!("write [0x{:04X}] <- 0x{:02X} (DMA)", addr, val);
-                self.dma_transfer(val);
+                self.dma.request(val);
+            }
+
+            // STAT: 0xFF41, the DMG writes all ones for a cycle first.
+            ADDR_PPU_STAT => {
+                trace!("write [0x{:04X}] <- 0x{:02X} (STAT)", addr, val);
+                if !self.model.is_cgb() {
+                    self.ppu.stat_write_glitch();
+                }
+                self.ppu.write_byte(addr, val);
             }
 
-            // PPU Registers: 0xFF40..=0xFF4B (excluding DMA)
-            ADDR_PPU_LCDC..=ADDR_PPU_WX => {
+            // PPU Registers: 0xFF40..=0xFF4B (excluding DMA), CGB VBK and palettes
+            ADDR_PPU_LCDC..=ADDR_PPU_WX | ADDR_PPU_VBK | ADDR_PPU_BCPS..=ADDR_PPU_OCPD => {
                 trace!("write [0x{:04X}] <- 0x{:02X} (PPU REG)", addr, val);
                 self.ppu.write_byte(addr, val);
             }
 
+            // CGB VRAM DMA: 0xFF51..=0xFF55
+            ADDR_PPU_HDMA1..=ADDR_PPU_HDMA5 => {
+                trace!("write [0x{:04X}] <- 0x{:02X} (HDMA)", addr, val);
+                if self.cgb_mode() {
+                    self.hdma.write(addr, val);
+                }
+            }
+
+            // CGB speed switch: 0xFF4D, only the prepare bit is writable.
+            ADDR_SYS_KEY1 => {
+                trace!("write [0x{:04X}] <- 0x{:02X} (KEY1)", addr, val);
+                if self.cgb_mode() {
+                    self.speed_switch_armed = (val & 0x01) != 0;
+                }
+            }
+
+            // CGB WRAM bank: 0xFF70
+            ADDR_SYS_SVBK => {
+                trace!("write [0x{:04X}] <- 0x{:02X} (SVBK)", addr, val);
+                if self.cgb_mode() {
+                    self.svbk = val & 0x07;
+                }
+            }
+
+            // Boot ROM disable: 0xFF50, can't be mapped back in.
+            ADDR_SYS_BOOT => {
+                trace!("write [0x{:04X}] <- 0x{:02X} (BOOT)", addr, val);
+                if val != 0 {
+                    self.boot_rom_mapped = false;
+                }
+            }
+
             // High RAM: 0xFF80..=0xFFFE
             ADDR_MEM_HRAM_START..=ADDR_MEM_HRAM_END => {
                 trace!("write [0x{:04X}] <- 0x{:02X} (HRAM)", addr, val);
@@ -0,0 +1,109 @@
+/*
+OAM DMA, started by writing the high byte of the source to 0xFF46.
+
+M-cycle,Content
+W,Write to 0xFF46
+W+1,Setup, a transfer that is already running keeps going
+W+2..W+161,One byte per M-cycle from XX00+i to FE00+i
+
+While a transfer runs the CPU only reaches HRAM and the I/O registers,
+anything below 0xFF00 reads 0xFF and ignores writes. Sources from 0xE000
+up read the echo of WRAM.
+*/
+
+use crate::state::{SaveState, StateError, StateReader, StateWriter};
+
+pub const OAM_DMA_LEN: u8 = 160;
+
+#[derive(Default)]
+pub struct OamDma {
+    /// Last value written to 0xFF46, reads back unchanged.
+    pub reg: u8,
+    source: u16,
+    /// Next byte to copy, `None` while idle.
+    index: Option<u8>,
+    /// Set by the write, armed once the writing instruction has finished.
+    requested: bool,
+    /// M-cycles until the requested transfer starts.
+    start_delay: u8,
+    /// T-cycles not yet making up a full M-cycle.
+    t_cycles: u8,
+}
+
+impl OamDma {
+    pub fn request(&mut self, val: u8) {
+        self.reg = val;
+        self.requested = true;
+    }
+
+    pub fn active(&self) -> bool {
+        self.index.is_some()
+    }
+
+    /// Whether the CPU is cut off from `addr` by a running transfer.
+    pub fn blocks(&self, addr: u16) -> bool {
+        self.active() && addr < 0xFF00
+    }
+
+    /// Turns `cycles` T-cycles into whole M-cycles to step through.
+    pub fn m_cycles(&mut self, cycles: u8) -> u8 {
+        let total = self.t_cycles as u16 + cycles as u16;
+        self.t_cycles = (total % 4) as u8;
+        (total / 4) as u8
+    }
+
+    /// Advances one M-cycle, returns the source address and OAM index of
+    /// the byte to copy in it, if any.
+    pub fn step(&mut self) -> Option<(u16, usize)> {
+        let copy = self.index.map(|i| (self.source + i as u16, i as usize));
+        if let Some(i) = self.index {
+            self.index = (i + 1 < OAM_DMA_LEN).then_some(i + 1);
+        }
+
+        if self.start_delay > 0 {
+            self.start_delay -= 1;
+            if self.start_delay == 0 {
+                // Restarting drops whatever the old transfer had left.
+                self.source = (self.reg as u16) << 8;
+                self.index = Some(0);
+            }
+        }
+        copy
+    }
+
+    /// The bus only ticks once an instruction is done, so a write made by it
+    /// counts as landing on its last M-cycle and the setup follows from here.
+    pub fn arm(&mut self) {
+        if self.requested {
+            self.requested = false;
+            self.start_delay = 1;
+        }
+    }
+}
+
+impl SaveState for OamDma {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.reg);
+        w.u16(self.source);
+        w.bool(self.index.is_some());
+        w.u8(self.index.unwrap_or(0));
+        w.bool(self.requested);
+        w.u8(self.start_delay);
+        w.u8(self.t_cycles);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.reg = r.u8()?;
+        self.source = r.u16()?;
+        let active = r.bool()?;
+        let index = r.u8()?;
+        if index >= OAM_DMA_LEN {
+            return Err(StateError::Corrupt("OAM DMA index"));
+        }
+        self.index = active.then_some(index);
+        self.requested = r.bool()?;
+        self.start_delay = r.u8()?;
+        self.t_cycles = r.u8()? % 4;
+        Ok(())
+    }
+}
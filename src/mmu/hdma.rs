@@ -0,0 +1,187 @@
+/*
+Source: https://gbdev.io/pandocs/CGB_Registers.html#lcd-vram-dma-transfers
+
+CGB mode only, copies blocks of 16 bytes from ROM/RAM into the VRAM bank selected by VBK.
+
+Register,Address,Content
+HDMA1/HDMA2,0xFF51/0xFF52,Source high/low, the low 4 bits are ignored
+HDMA3/HDMA4,0xFF53/0xFF54,Destination in VRAM, only bits 4-12 are used
+HDMA5,0xFF55,Write: bit 7 picks H-Blank (1) or general purpose (0), bits 0-6 are blocks - 1
+HDMA5,0xFF55,Read: bit 7 clear while an H-Blank transfer runs, bits 0-6 are blocks left - 1
+
+Mode,Timing
+General purpose,Every block at once, the CPU is stopped for 8 M-cycles per block
+H-Blank,One block at the start of each H-Blank on lines 0-143, the CPU is stopped meanwhile
+
+Writing HDMA5 with bit 7 clear during an H-Blank transfer cancels it, HDMA5
+then reads 0x80 ORed with the blocks that were left. A finished transfer reads 0xFF.
+*/
+
+use crate::state::{SaveState, StateError, StateReader, StateWriter};
+
+pub const HDMA_BLOCK_LEN: u16 = 16;
+/// T-cycles the CPU is stopped for per block, in single speed.
+pub const HDMA_BLOCK_CYCLES: u8 = 32;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HdmaMode {
+    #[default]
+    Idle,
+    General,
+    HBlank,
+}
+
+pub struct Hdma {
+    source: u16,
+    /// Offset into VRAM, 0x0000-0x1FF0.
+    dest: u16,
+    /// Blocks left minus one, as HDMA5 shows it.
+    blocks: u8,
+    pub mode: HdmaMode,
+}
+
+impl Default for Hdma {
+    /// HDMA5 reads 0xFF at power-on, like after a finished transfer.
+    fn default() -> Self {
+        Self {
+            source: 0,
+            dest: 0,
+            blocks: 0x7F,
+            mode: HdmaMode::Idle,
+        }
+    }
+}
+
+impl Hdma {
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0xFF55 if self.mode == HdmaMode::Idle => 0x80 | self.blocks,
+            0xFF55 => self.blocks,
+            // HDMA1-HDMA4 are write only.
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0xFF51 => self.source = (self.source & 0x00F0) | ((val as u16) << 8),
+            0xFF52 => self.source = (self.source & 0xFF00) | (val & 0xF0) as u16,
+            0xFF53 => self.dest = (self.dest & 0x00F0) | (((val & 0x1F) as u16) << 8),
+            0xFF54 => self.dest = (self.dest & 0x1F00) | (val & 0xF0) as u16,
+            0xFF55 => self.start(val),
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, val: u8) {
+        if self.mode == HdmaMode::HBlank && (val & 0x80) == 0 {
+            self.mode = HdmaMode::Idle;
+            return;
+        }
+        self.blocks = val & 0x7F;
+        self.mode = if (val & 0x80) != 0 {
+            HdmaMode::HBlank
+        } else {
+            HdmaMode::General
+        };
+    }
+
+    pub fn active(&self) -> bool {
+        self.mode != HdmaMode::Idle
+    }
+
+    /// Bytes still to be copied, 0 once idle.
+    pub fn remaining(&self) -> u16 {
+        if self.active() {
+            (self.blocks as u16 + 1) * HDMA_BLOCK_LEN
+        } else {
+            0
+        }
+    }
+
+    /// Source address and VRAM offset of the next block, moves past it.
+    pub fn next_block(&mut self) -> Option<(u16, u16)> {
+        if !self.active() {
+            return None;
+        }
+        let block = (self.source, self.dest);
+        self.source = self.source.wrapping_add(HDMA_BLOCK_LEN);
+        self.dest = (self.dest + HDMA_BLOCK_LEN) & 0x1FF0;
+        if self.blocks == 0 {
+            self.mode = HdmaMode::Idle;
+            self.blocks = 0x7F;
+        } else {
+            self.blocks -= 1;
+        }
+        Some(block)
+    }
+}
+
+impl SaveState for Hdma {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u16(self.source);
+        w.u16(self.dest);
+        w.u8(self.blocks);
+        w.u8(self.mode as u8);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.source = r.u16()? & 0xFFF0;
+        self.dest = r.u16()? & 0x1FF0;
+        self.blocks = r.u8()? & 0x7F;
+        self.mode = match r.u8()? {
+            0 => HdmaMode::Idle,
+            1 => HdmaMode::General,
+            2 => HdmaMode::HBlank,
+            _ => return Err(StateError::Corrupt("HDMA mode")),
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registers_mask_addresses() {
+        let mut hdma = Hdma::default();
+        for (addr, val) in [
+            (0xFF51, 0xC1),
+            (0xFF52, 0x2F),
+            (0xFF53, 0xFF),
+            (0xFF54, 0x3F),
+        ] {
+            hdma.write(addr, val);
+        }
+        hdma.write(0xFF55, 0x00);
+        assert_eq!(hdma.next_block(), Some((0xC120, 0x1F30)));
+        assert_eq!(hdma.read(0xFF51), 0xFF, "Write only");
+    }
+
+    #[test]
+    fn test_hblank_transfer_counts_down_and_cancels() {
+        let mut hdma = Hdma::default();
+        hdma.write(0xFF55, 0x80 | 0x02);
+        assert_eq!(hdma.read(0xFF55), 0x02);
+        assert_eq!(hdma.remaining(), 48);
+
+        hdma.next_block();
+        assert_eq!(hdma.read(0xFF55), 0x01);
+
+        hdma.write(0xFF55, 0x00);
+        assert_eq!(hdma.read(0xFF55), 0x81, "Cancelled with a block left over");
+        assert_eq!(hdma.remaining(), 0);
+    }
+
+    #[test]
+    fn test_finished_transfer_reads_ff() {
+        let mut hdma = Hdma::default();
+        assert_eq!(hdma.read(0xFF55), 0xFF, "Power-on");
+        hdma.write(0xFF55, 0x01);
+        assert!(hdma.next_block().is_some());
+        assert!(hdma.next_block().is_some());
+        assert_eq!(hdma.next_block(), None);
+        assert_eq!(hdma.read(0xFF55), 0xFF);
+    }
+}
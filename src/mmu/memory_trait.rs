@@ -1,6 +1,7 @@
 use log::trace;
 
 use crate::constants::{ADDR_TIMER_TIMA, IE_ADDR, IF_ADDR};
+use crate::ppu::OamBugAccess;
 
 pub trait Memory {
     /// Read directly from the memory without allowing the bus to route
@@ -12,6 +13,16 @@ pub trait Memory {
     fn tick_components(&mut self, cycles: u8) -> bool;
     fn write_div(&mut self);
 
+    /// Reports a CPU bus cycle that can trigger the OAM corruption bug,
+    /// the read or write itself still goes through `read_byte`/`write_byte`.
+    fn oam_bug(&mut self, _addr: u16, _access: OamBugAccess) {}
+
+    /// Called by STOP, switches the CGB speed if KEY1 asked for it.
+    /// Returns true if the speed changed, STOP then doesn't stop.
+    fn speed_switch(&mut self) -> bool {
+        false
+    }
+
     // Helper for 16-bit reads (Little Endian)
     fn read_u16(&self, addr: u16) -> u16 {
         let low = self.read_byte(addr) as u16;
@@ -1,5 +1,9 @@
 mod bus;
+mod dma;
+mod hdma;
 mod memory_trait;
 
 pub use bus::Bus;
+pub use dma::{OAM_DMA_LEN, OamDma};
+pub use hdma::{HDMA_BLOCK_CYCLES, HDMA_BLOCK_LEN, Hdma, HdmaMode};
 pub use memory_trait::Memory;
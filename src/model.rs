@@ -0,0 +1,45 @@
+/*
+Hardware models, source: https://gbdev.io/pandocs/Power_Up_Sequence.html
+
+Model,Hardware
+DMG0,Early original Game Boy, only sold in Japan
+DMG,Original Game Boy (revisions A, B, C)
+MGB,Game Boy Pocket and Light
+SGB,Super Game Boy
+CGB,Game Boy Color, running a CGB cartridge
+*/
+
+use clap::ValueEnum;
+
+/// Which console is emulated. The boot ROMs differ, so the registers
+/// they leave behind do too, and test ROMs check for them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Model {
+    Dmg0,
+    #[default]
+    Dmg,
+    Mgb,
+    Sgb,
+    Cgb,
+}
+
+impl Model {
+    pub fn is_cgb(self) -> bool {
+        self == Model::Cgb
+    }
+
+    pub fn is_sgb(self) -> bool {
+        self == Model::Sgb
+    }
+
+    /// DIV's internal counter when the boot ROM hands over to the cartridge.
+    /// The SGB and CGB values haven't been measured, those models only start
+    /// right by running their boot ROM.
+    pub fn post_boot_div(self) -> Option<u16> {
+        match self {
+            Model::Dmg0 => Some(0x1830),
+            Model::Dmg | Model::Mgb => Some(0xABCC),
+            Model::Sgb | Model::Cgb => None,
+        }
+    }
+}
@@ -0,0 +1,175 @@
+/*
+Source: https://gbdev.io/pandocs/CGB_Registers.html, https://gbdev.io/pandocs/Palettes.html
+
+Only reachable in CGB mode, a DMG game on a CGB reads 0xFF from all of them.
+
+Register,Address,Content
+VBK,0xFF4F,Bit 0 picks the VRAM bank the CPU sees
+BCPS/OCPS,0xFF68/0xFF6A,Bits 0-5 index palette RAM, bit 7 increments the index after each data write
+BCPD/OCPD,0xFF69/0xFF6B,Palette RAM at the index, out of reach during mode 3
+
+Palette RAM holds 8 palettes of 4 little endian RGB555 colors, for BG and OBJ each.
+
+The BG map attributes live in VRAM bank 1, at the address of the tile id in bank 0.
+
+Bit,Content
+0-2,BG palette
+3,Tile data bank
+5,Horizontal flip
+6,Vertical flip
+7,BG and window over OBJ, unless LCDC bit 0 is clear
+*/
+
+use crate::ppu::Ppu;
+use crate::state::{SaveState, StateError, StateReader, StateWriter};
+
+pub const ATTR_PALETTE: u8 = 0x07;
+pub const ATTR_BANK: u8 = 0x08;
+pub const ATTR_X_FLIP: u8 = 0x20;
+pub const ATTR_Y_FLIP: u8 = 0x40;
+pub const ATTR_PRIORITY: u8 = 0x80;
+
+/// The DMG shades, white to black, as RGB555.
+pub const DMG_SHADES: [u16; 4] = [0x7FFF, 0x56B5, 0x294A, 0x0000];
+
+const AUTO_INCREMENT: u8 = 0x80;
+
+/// BG or OBJ palette RAM with its BCPS/OCPS index register.
+pub struct ColorPalettes {
+    pub data: [u8; 64],
+    spec: u8,
+}
+
+impl Default for ColorPalettes {
+    fn default() -> Self {
+        Self {
+            data: [0; 64],
+            spec: 0,
+        }
+    }
+}
+
+impl ColorPalettes {
+    pub fn read_spec(&self) -> u8 {
+        self.spec | 0x40
+    }
+
+    pub fn write_spec(&mut self, val: u8) {
+        self.spec = val & (AUTO_INCREMENT | 0x3F);
+    }
+
+    pub fn read_data(&self) -> u8 {
+        self.data[(self.spec & 0x3F) as usize]
+    }
+
+    /// A write blocked by mode 3 is lost, the index still moves on.
+    pub fn write_data(&mut self, val: u8, accessible: bool) {
+        if accessible {
+            self.data[(self.spec & 0x3F) as usize] = val;
+        }
+        if (self.spec & AUTO_INCREMENT) != 0 {
+            self.spec = AUTO_INCREMENT | (self.spec.wrapping_add(1) & 0x3F);
+        }
+    }
+
+    pub fn color(&self, palette: u8, color_idx: u8) -> u16 {
+        let i = (palette & ATTR_PALETTE) as usize * 8 + color_idx as usize * 2;
+        u16::from_le_bytes([self.data[i], self.data[i + 1]]) & 0x7FFF
+    }
+}
+
+impl SaveState for ColorPalettes {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.data);
+        w.u8(self.spec);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        r.bytes_into(&mut self.data)?;
+        self.spec = r.u8()? & (AUTO_INCREMENT | 0x3F);
+        Ok(())
+    }
+}
+
+/// Closest DMG shade to an RGB555 color, keeps the 2-bit frame buffer
+/// usable for grayscale frontends in CGB mode.
+pub fn shade_of(rgb: u16) -> u8 {
+    let (r, g, b) = (rgb & 0x1F, (rgb >> 5) & 0x1F, (rgb >> 10) & 0x1F);
+    let luma = (r * 3 + g * 6 + b) / 10; // 0-31
+    3 - (luma * 4 / 32) as u8
+}
+
+impl Ppu {
+    /// VRAM bank 0 or 1, as read by the fetchers.
+    pub(super) fn vram_bank(&self, bank: u8) -> &[u8; 0x2000] {
+        if (bank & 1) != 0 {
+            &self.vram1
+        } else {
+            &self.vram
+        }
+    }
+
+    /// The bank selected by VBK, as seen by the CPU.
+    pub(super) fn cpu_vram_mut(&mut self) -> &mut [u8; 0x2000] {
+        if (self.vbk & 1) != 0 {
+            &mut self.vram1
+        } else {
+            &mut self.vram
+        }
+    }
+
+    pub(super) fn read_cgb_register(&self, addr: u16) -> u8 {
+        if !self.cgb_mode {
+            return 0xFF;
+        }
+        match addr {
+            0xFF4F => 0xFE | self.vbk,
+            0xFF68 => self.bg_palettes.read_spec(),
+            0xFF69 if self.vram_accessible() => self.bg_palettes.read_data(),
+            0xFF6A => self.obj_palettes.read_spec(),
+            0xFF6B if self.vram_accessible() => self.obj_palettes.read_data(),
+            _ => 0xFF,
+        }
+    }
+
+    pub(super) fn write_cgb_register(&mut self, addr: u16, val: u8) {
+        if !self.cgb_mode {
+            return;
+        }
+        let accessible = self.vram_accessible();
+        match addr {
+            0xFF4F => self.vbk = val & 0x01,
+            0xFF68 => self.bg_palettes.write_spec(val),
+            0xFF69 => self.bg_palettes.write_data(val, accessible),
+            0xFF6A => self.obj_palettes.write_spec(val),
+            0xFF6B => self.obj_palettes.write_data(val, accessible),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_palette_auto_increment() {
+        let mut pal = ColorPalettes::default();
+        pal.write_spec(0x80 | 0x3E);
+        pal.write_data(0x1F, true);
+        pal.write_data(0x00, true);
+        assert_eq!(pal.read_spec(), 0x80 | 0x40, "Wraps around to 0");
+        assert_eq!(pal.color(7, 3), 0x001F);
+
+        pal.write_data(0xAA, false);
+        assert_eq!(pal.data[0], 0x00, "Blocked write is lost");
+        assert_eq!(pal.read_spec() & 0x3F, 1, "Index moves anyway");
+    }
+
+    #[test]
+    fn test_shade_of() {
+        for (shade, rgb) in DMG_SHADES.iter().enumerate() {
+            assert_eq!(shade_of(*rgb), shade as u8);
+        }
+    }
+}
@@ -0,0 +1,499 @@
+/*
+Pixel FIFO, mode 3 is stepped one dot at a time.
+
+Dot,Content
+0-5,First tile fetch, thrown away by the hardware
+6-11,Fetch of the first real tile: tile id, data low, data high (2 dots each)
+12-171,One pixel shifted out per dot, the fetcher refills the FIFO every 8 dots once it runs empty
+
+Anything below makes mode 3 longer than the minimal 172 dots.
+
+Cause,Penalty
+SCX % 8,The first pixels of the line are shifted out and discarded (0-7 dots)
+Window,The FIFO is cleared and the fetcher restarts on the window map (6 dots)
+Sprite,The shifter stalls until the BG fetch is done, then the sprite row is fetched (6-11 dots)
+
+BG pixels are kept as one byte: bits 0-1 color index, bits 2-4 CGB palette, bit 7 CGB priority.
+*/
+
+use std::collections::VecDeque;
+
+use crate::ppu::Ppu;
+use crate::ppu::cgb::{
+    ATTR_BANK, ATTR_PALETTE, ATTR_PRIORITY, ATTR_X_FLIP, ATTR_Y_FLIP, DMG_SHADES, shade_of,
+};
+use crate::state::{SaveState, StateError, StateReader, StateWriter};
+
+const STARTUP_DOTS: u8 = 6;
+const MAX_SPRITES_PER_LINE: usize = 10;
+const SPRITE_FETCH_DOTS: u8 = 6;
+/// Fetcher step at which the tile data is about to be complete.
+const FETCHER_DATA_HIGH: u8 = 5;
+const FETCHER_PUSH: u8 = 6;
+
+/// A sprite found by the OAM scan, attributes are read from OAM when fetched.
+#[derive(Clone, Copy)]
+struct Sprite {
+    oam_index: u8,
+    x: u8,
+    fetched: bool,
+}
+
+impl Sprite {
+    /// Screen X at which the sprite is fetched, sprites partially off the left edge go first.
+    fn fetch_x(&self) -> Option<u8> {
+        (self.x < 168).then(|| self.x.saturating_sub(8))
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct ObjPixel {
+    color_idx: u8,
+    attrs: u8,
+    /// CGB mode priority, the lower OAM index wins.
+    oam_index: u8,
+}
+
+#[derive(Default)]
+pub struct PixelFifo {
+    bg: VecDeque<u8>,
+    obj: VecDeque<ObjPixel>,
+    fetcher_step: u8,
+    fetcher_x: u8,
+    tile_id: u8,
+    /// BG map attributes of the tile being fetched, 0 outside of CGB mode.
+    tile_attrs: u8,
+    tile_lo: u8,
+    tile_hi: u8,
+    /// Next pixel on the line to be output.
+    lx: u8,
+    discard: u8,
+    startup: u8,
+    in_window: bool,
+    sprites: Vec<Sprite>,
+    sprite_fetch: u8,
+    current_sprite: usize,
+    pub drawing: bool,
+}
+
+impl Ppu {
+    /// OAM scan and pipeline reset, called on the first dot of mode 3.
+    pub(super) fn start_mode3(&mut self) {
+        if self.ly == self.wy {
+            self.window_y_triggered = true;
+        }
+
+        let height: i16 = if (self.lcdc & 0x04) != 0 { 16 } else { 8 };
+        let ly = self.ly as i16;
+        let fifo = &mut self.fifo;
+        fifo.sprites.clear();
+        // Only Y is checked, sprites off the screen horizontally still take up a slot.
+        for i in 0..40 {
+            if fifo.sprites.len() == MAX_SPRITES_PER_LINE {
+                break;
+            }
+            let top = self.oam[i * 4] as i16 - 16;
+            if ly >= top && ly < top + height {
+                fifo.sprites.push(Sprite {
+                    oam_index: i as u8,
+                    x: self.oam[i * 4 + 1],
+                    fetched: false,
+                });
+            }
+        }
+
+        fifo.bg.clear();
+        fifo.obj.clear();
+        fifo.fetcher_step = 0;
+        fifo.fetcher_x = 0;
+        fifo.lx = 0;
+        fifo.discard = self.scx & 0x07;
+        fifo.startup = STARTUP_DOTS;
+        fifo.in_window = false;
+        fifo.sprite_fetch = 0;
+        fifo.drawing = true;
+    }
+
+    /// Advances mode 3 by one dot, `fifo.drawing` is cleared once the line is complete.
+    pub(super) fn step_mode3(&mut self) {
+        if self.fifo.startup > 0 {
+            self.fifo.startup -= 1;
+            return;
+        }
+
+        // The BG fetcher and the shifter are paused while a sprite is fetched.
+        if self.fifo.sprite_fetch > 0 {
+            self.fifo.sprite_fetch -= 1;
+            if self.fifo.sprite_fetch == 0 {
+                self.fetch_sprite(self.fifo.current_sprite);
+            }
+            return;
+        }
+
+        if !self.fifo.in_window
+            && (self.lcdc & 0x20) != 0
+            && self.window_y_triggered
+            && self.fifo.lx as u16 + 7 >= self.wx as u16
+        {
+            // Restart the fetcher on the window, WX below 7 cuts off its first columns.
+            self.fifo.in_window = true;
+            self.fifo.bg.clear();
+            self.fifo.fetcher_step = 0;
+            self.fifo.fetcher_x = 0;
+            if self.fifo.lx == 0 {
+                self.fifo.discard = 7u8.saturating_sub(self.wx);
+            }
+        }
+
+        let pending_sprite = self.pending_sprite();
+        if let Some(i) = pending_sprite
+            && !self.fifo.bg.is_empty()
+            && self.fifo.fetcher_step >= FETCHER_DATA_HIGH
+        {
+            self.fifo.sprites[i].fetched = true;
+            self.fifo.current_sprite = i;
+            self.fifo.sprite_fetch = SPRITE_FETCH_DOTS - 1;
+            return;
+        }
+
+        self.step_fetcher();
+        if pending_sprite.is_some() {
+            return;
+        }
+
+        let Some(bg_pixel) = self.fifo.bg.pop_front() else {
+            return;
+        };
+        if self.fifo.discard > 0 {
+            self.fifo.discard -= 1;
+            return;
+        }
+        let obj = self.fifo.obj.pop_front();
+        let (shade, rgb) = if self.cgb_mode {
+            let rgb = self.mix_pixel_cgb(bg_pixel, obj);
+            (shade_of(rgb), rgb)
+        } else {
+            let shade = self.mix_pixel(bg_pixel, obj);
+            (shade, DMG_SHADES[shade as usize])
+        };
+        let i = self.ly as usize * 160 + self.fifo.lx as usize;
+        self.frame_buffer[i] = shade;
+        self.frame_buffer_rgb[i] = rgb;
+
+        self.fifo.lx += 1;
+        if self.fifo.lx == 160 {
+            self.fifo.drawing = false;
+            // The window keeps its own line counter, lines it was hidden on don't count.
+            if self.fifo.in_window {
+                self.window_line = self.window_line.wrapping_add(1);
+            }
+        }
+    }
+
+    /// Runs the rest of mode 3 at once.
+    pub(super) fn finish_mode3(&mut self) {
+        while self.fifo.drawing {
+            self.step_mode3();
+        }
+    }
+
+    fn pending_sprite(&self) -> Option<usize> {
+        if (self.lcdc & 0x02) == 0 {
+            return None;
+        }
+        // Sprites sharing a fetch position go leftmost first, then by OAM index.
+        self.fifo
+            .sprites
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| !s.fetched && s.fetch_x() == Some(self.fifo.lx))
+            .min_by_key(|(i, s)| (s.x, *i))
+            .map(|(i, _)| i)
+    }
+
+    fn step_fetcher(&mut self) {
+        match self.fifo.fetcher_step {
+            1 => {
+                self.fifo.tile_id = self.read_tile_id(0);
+                self.fifo.tile_attrs = if self.cgb_mode {
+                    self.read_tile_id(1)
+                } else {
+                    0
+                };
+            }
+            3 => self.fifo.tile_lo = self.read_tile_data(0),
+            5 => self.fifo.tile_hi = self.read_tile_data(1),
+            FETCHER_PUSH => {
+                // On DMG the row is only pushed once the FIFO has run empty.
+                if !self.fifo.bg.is_empty() {
+                    return;
+                }
+                let (lo, hi) = (self.fifo.tile_lo, self.fifo.tile_hi);
+                let attrs = self.fifo.tile_attrs;
+                let extra = ((attrs & ATTR_PALETTE) << 2) | (attrs & ATTR_PRIORITY);
+                let pixel = |bit: u8| (((hi >> bit) & 1) << 1) | ((lo >> bit) & 1) | extra;
+                if (attrs & ATTR_X_FLIP) != 0 {
+                    self.fifo.bg.extend((0..8).map(pixel));
+                } else {
+                    self.fifo.bg.extend((0..8).rev().map(pixel));
+                }
+                self.fifo.fetcher_x = self.fifo.fetcher_x.wrapping_add(1);
+                self.fifo.fetcher_step = 0;
+                return;
+            }
+            _ => {}
+        }
+        self.fifo.fetcher_step += 1;
+    }
+
+    /// Map coordinates of the tile being fetched, registers are read at fetch time
+    /// so mid-line writes land on the next tile.
+    fn fetch_position(&self) -> (u16, u8, u8) {
+        if self.fifo.in_window {
+            let map_base = if (self.lcdc & 0x40) != 0 {
+                0x9C00
+            } else {
+                0x9800
+            };
+            (map_base, self.fifo.fetcher_x, self.window_line)
+        } else {
+            let map_base = if (self.lcdc & 0x08) != 0 {
+                0x9C00
+            } else {
+                0x9800
+            };
+            let x = (self.scx / 8).wrapping_add(self.fifo.fetcher_x);
+            (map_base, x, self.ly.wrapping_add(self.scy))
+        }
+    }
+
+    /// Tile id from bank 0, or its CGB attributes from bank 1.
+    fn read_tile_id(&self, bank: u8) -> u8 {
+        let (map_base, x, y) = self.fetch_position();
+        let addr = map_base + (y as u16 / 8) * 32 + (x as u16 % 32);
+        self.vram_bank(bank)[(addr - 0x8000) as usize]
+    }
+
+    fn read_tile_data(&self, byte: u16) -> u8 {
+        let (_, _, y) = self.fetch_position();
+        let tile_id = self.fifo.tile_id;
+        // Calculate tile data address (Signed vs Unsigned)
+        let tile_addr = if (self.lcdc & 0x10) != 0 {
+            0x8000 + (tile_id as u16 * 16)
+        } else {
+            let offset = (tile_id as i8 as i16 * 16) as u16;
+            0x9000_u16.wrapping_add(offset)
+        };
+        let attrs = self.fifo.tile_attrs;
+        let row = if (attrs & ATTR_Y_FLIP) != 0 {
+            7 - (y as u16 % 8)
+        } else {
+            y as u16 % 8
+        };
+        let addr = tile_addr + row * 2 + byte;
+        self.vram_bank((attrs & ATTR_BANK) >> 3)[(addr - 0x8000) as usize]
+    }
+
+    fn fetch_sprite(&mut self, i: usize) {
+        let sprite = self.fifo.sprites[i];
+        let base = sprite.oam_index as usize * 4;
+        let top = self.oam[base].wrapping_sub(16);
+        let tile_index = self.oam[base + 2];
+        let attrs = self.oam[base + 3];
+
+        let height = if (self.lcdc & 0x04) != 0 { 16 } else { 8 };
+        let mut line_in_tile = self.ly.wrapping_sub(top) & (height - 1);
+
+        // Vertical Flip
+        if (attrs & ATTR_Y_FLIP) != 0 {
+            line_in_tile = (height - 1) - line_in_tile;
+        }
+
+        // 8x16 Mode adjustment: bit 0 of tile index is ignored
+        let final_tile_id = if height == 16 {
+            tile_index & 0xFE
+        } else {
+            tile_index
+        };
+        let data_addr = (final_tile_id as usize * 16) + (line_in_tile as usize * 2);
+        let bank = if self.cgb_mode {
+            (attrs & ATTR_BANK) >> 3
+        } else {
+            0
+        };
+        let byte1 = self.vram_bank(bank)[data_addr];
+        let byte2 = self.vram_bank(bank)[data_addr + 1];
+
+        while self.fifo.obj.len() < 8 {
+            self.fifo.obj.push_back(ObjPixel::default());
+        }
+
+        // Columns left of the screen edge are already gone.
+        let skip = 8u8.saturating_sub(sprite.x);
+        for x_offset in skip..8 {
+            let bit = if (attrs & ATTR_X_FLIP) != 0 {
+                x_offset
+            } else {
+                7 - x_offset
+            };
+            let color_idx = ((byte1 >> bit) & 0x01) | (((byte2 >> bit) & 0x01) << 1);
+
+            // DMG X-priority: sprites fetched earlier are further left (or lower in
+            // OAM on a tie), so they keep their opaque pixels. CGB goes by OAM index only.
+            let slot = &mut self.fifo.obj[(x_offset - skip) as usize];
+            let cgb_wins = self.cgb_mode && color_idx != 0 && sprite.oam_index < slot.oam_index;
+            if slot.color_idx == 0 || cgb_wins {
+                *slot = ObjPixel {
+                    color_idx,
+                    attrs,
+                    oam_index: sprite.oam_index,
+                };
+            }
+        }
+    }
+
+    /// LCDC is read per pixel, so toggling bits 0 and 1 mid-line takes effect right away.
+    fn mix_pixel(&self, bg_pixel: u8, obj: Option<ObjPixel>) -> u8 {
+        // On DMG, LCDC bit 0 clear blanks both BG and window to color 0.
+        let bg_color_idx = if (self.lcdc & 0x01) != 0 {
+            bg_pixel & 0x03
+        } else {
+            0
+        };
+        // Apply Background Palette
+        let bg_color = (self.bgp >> (bg_color_idx * 2)) & 0b11;
+        let obj = obj.filter(|obj| obj.color_idx != 0 && (self.lcdc & 0x02) != 0);
+        let Some(obj) = obj else {
+            return bg_color;
+        };
+
+        // Sprite-to-BG Priority, checked on the color index before the palette.
+        if (obj.attrs & 0x80) != 0 && bg_color_idx != 0 {
+            return bg_color;
+        }
+
+        let palette = if (obj.attrs & 0x10) != 0 {
+            self.obp1
+        } else {
+            self.obp0
+        };
+        (palette >> (obj.color_idx * 2)) & 0b11
+    }
+
+    /// CGB mode: LCDC bit 0 is the master priority, clear lets every sprite
+    /// on top, the BG is never blanked.
+    fn mix_pixel_cgb(&self, bg_pixel: u8, obj: Option<ObjPixel>) -> u16 {
+        let bg_color_idx = bg_pixel & 0x03;
+        let bg_color = self
+            .bg_palettes
+            .color((bg_pixel >> 2) & ATTR_PALETTE, bg_color_idx);
+        let obj = obj.filter(|obj| obj.color_idx != 0 && (self.lcdc & 0x02) != 0);
+        let Some(obj) = obj else {
+            return bg_color;
+        };
+
+        let bg_priority = (bg_pixel & ATTR_PRIORITY) != 0 || (obj.attrs & ATTR_PRIORITY) != 0;
+        if (self.lcdc & 0x01) != 0 && bg_priority && bg_color_idx != 0 {
+            return bg_color;
+        }
+        self.obj_palettes
+            .color(obj.attrs & ATTR_PALETTE, obj.color_idx)
+    }
+}
+
+impl PixelFifo {
+    /// Parts of the FIFO only used in CGB mode, saved after the rest of the PPU.
+    pub(super) fn save_cgb_state(&self, w: &mut StateWriter) {
+        w.u8(self.tile_attrs);
+        w.bytes(&self.obj.iter().map(|px| px.oam_index).collect::<Vec<_>>());
+    }
+
+    pub(super) fn load_cgb_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.tile_attrs = r.u8()?;
+        let oam_indices = r.bytes()?;
+        if oam_indices.len() != self.obj.len() {
+            return Err(StateError::Corrupt("pixel fifo OAM indices"));
+        }
+        for (px, oam_index) in self.obj.iter_mut().zip(oam_indices) {
+            px.oam_index = *oam_index;
+        }
+        Ok(())
+    }
+}
+
+impl SaveState for PixelFifo {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.bg.iter().copied().collect::<Vec<_>>());
+        w.u8(self.obj.len() as u8);
+        for px in &self.obj {
+            w.u8(px.color_idx);
+            w.u8(px.attrs);
+        }
+        for val in [
+            self.fetcher_step,
+            self.fetcher_x,
+            self.tile_id,
+            self.tile_lo,
+            self.tile_hi,
+            self.lx,
+            self.discard,
+            self.startup,
+            self.sprite_fetch,
+            self.current_sprite as u8,
+        ] {
+            w.u8(val);
+        }
+        w.bool(self.in_window);
+        w.bool(self.drawing);
+        w.u8(self.sprites.len() as u8);
+        for sprite in &self.sprites {
+            w.u8(sprite.oam_index);
+            w.u8(sprite.x);
+            w.bool(sprite.fetched);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.bg = r.bytes()?.iter().copied().collect();
+        if self.bg.len() > 16 {
+            return Err(StateError::Corrupt("pixel fifo overflow"));
+        }
+        self.obj.clear();
+        for _ in 0..r.u8()? {
+            self.obj.push_back(ObjPixel {
+                color_idx: r.u8()?,
+                attrs: r.u8()?,
+                oam_index: 0,
+            });
+        }
+        self.fetcher_step = r.u8()?;
+        self.fetcher_x = r.u8()?;
+        self.tile_id = r.u8()?;
+        self.tile_lo = r.u8()?;
+        self.tile_hi = r.u8()?;
+        self.lx = r.u8()?;
+        self.discard = r.u8()?;
+        self.startup = r.u8()?;
+        self.sprite_fetch = r.u8()?;
+        self.current_sprite = r.u8()? as usize;
+        self.in_window = r.bool()?;
+        self.drawing = r.bool()?;
+        self.sprites.clear();
+        for _ in 0..r.u8()? {
+            self.sprites.push(Sprite {
+                oam_index: r.u8()?,
+                x: r.u8()?,
+                fetched: r.bool()?,
+            });
+        }
+        if self.lx >= 160 && self.drawing
+            || self.fetcher_step > FETCHER_PUSH
+            || self.obj.len() > 8
+            || self.sprites.len() > MAX_SPRITES_PER_LINE
+            || (self.sprite_fetch > 0 && self.current_sprite >= self.sprites.len())
+        {
+            return Err(StateError::Corrupt("pixel fifo"));
+        }
+        Ok(())
+    }
+}
@@ -1,17 +1,32 @@
+mod cgb;
+mod fifo;
+mod oam_bug;
+mod stat;
 pub mod terminal;
 
+pub use cgb::ColorPalettes;
+pub use fifo::PixelFifo;
+pub use oam_bug::OamBugAccess;
+
 use crate::constants::*;
+use crate::model::Model;
+use crate::state::{SaveState, StateError, StateReader, StateWriter};
 use core::fmt;
 use log::{trace, warn};
 
 const OAM_SIZE: usize = 0xA0; // 160 bytes.
+const MODE3_MIN_END: u32 = 80 + 172; // No fine scroll, window or sprites.
 
 pub struct Ppu {
     pub vram: [u8; 0x2000], // 8KB
+    /// Second VRAM bank, CGB mode only. Holds the BG map attributes and more tiles.
+    pub vram1: [u8; 0x2000],
     pub oam: [u8; OAM_SIZE],
     pub ly: u8,           // Current Scanline (0xFF44)
     pub dot_counter: u32, // Progress within the current line
     pub frame_buffer: [u8; 160 * 144],
+    /// The same frame in RGB555, with the DMG shades outside of CGB mode.
+    pub frame_buffer_rgb: [u16; 160 * 144],
     pub lcdc: u8,
     pub scy: u8,
     pub scx: u8,
@@ -23,6 +38,22 @@ pub struct Ppu {
     pub obp0: u8,
     pub obp1: u8,
     pub stat_line: bool,
+    /// Rising edge of the STAT line caused by a register write, reported on the next tick.
+    pub stat_irq: bool,
+    /// Internal window line counter, only advances on lines the window was drawn on.
+    pub window_line: u8,
+    /// Set once LY has matched WY this frame.
+    pub window_y_triggered: bool,
+    pub fifo: PixelFifo,
+    /// Dot on which mode 0 starts, mode 3 stretches depending on scroll, window and sprites.
+    pub mode3_end: u32,
+    /// Debug override, lets tools read and write VRAM and OAM in any mode.
+    pub debug_access: bool,
+    /// Set for a CGB running a game that supports it, see header byte 0x0143.
+    pub cgb_mode: bool,
+    pub vbk: u8,
+    pub bg_palettes: ColorPalettes,
+    pub obj_palettes: ColorPalettes,
 }
 
 impl Default for Ppu {
@@ -31,14 +62,85 @@ impl Default for Ppu {
     }
 }
 
+impl SaveState for Ppu {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.vram);
+        w.bytes(&self.oam);
+        w.u8(self.ly);
+        w.u32(self.dot_counter);
+        w.bytes(&self.frame_buffer);
+        for reg in [
+            self.lcdc, self.scy, self.scx, self.bgp, self.wx, self.wy, self.stat, self.lyc,
+            self.obp0, self.obp1,
+        ] {
+            w.u8(reg);
+        }
+        w.bool(self.stat_line);
+        w.u8(self.window_line);
+        w.bool(self.window_y_triggered);
+        self.fifo.save_state(w);
+        w.u32(self.mode3_end);
+        w.bool(self.stat_irq);
+        w.bool(self.cgb_mode);
+        w.bytes(&self.vram1);
+        w.u8(self.vbk);
+        self.bg_palettes.save_state(w);
+        self.obj_palettes.save_state(w);
+        self.fifo.save_cgb_state(w);
+        for px in self.frame_buffer_rgb {
+            w.u16(px);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        r.bytes_into(&mut self.vram)?;
+        r.bytes_into(&mut self.oam)?;
+        self.ly = r.u8()?;
+        self.dot_counter = r.u32()?;
+        r.bytes_into(&mut self.frame_buffer)?;
+        for reg in [
+            &mut self.lcdc,
+            &mut self.scy,
+            &mut self.scx,
+            &mut self.bgp,
+            &mut self.wx,
+            &mut self.wy,
+            &mut self.stat,
+            &mut self.lyc,
+            &mut self.obp0,
+            &mut self.obp1,
+        ] {
+            *reg = r.u8()?;
+        }
+        self.stat_line = r.bool()?;
+        self.window_line = r.u8()?;
+        self.window_y_triggered = r.bool()?;
+        self.fifo.load_state(r)?;
+        self.mode3_end = r.u32()?;
+        self.stat_irq = r.bool()?;
+        self.cgb_mode = r.bool()?;
+        r.bytes_into(&mut self.vram1)?;
+        self.vbk = r.u8()? & 0x01;
+        self.bg_palettes.load_state(r)?;
+        self.obj_palettes.load_state(r)?;
+        self.fifo.load_cgb_state(r)?;
+        for px in self.frame_buffer_rgb.iter_mut() {
+            *px = r.u16()?;
+        }
+        Ok(())
+    }
+}
+
 impl Ppu {
     pub fn new() -> Self {
         Self {
             vram: [0; 0x2000],
+            vram1: [0; 0x2000],
             oam: [0; OAM_SIZE],
             ly: 0,
             dot_counter: 0,
             frame_buffer: [0; 160 * 144],
+            frame_buffer_rgb: [0; 160 * 144],
             lcdc: 0,
             scy: 0,
             scx: 0,
@@ -50,6 +152,16 @@ impl Ppu {
             obp0: 0,
             obp1: 0,
             stat_line: false,
+            stat_irq: false,
+            window_line: 0,
+            window_y_triggered: false,
+            fifo: PixelFifo::default(),
+            mode3_end: MODE3_MIN_END,
+            debug_access: false,
+            cgb_mode: false,
+            vbk: 0,
+            bg_palettes: ColorPalettes::default(),
+            obj_palettes: ColorPalettes::default(),
         }
     }
 
@@ -63,9 +175,9 @@ impl Ppu {
         if self.dot_counter < 80 {
             // OAM Search: PPU is scanning OAM for sprites on the current line
             2
-        } else if self.dot_counter < 80 + 172 {
+        } else if self.dot_counter < self.mode3_end {
             // Drawing: PPU is pushing pixels to the LCD
-            // Note: On real hardware, this duration varies, but 172 is the minimum.
+            // The length varies, see the pixel FIFO for what stretches it.
             3
         } else {
             // H-Blank: Line is finished, waiting for the next scanline
@@ -89,20 +201,17 @@ impl Ppu {
         (self.lcdc & 0x80) != 0
     }
 
-    pub fn request_stat_interrupt(&mut self) {
-        todo!("");
-    }
-
-    pub fn init_post_boot(&mut self) {
+    pub fn init_post_boot(&mut self, model: Model) {
         // LCDC: 0x91 (LCD ON, Window Tile Map 0x9800, BG/Window Tile Data 0x8000, BG ON)
         self.write_byte(ADDR_PPU_LCDC, 0x91);
 
         // STAT: 0x85 (Bit 7 always 1, Bit 2 LYC=LY coincidence, Bit 0-1 Mode 1 V-Blank)
         // Note: Some logs expect 0x80 or 0x82, but 0x85 is common post-bootrom.
-        self.stat = 0x85;
-
-        // LY: 0x00
-        self.ly = 0x00;
+        // The DMG0 boot ROM is shorter and hands over mid V-Blank on line 0x91.
+        (self.stat, self.ly) = match model {
+            Model::Dmg0 => (0x81, 0x91),
+            _ => (0x85, 0x00),
+        };
 
         // LYC: 0x00
         self.lyc = 0x00;
@@ -121,42 +230,23 @@ impl Ppu {
         // Internal counters
         self.dot_counter = 0;
         self.stat_line = false;
+        self.stat_irq = false;
+        self.fifo.drawing = false;
+        self.mode3_end = MODE3_MIN_END;
+        self.reset_window();
+
+        // The CGB boot ROM leaves every color white.
+        if self.cgb_mode {
+            self.vbk = 0;
+            self.bg_palettes.data.fill(0xFF);
+            self.obj_palettes.data.fill(0xFF);
+        }
     }
 
-    pub fn update_stat_interrupt(&mut self) -> bool {
-        let mode = self.get_mode();
-
-        let lyc_int = (self.stat & 0x40) != 0 && (self.stat & 0x04) != 0;
-        let mode2_int = (self.stat & 0x20) != 0 && mode == 2;
-        let mode1_int = (self.stat & 0x10) != 0 && mode == 1;
-        let mode0_int = (self.stat & 0x08) != 0 && mode == 0;
-
-        let current_signal = lyc_int || mode2_int || mode1_int || mode0_int;
-
-        // PRESERVE the edge detection logic:
-        // Trigger is true only if the signal IS high now but WAS NOT high before.
-        let interrupt_triggered = current_signal && !self.stat_line;
-
-        // NOW update the persistent state for the next cycle
-        self.stat_line = current_signal;
-
-        interrupt_triggered
-    }
-
-    pub fn update_lyc(&mut self) {
-        // 1. Check if LY matches LYC
-        if self.ly == self.lyc {
-            // 2. Set Bit 2 of STAT (The LYC == LY flag)
-            self.stat |= 0x04;
-
-            // 3. Trigger Interrupt if Bit 6 of STAT (LYC Interrupt Source) is enabled
-            if (self.stat & 0x40) != 0 {
-                self.request_stat_interrupt();
-            }
-        } else {
-            // 4. Clear Bit 2 if they don't match
-            self.stat &= !0x04;
-        }
+    /// The window state is per frame, cleared on V-Blank and when the LCD is turned on.
+    fn reset_window(&mut self) {
+        self.window_line = 0;
+        self.window_y_triggered = false;
     }
 
     pub fn set_mode(&mut self, mode: u8) {
@@ -170,131 +260,6 @@ impl Ppu {
         // you would trigger the LCD_STAT interrupt.
     }
 
-    pub fn render_line(&mut self) {
-        let line = self.ly;
-        if line >= 144 {
-            return;
-        }
-
-        // 1. Calculate the actual vertical position in the 256px background map
-        let y_pos = line.wrapping_add(self.scy);
-        let tile_row = (y_pos / 8) as u16;
-        let pixel_row = (y_pos % 8) as u16;
-
-        let tile_map_base = if (self.lcdc & 0x08) != 0 {
-            0x9C00
-        } else {
-            0x9800
-        };
-        let bit4 = (self.lcdc & 0x10) != 0;
-        // let bit4 = true; // Force unsigned mode
-
-        for x in 0..160u8 {
-            // 2. Calculate the actual horizontal position in the 256px map
-            let x_pos = x.wrapping_add(self.scx);
-            let tile_col = (x_pos / 8) as u16;
-            let pixel_col = (x_pos % 8) as u32;
-
-            // 3. Find the tile ID in the map
-            let tile_index_addr = tile_map_base + (tile_row * 32) + tile_col;
-            let tile_id = self.vram[(tile_index_addr - 0x8000) as usize];
-
-            // 4. Calculate tile data address (Signed vs Unsigned)
-            let tile_addr = if bit4 {
-                0x8000 + (tile_id as u16 * 16)
-            } else {
-                let offset = (tile_id as i8 as i16 * 16) as u16;
-                0x9000_u16.wrapping_add(offset)
-            };
-
-            // 5. Fetch the two bytes for the specific row of the tile
-            let addr = tile_addr + (pixel_row * 2);
-            let byte1 = self.vram[(addr - 0x8000) as usize];
-            let byte2 = self.vram[(addr + 1 - 0x8000) as usize];
-
-            // 6. Extract the color index for the specific pixel
-            let bit = 7 - pixel_col;
-            let color_idx = (((byte2 >> bit) & 1) << 1) | ((byte1 >> bit) & 1);
-
-            // 7. Apply Background Palette
-            let color = (self.bgp >> (color_idx * 2)) & 0b11;
-
-            // Store color (0-3) in frame buffer
-            self.frame_buffer[line as usize * 160 + x as usize] = color;
-        }
-    }
-
-    pub fn render_sprites(&mut self) {
-        if (self.lcdc & 0x02) == 0 {
-            return;
-        }
-
-        // 1. Iterate backwards so index 0 (highest priority) is drawn last
-        for i in (0..40).rev() {
-            let base = i * 4;
-            let y_pos = self.oam[base].wrapping_sub(16);
-            let x_pos = self.oam[base + 1].wrapping_sub(8);
-            let tile_index = self.oam[base + 2];
-            let attrs = self.oam[base + 3];
-
-            let height = if (self.lcdc & 0x04) != 0 { 16 } else { 8 };
-
-            if self.ly >= y_pos && self.ly < y_pos.wrapping_add(height) {
-                let mut line_in_tile = self.ly.wrapping_sub(y_pos);
-
-                // Vertical Flip
-                if (attrs & 0x40) != 0 {
-                    line_in_tile = (height - 1) - line_in_tile;
-                }
-
-                // 8x16 Mode adjustment: bit 0 of tile index is ignored
-                let final_tile_id = if height == 16 {
-                    tile_index & 0xFE
-                } else {
-                    tile_index
-                };
-                let data_addr = (final_tile_id as u16 * 16) + (line_in_tile as u16 * 2);
-
-                let byte1 = self.vram[data_addr as usize];
-                let byte2 = self.vram[(data_addr + 1) as usize];
-
-                for x_offset in 0..8 {
-                    let bit = if (attrs & 0x20) != 0 {
-                        x_offset
-                    } else {
-                        7 - x_offset
-                    };
-                    let color_idx = ((byte1 >> bit) & 0x01) | (((byte2 >> bit) & 0x01) << 1);
-
-                    if color_idx == 0 {
-                        continue;
-                    }
-
-                    let screen_x = x_pos.wrapping_add(x_offset);
-                    if screen_x < 160 {
-                        let palette = if (attrs & 0x10) != 0 {
-                            self.obp1
-                        } else {
-                            self.obp0
-                        };
-                        let color = (palette >> (color_idx * 2)) & 0b11;
-
-                        // Sprite-to-BG Priority
-                        let buffer_idx = self.ly as usize * 160 + screen_x as usize;
-                        let bg_color_is_not_zero =
-                            self.frame_buffer[buffer_idx] != (self.bgp & 0x03);
-
-                        if (attrs & 0x80) != 0 && bg_color_is_not_zero {
-                            continue;
-                        }
-
-                        self.frame_buffer[buffer_idx] = color;
-                    }
-                }
-            }
-        }
-    }
-
     pub fn set_ly(&mut self, val: u8) {
         self.ly = val;
     }
@@ -311,6 +276,10 @@ impl Ppu {
         &self.frame_buffer
     }
 
+    pub fn get_frame_buffer_rgb(&self) -> &[u16; 23040] {
+        &self.frame_buffer_rgb
+    }
+
     pub fn tick(&mut self, cycles: u32) -> (bool, bool) {
         if !self.lcd_enabled() {
             trace!("ppu timer tick ignored, LCD disabled");
@@ -318,42 +287,45 @@ impl Ppu {
         }
 
         let mut vblank_triggered = false;
-        let mut stat_triggered = false;
+        let mut stat_triggered = std::mem::take(&mut self.stat_irq);
 
         for _ in 0..cycles {
             self.dot_counter += 1;
 
             // --- 1. Line Timing ---
             if self.dot_counter >= 456 {
+                // Mode 3 can't run into the next line, draw whatever is left.
+                if self.fifo.drawing {
+                    self.finish_mode3();
+                }
                 self.dot_counter = 0;
                 self.ly = (self.ly + 1) % 154;
-                self.update_lyc(); // Update Bit 2 of STAT
 
                 if self.ly == 144 {
                     vblank_triggered = true;
+                    self.reset_window();
                 }
             }
 
-            // --- 2. Mode Determination ---
-            let new_mode = if self.ly >= 144 {
-                1 // V-Blank
-            } else {
-                match self.dot_counter {
-                    0..=79 => 2,   // OAM Search
-                    80..=251 => 3, // Data Transfer (Approx)
-                    _ => 0,        // H-Blank
+            // --- 2. Pixel FIFO ---
+            if self.ly < 144 {
+                if self.dot_counter == 80 {
+                    self.start_mode3();
+                    self.mode3_end = u32::MAX;
+                }
+                if self.fifo.drawing {
+                    self.step_mode3();
+                    if !self.fifo.drawing {
+                        self.mode3_end = self.dot_counter + 1;
+                    }
                 }
-            };
-
-            let old_mode = self.stat & 0x03;
-            if old_mode == 3 && new_mode == 0 {
-                self.render_line();
-                self.render_sprites();
             }
+            let new_mode = self.get_mode();
 
-            // --- 3. STAT Mode Update ---
-            // Only update bits 0-1
+            // --- 3. STAT Mode and LY=LYC Update ---
+            // Only update bits 0-2
             self.stat = (self.stat & !0x03) | (new_mode & 0x03);
+            self.update_coincidence();
 
             // --- 4. STAT Interrupt (Rising Edge) ---
             if self.update_stat_interrupt() {
@@ -364,31 +336,52 @@ impl Ppu {
         (vblank_triggered, stat_triggered)
     }
 
+    /// The CPU can't reach VRAM while the PPU is drawing (mode 3).
+    pub fn vram_accessible(&self) -> bool {
+        self.debug_access || !self.lcd_enabled() || self.get_mode() != 3
+    }
+
+    /// The CPU can't reach OAM during the OAM scan and drawing (modes 2 and 3).
+    pub fn oam_accessible(&self) -> bool {
+        self.debug_access || !self.lcd_enabled() || !matches!(self.get_mode(), 2 | 3)
+    }
+
+    /// Reads VRAM or OAM ignoring the PPU mode, for debuggers and raw bus access.
+    pub fn peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0x9FFF => self.vram_bank(self.vbk)[(addr - 0x8000) as usize],
+            0xFE00..=0xFE9F => self.oam[(addr - 0xFE00) as usize],
+            _ => 0xFF,
+        }
+    }
+
+    /// Writes VRAM or OAM ignoring the PPU mode.
+    pub fn poke(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x8000..=0x9FFF => self.cpu_vram_mut()[(addr - 0x8000) as usize] = val,
+            0xFE00..=0xFE9F => self.oam[(addr - 0xFE00) as usize] = val,
+            _ => warn!("PPU: poke outside VRAM/OAM, addr: {addr:04X}"),
+        }
+    }
+
     pub fn read_byte(&self, addr: u16) -> u8 {
         match addr {
-            0x8000..=0x9FFF => self.vram[(addr - 0x8000) as usize],
-            0xFE00..=0xFE9F => {
-                // Requires a mutable reference due to OAM corrupt gameboy hardware bug.
-                // https://gbdev.io/pandocs/OAM_Corruption_Bug
-                // TODO:
-                if self.get_mode() == 2 {
-                    // OAM corruption bug.
-                    // This is the simplest, but it's not accurate.
-                    return 0xFF;
-                }
-                self.oam[(addr - 0xFE00) as usize]
-            }
+            0x8000..=0x9FFF if !self.vram_accessible() => 0xFF,
+            // The OAM corruption this can cause is reported by the CPU, see `oam_bug`.
+            0xFE00..=0xFE9F if !self.oam_accessible() => 0xFF,
+            0x8000..=0x9FFF | 0xFE00..=0xFE9F => self.peek(addr),
             ADDR_PPU_LCDC => self.lcdc,
             ADDR_PPU_STAT => self.stat,
             ADDR_PPU_SCY => self.scy,
             ADDR_PPU_SCX => self.scx,
-            ADDR_PPU_LY => self.ly, // This is the one the CPU polls most often
+            ADDR_PPU_LY => self.ly_register(), // This is the one the CPU polls most often
             ADDR_PPU_LYC => self.lyc,
             ADDR_PPU_BGP => self.bgp,
             ADDR_PPU_OBP0 => self.obp0,
             ADDR_PPU_OBP1 => self.obp1,
             ADDR_PPU_WY => self.wy,
             ADDR_PPU_WX => self.wx,
+            ADDR_PPU_VBK | ADDR_PPU_BCPS..=ADDR_PPU_OCPD => self.read_cgb_register(addr),
             _ => 0xFF, // Default for unmapped IO
         }
     }
@@ -396,8 +389,13 @@ impl Ppu {
     pub fn write_byte(&mut self, addr: u16, val: u8) {
         // info!("ppu: write_byte: addr: {:04X}, val: {:02X}", addr, val);
         match addr {
-            0x8000..=0x9FFF => self.vram[(addr - 0x8000) as usize] = val,
-            0xFE00..=0xFE9F => self.oam[(addr - 0xFE00) as usize] = val,
+            0x8000..=0x9FFF if !self.vram_accessible() => {
+                trace!("PPU: VRAM write blocked in mode 3, addr: {addr:04X}");
+            }
+            0xFE00..=0xFE9F if !self.oam_accessible() => {
+                trace!("PPU: OAM write blocked in mode 2/3, addr: {addr:04X}");
+            }
+            0x8000..=0x9FFF | 0xFE00..=0xFE9F => self.poke(addr, val),
             0xFF40 => {
                 let was_on = (self.lcdc & 0x80) != 0;
                 let is_on = (val & 0x80) != 0;
@@ -408,16 +406,21 @@ impl Ppu {
                     // LCD turned ON: Synchronization Point
                     self.dot_counter = 0;
                     self.ly = 0;
+                    self.reset_window();
 
                     // Immediately enter Mode 2 (OAM Search)
                     // Set bits 0-1 of STAT to 0b10 (2)
                     self.stat = (self.stat & !0x03) | 0x02;
+                    self.update_coincidence();
                 } else if was_on && !is_on {
                     // LCD turned OFF: Reset state
                     self.dot_counter = 0;
+                    self.fifo.drawing = false;
+                    self.mode3_end = MODE3_MIN_END;
                     self.ly = 0;
                     // Mode 0 (H-Blank) is the standard state when OFF
                     self.stat &= !0x03;
+                    self.stat_line = false;
                 }
             }
             0xFF41 => {
@@ -428,19 +431,26 @@ impl Ppu {
                 self.stat = (val & writable_mask) | (self.stat & !writable_mask) | 0x80;
 
                 // Crucial: A write to STAT can trigger an interrupt if a condition is met
-                self.update_stat_interrupt();
+                self.update_stat_interrupt_on_write();
             }
 
             0xFF42 => self.scy = val,
             0xFF43 => self.scx = val,
             0xFF44 => self.ly = 0, // Writing to LY usually resets it on real hardware
-            0xFF45 => self.lyc = val,
+            0xFF45 => {
+                self.lyc = val;
+                if self.lcd_enabled() {
+                    self.update_coincidence();
+                    self.update_stat_interrupt_on_write();
+                }
+            }
             // 0xFF46 => self.perform_dma(val),
             0xFF47 => self.bgp = val,
             0xFF48 => self.obp0 = val,
             0xFF49 => self.obp1 = val,
             0xFF4A => self.wy = val,
             0xFF4B => self.wx = val,
+            ADDR_PPU_VBK | ADDR_PPU_BCPS..=ADDR_PPU_OCPD => self.write_cgb_register(addr, val),
             _ => {
                 // Log unhandled writes instead of panicking
                 warn!("PPU: Unhandled Write_byte, addr: {addr:04X}, val: {val:02X}");
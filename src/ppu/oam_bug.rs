@@ -0,0 +1,168 @@
+/*
+Source: https://gbdev.io/pandocs/OAM_Corruption_Bug.html
+
+On DMG, putting an address in 0xFE00-0xFEFF on the bus during mode 2 garbles
+the OAM row the PPU is scanning. OAM is seen as 20 rows of 4 words (8 bytes),
+the PPU reads one row per M-cycle. Row 0 is never corrupted.
+
+Access,Effect on the current row
+Write,word0 = ((a ^ c) & (b ^ c)) ^ c; words 1-3 copied from the preceding row
+Read,word0 = b | (a & c); words 1-3 copied from the preceding row
+Read + increase,Preceding row mixed with the two around it and copied over both, then a Read
+
+a = word0 of the current row, b = word0 of the preceding row, c = word2 of the preceding row.
+*/
+
+use crate::ppu::Ppu;
+
+const ROW_LEN: usize = 8;
+const ROWS: usize = 20;
+
+/// The kind of CPU bus cycle that hit OAM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OamBugAccess {
+    /// A write, or the 16-bit INC/DEC unit putting the address on the bus.
+    Write,
+    Read,
+    /// A read in the same M-cycle as a 16-bit increment or decrement (POP, LD A,(HL+/-)).
+    ReadIncrease,
+}
+
+impl Ppu {
+    /// Corrupts OAM if `addr` is in the OAM page and the PPU is scanning it.
+    pub fn oam_bug(&mut self, addr: u16, access: OamBugAccess) {
+        if !(0xFE00..=0xFEFF).contains(&addr) || !self.lcd_enabled() || self.get_mode() != 2 {
+            return;
+        }
+        let row = (self.dot_counter as usize / 4).min(ROWS - 1);
+        if row == 0 {
+            return;
+        }
+        match access {
+            OamBugAccess::Write => {
+                let (a, b, c) = (
+                    self.oam_word(row, 0),
+                    self.oam_word(row - 1, 0),
+                    self.oam_word(row - 1, 2),
+                );
+                self.set_oam_word(row, 0, ((a ^ c) & (b ^ c)) ^ c);
+                self.copy_oam_row_tail(row - 1, row);
+            }
+            OamBugAccess::Read => self.oam_read_corruption(row),
+            OamBugAccess::ReadIncrease => {
+                if (4..ROWS - 1).contains(&row) {
+                    let a = self.oam_word(row - 2, 0);
+                    let b = self.oam_word(row - 1, 0);
+                    let c = self.oam_word(row, 0);
+                    let d = self.oam_word(row - 1, 2);
+                    self.set_oam_word(row - 1, 0, (b & (a | c | d)) | (a & c & d));
+                    let preceding = row - 1;
+                    for target in [row, row - 2] {
+                        self.oam.copy_within(
+                            preceding * ROW_LEN..(preceding + 1) * ROW_LEN,
+                            target * ROW_LEN,
+                        );
+                    }
+                }
+                self.oam_read_corruption(row);
+            }
+        }
+    }
+
+    fn oam_read_corruption(&mut self, row: usize) {
+        let (a, b, c) = (
+            self.oam_word(row, 0),
+            self.oam_word(row - 1, 0),
+            self.oam_word(row - 1, 2),
+        );
+        self.set_oam_word(row, 0, b | (a & c));
+        self.copy_oam_row_tail(row - 1, row);
+    }
+
+    fn oam_word(&self, row: usize, word: usize) -> u16 {
+        let i = row * ROW_LEN + word * 2;
+        u16::from_le_bytes([self.oam[i], self.oam[i + 1]])
+    }
+
+    fn set_oam_word(&mut self, row: usize, word: usize, val: u16) {
+        let i = row * ROW_LEN + word * 2;
+        self.oam[i..i + 2].copy_from_slice(&val.to_le_bytes());
+    }
+
+    /// Copies words 1-3 of one row over another.
+    fn copy_oam_row_tail(&mut self, from: usize, to: usize) {
+        self.oam
+            .copy_within(from * ROW_LEN + 2..(from + 1) * ROW_LEN, to * ROW_LEN + 2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ppu_in_row(row: u32) -> Ppu {
+        let mut ppu = Ppu::new();
+        for (i, b) in ppu.oam.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        ppu.enable_ldc();
+        ppu.dot_counter = row * 4;
+        ppu
+    }
+
+    #[test]
+    fn test_write_corruption() {
+        let mut ppu = ppu_in_row(5);
+        let (a, b, c) = (ppu.oam_word(5, 0), ppu.oam_word(4, 0), ppu.oam_word(4, 2));
+        ppu.oam_bug(0xFE10, OamBugAccess::Write);
+
+        assert_eq!(ppu.oam_word(5, 0), ((a ^ c) & (b ^ c)) ^ c);
+        assert_eq!(ppu.oam[42..48], ppu.oam[34..40]);
+        assert_eq!(ppu.oam[48], 48, "Next row untouched");
+    }
+
+    #[test]
+    fn test_read_corruption() {
+        let mut ppu = ppu_in_row(3);
+        let (a, b, c) = (ppu.oam_word(3, 0), ppu.oam_word(2, 0), ppu.oam_word(2, 2));
+        ppu.oam_bug(0xFEFF, OamBugAccess::Read);
+
+        assert_eq!(ppu.oam_word(3, 0), b | (a & c));
+        assert_eq!(ppu.oam[26..32], ppu.oam[18..24]);
+    }
+
+    #[test]
+    fn test_read_increase_corruption() {
+        let mut ppu = ppu_in_row(6);
+        let a = ppu.oam_word(4, 0);
+        let b = ppu.oam_word(5, 0);
+        let c = ppu.oam_word(6, 0);
+        let d = ppu.oam_word(5, 2);
+        ppu.oam_bug(0xFE00, OamBugAccess::ReadIncrease);
+
+        let mixed = (b & (a | c | d)) | (a & c & d);
+        assert_eq!(ppu.oam_word(5, 0), mixed);
+        assert_eq!(ppu.oam[32..40], ppu.oam[40..48], "Copied two rows back");
+        // The read corruption then runs on top of the copied row.
+        assert_eq!(ppu.oam_word(6, 0), mixed);
+        assert_eq!(ppu.oam[50..56], ppu.oam[42..48]);
+    }
+
+    #[test]
+    fn test_no_corruption_outside_mode_2() {
+        let mut ppu = ppu_in_row(5);
+        let before = ppu.oam;
+
+        ppu.dot_counter = 100; // Mode 3
+        ppu.oam_bug(0xFE00, OamBugAccess::Write);
+        assert_eq!(ppu.oam, before);
+
+        ppu.dot_counter = 8;
+        ppu.oam_bug(0xFF00, OamBugAccess::Write);
+        assert_eq!(ppu.oam, before, "Outside the OAM page");
+
+        ppu.dot_counter = 0;
+        ppu.oam_bug(0xFE00, OamBugAccess::Write);
+        assert_eq!(ppu.oam, before, "Row 0 is never corrupted");
+    }
+}
@@ -0,0 +1,168 @@
+/*
+Sources: https://gbdev.io/pandocs/STAT.html, mooneye-test-suite acceptance/ppu
+
+The enabled STAT sources are ORed into a single line and the interrupt is
+requested on its rising edge only. A source that comes on while another one
+already holds the line high requests nothing ("STAT blocking").
+
+Event,Timing
+LY=LYC compare,Lags LY by 4 dots and sees nothing while LY changes on lines 1-153
+LY on line 153,Reads 153 for dots 0-3 then 0 for the rest of the line
+LYC=153 match,Dots 4-7 of line 153
+LYC=0 match,From dot 12 of line 153 through line 0
+Mode 2 source on line 144,Raised for dots 0-3 together with V-Blank
+STAT write (DMG),All sources but mode 2 enabled for one cycle before the value lands
+*/
+
+use crate::ppu::Ppu;
+
+const STAT_LYC_FLAG: u8 = 0x04;
+const STAT_MODE0_SRC: u8 = 0x08;
+const STAT_MODE1_SRC: u8 = 0x10;
+const STAT_MODE2_SRC: u8 = 0x20;
+const STAT_LYC_SRC: u8 = 0x40;
+
+impl Ppu {
+    /// LY as the CPU reads it, line 153 turns into 0 early.
+    pub fn ly_register(&self) -> u8 {
+        if self.ly == 153 && self.dot_counter >= 4 {
+            0
+        } else {
+            self.ly
+        }
+    }
+
+    /// The value the LY=LYC comparator sees, `None` while LY is changing.
+    fn ly_compare(&self) -> Option<u8> {
+        match (self.ly, self.dot_counter) {
+            (0, _) => Some(0),
+            (_, 0..4) => None,
+            (153, 4..8) => Some(153),
+            (153, 8..12) => None,
+            (153, _) => Some(0),
+            (ly, _) => Some(ly),
+        }
+    }
+
+    /// Refreshes the LY=LYC flag, bit 2 of STAT.
+    pub fn update_coincidence(&mut self) {
+        if self.ly_compare() == Some(self.lyc) {
+            self.stat |= STAT_LYC_FLAG;
+        } else {
+            self.stat &= !STAT_LYC_FLAG;
+        }
+    }
+
+    /// State of the STAT line if only the sources in `enabled` were on.
+    fn stat_signal(&self, enabled: u8) -> bool {
+        let mode = self.get_mode();
+        let mode2 = mode == 2 || (self.ly == 144 && self.dot_counter < 4);
+
+        (enabled & STAT_LYC_SRC != 0 && self.stat & STAT_LYC_FLAG != 0)
+            || (enabled & STAT_MODE2_SRC != 0 && mode2)
+            || (enabled & STAT_MODE1_SRC != 0 && mode == 1)
+            || (enabled & STAT_MODE0_SRC != 0 && mode == 0)
+    }
+
+    /// Updates the STAT line, true on a rising edge.
+    pub fn update_stat_interrupt(&mut self) -> bool {
+        let signal = self.stat_signal(self.stat);
+        let rising = signal && !self.stat_line;
+        self.stat_line = signal;
+        rising
+    }
+
+    /// Like `update_stat_interrupt`, for register writes between ticks. The
+    /// edge is kept until the next `tick` hands it to the bus.
+    pub(super) fn update_stat_interrupt_on_write(&mut self) {
+        if self.lcd_enabled() && self.update_stat_interrupt() {
+            self.stat_irq = true;
+        }
+    }
+
+    /// DMG only: a STAT write briefly enables every source but mode 2, so it
+    /// fires during H-Blank, V-Blank or LY=LYC whatever gets written.
+    pub fn stat_write_glitch(&mut self) {
+        if !self.lcd_enabled() || self.stat_line {
+            return;
+        }
+        if self.stat_signal(STAT_LYC_SRC | STAT_MODE1_SRC | STAT_MODE0_SRC) {
+            self.stat_line = true;
+            self.stat_irq = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ppu_at(ly: u8, dot: u32) -> Ppu {
+        let mut ppu = Ppu::new();
+        ppu.enable_ldc();
+        ppu.ly = ly;
+        ppu.dot_counter = dot;
+        ppu
+    }
+
+    #[test]
+    fn test_line_153_reads_zero_early() {
+        let mut ppu = ppu_at(153, 0);
+        assert_eq!(ppu.ly_register(), 153);
+        ppu.dot_counter = 4;
+        assert_eq!(ppu.ly_register(), 0);
+    }
+
+    #[test]
+    fn test_lyc_match_timing_on_line_153() {
+        let mut ppu = ppu_at(153, 0);
+        let matches = |ppu: &mut Ppu, lyc: u8, dot: u32| {
+            ppu.lyc = lyc;
+            ppu.dot_counter = dot;
+            ppu.update_coincidence();
+            ppu.stat & STAT_LYC_FLAG != 0
+        };
+
+        assert!(!matches(&mut ppu, 153, 0));
+        assert!(matches(&mut ppu, 153, 4));
+        assert!(!matches(&mut ppu, 153, 8));
+        assert!(!matches(&mut ppu, 0, 8));
+        assert!(matches(&mut ppu, 0, 12));
+    }
+
+    #[test]
+    fn test_lyc_flag_clear_while_ly_changes() {
+        let mut ppu = ppu_at(42, 2);
+        ppu.lyc = 42;
+        ppu.update_coincidence();
+        assert_eq!(ppu.stat & STAT_LYC_FLAG, 0);
+
+        ppu.dot_counter = 4;
+        ppu.update_coincidence();
+        assert_ne!(ppu.stat & STAT_LYC_FLAG, 0);
+    }
+
+    #[test]
+    fn test_mode2_source_on_line_144() {
+        let mut ppu = ppu_at(144, 0);
+        ppu.stat = STAT_MODE2_SRC;
+        assert!(ppu.update_stat_interrupt());
+
+        ppu.stat_line = false;
+        ppu.dot_counter = 4;
+        assert!(!ppu.update_stat_interrupt(), "Only at the start of V-Blank");
+    }
+
+    #[test]
+    fn test_stat_write_glitch() {
+        let mut ppu = ppu_at(10, 300); // H-Blank
+        ppu.stat_write_glitch();
+        assert!(ppu.stat_irq);
+
+        let mut ppu = ppu_at(10, 20); // OAM scan, LYC doesn't match
+        ppu.lyc = 99;
+        ppu.update_coincidence();
+        ppu.stat_write_glitch();
+        assert!(!ppu.stat_irq, "Mode 2 isn't part of the glitch");
+    }
+}
@@ -0,0 +1,174 @@
+use log::debug;
+
+use crate::sgb::{SGB_HEIGHT, SGB_SCREEN_X, SGB_SCREEN_Y, SGB_WIDTH, ScreenMask, Sgb, Transfer};
+
+/// Bytes a *_TRN command takes from the screen, 256 tiles of 2 bits per pixel.
+const TRANSFER_LEN: usize = 0x1000;
+/// Border palettes 4-7 follow the 32x32 map in PCT_TRN data.
+const BORDER_PALETTES: usize = 0x800;
+
+impl Sgb {
+    /// Reads the data back from the shades on screen, the tiles are laid out
+    /// 20 to a row like the BG map games use for transfers.
+    pub(super) fn vram_transfer(&mut self, transfer: Transfer, frame_buffer: &[u8; 160 * 144]) {
+        let mut data = [0u8; TRANSFER_LEN];
+        for (tile, bytes) in data.chunks_exact_mut(16).enumerate() {
+            let (tx, ty) = (tile % 20 * 8, tile / 20 * 8);
+            for row in 0..8 {
+                let line = &frame_buffer[(ty + row) * 160 + tx..][..8];
+                for (px, shade) in line.iter().enumerate() {
+                    let bit = 7 - px;
+                    bytes[row * 2] |= (shade & 0x01) << bit;
+                    bytes[row * 2 + 1] |= ((shade >> 1) & 0x01) << bit;
+                }
+            }
+        }
+        debug!("SGB VRAM transfer {:?}", transfer);
+        match transfer {
+            Transfer::Chr(high) => {
+                let start = if high { TRANSFER_LEN } else { 0 };
+                self.border_tiles[start..start + TRANSFER_LEN].copy_from_slice(&data);
+            }
+            Transfer::Pct => {
+                let len = self.border_map.len();
+                self.border_map.copy_from_slice(&data[..len]);
+            }
+        }
+    }
+
+    fn border_color(&self, palette: usize, idx: usize) -> u16 {
+        let i = BORDER_PALETTES + (palette * 16 + idx) * 2;
+        u16::from_le_bytes([self.border_map[i], self.border_map[i + 1]]) & 0x7FFF
+    }
+
+    /// Color index 0-15 of a border pixel, 0 is transparent.
+    fn border_pixel(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        let i = ((y / 8) * 32 + x / 8) * 2;
+        let entry = u16::from_le_bytes([self.border_map[i], self.border_map[i + 1]]);
+        let tile = (entry & 0xFF) as usize;
+        let palette = ((entry >> 10) & 0x03) as usize; // 4-7
+        let px = if (entry & 0x4000) != 0 {
+            7 - x % 8
+        } else {
+            x % 8
+        };
+        let row = if (entry & 0x8000) != 0 {
+            7 - y % 8
+        } else {
+            y % 8
+        };
+        let data = &self.border_tiles[tile * 32..][..32];
+        let bit = 7 - px;
+        let idx = [
+            data[row * 2],
+            data[row * 2 + 1],
+            data[16 + row * 2],
+            data[17 + row * 2],
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |idx, (plane, b)| {
+            idx | (((b >> bit) & 1) as usize) << plane
+        });
+        (idx != 0).then_some((palette, idx))
+    }
+
+    /// Draws the game screen in its palettes with the border over it.
+    pub(super) fn render(&mut self) {
+        let backdrop = self.palettes[0][0];
+        for y in 0..SGB_HEIGHT {
+            for x in 0..SGB_WIDTH {
+                let color = match self.border_pixel(x, y) {
+                    Some((palette, idx)) => self.border_color(palette, idx),
+                    None => {
+                        let sx = x.wrapping_sub(SGB_SCREEN_X);
+                        let sy = y.wrapping_sub(SGB_SCREEN_Y);
+                        if sx < 160 && sy < 144 {
+                            self.screen_color(sx, sy)
+                        } else {
+                            backdrop
+                        }
+                    }
+                };
+                self.frame[y * SGB_WIDTH + x] = color;
+            }
+        }
+    }
+
+    fn screen_color(&self, x: usize, y: usize) -> u16 {
+        match self.mask {
+            ScreenMask::Black => 0x0000,
+            ScreenMask::Color0 => self.palettes[0][0],
+            ScreenMask::Off | ScreenMask::Freeze => {
+                let palette = self.attrs[(y / 8) * 20 + x / 8] as usize;
+                self.palettes[palette][self.screen[y * 160 + x] as usize]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sgb::tests::send;
+
+    /// A screen showing `data` the way games lay it out for a transfer.
+    fn screen_of(data: &[u8]) -> [u8; 160 * 144] {
+        let mut screen = [0u8; 160 * 144];
+        for (tile, bytes) in data.chunks_exact(16).enumerate() {
+            let (tx, ty) = (tile % 20 * 8, tile / 20 * 8);
+            for row in 0..8 {
+                for px in 0..8 {
+                    let lo = (bytes[row * 2] >> (7 - px)) & 1;
+                    let hi = (bytes[row * 2 + 1] >> (7 - px)) & 1;
+                    screen[(ty + row) * 160 + tx + px] = hi << 1 | lo;
+                }
+            }
+        }
+        screen
+    }
+
+    #[test]
+    fn test_border_upload_and_render() {
+        let mut sgb = Sgb::new();
+
+        // Tile 1: every pixel color 15.
+        let mut tiles = [0u8; TRANSFER_LEN];
+        tiles[32..64].fill(0xFF);
+        send(&mut sgb, &[0x13 << 3 | 1, 0x00]);
+        sgb.on_vblank(&screen_of(&tiles));
+
+        // Top left map entry uses tile 1 with palette 5, color 15 is 0x1234.
+        let mut pct = [0u8; TRANSFER_LEN];
+        pct[0..2].copy_from_slice(&(0x0001u16 | 1 << 10).to_le_bytes());
+        let i = BORDER_PALETTES + (16 + 15) * 2;
+        pct[i..i + 2].copy_from_slice(&0x1234u16.to_le_bytes());
+        send(&mut sgb, &[0x14 << 3 | 1]);
+        sgb.on_vblank(&screen_of(&pct));
+
+        let mut game = [0u8; 160 * 144];
+        game[0] = 3;
+        sgb.on_vblank(&game);
+        let frame = sgb.get_frame();
+        assert_eq!(frame[0], 0x1234, "Border tile");
+        assert_eq!(frame[8], sgb.palettes[0][0], "Transparent border");
+        assert_eq!(frame[SGB_SCREEN_Y * SGB_WIDTH + SGB_SCREEN_X], 0x2866);
+    }
+
+    #[test]
+    fn test_mask_en_freezes_screen() {
+        let mut sgb = Sgb::new();
+        let mut game = [0u8; 160 * 144];
+        game[0] = 1;
+        sgb.on_vblank(&game);
+        send(&mut sgb, &[0x17 << 3 | 1, 0x01]);
+        game[0] = 2;
+        sgb.on_vblank(&game);
+        let origin = SGB_SCREEN_Y * SGB_WIDTH + SGB_SCREEN_X;
+        assert_eq!(sgb.get_frame()[origin], sgb.palettes[0][1], "Frozen");
+
+        send(&mut sgb, &[0x17 << 3 | 1, 0x02]);
+        sgb.on_vblank(&game);
+        assert_eq!(sgb.get_frame()[origin], 0x0000, "Black");
+    }
+}
@@ -0,0 +1,211 @@
+use log::debug;
+
+use crate::sgb::{ScreenMask, Sgb, Transfer};
+
+const PAL01: u8 = 0x00;
+const PAL23: u8 = 0x01;
+const PAL03: u8 = 0x02;
+const PAL12: u8 = 0x03;
+const ATTR_BLK: u8 = 0x04;
+const ATTR_LIN: u8 = 0x05;
+const ATTR_DIV: u8 = 0x06;
+const ATTR_CHR: u8 = 0x07;
+const MLT_REQ: u8 = 0x11;
+const CHR_TRN: u8 = 0x13;
+const PCT_TRN: u8 = 0x14;
+const MASK_EN: u8 = 0x17;
+
+const CELLS_X: usize = 20;
+const CELLS_Y: usize = 18;
+
+impl Sgb {
+    /// Runs a complete command, `packets` holds all of its packets.
+    pub(super) fn command(&mut self, packets: &[u8]) {
+        let code = packets[0] >> 3;
+        debug!("SGB command {:02X}", code);
+        match code {
+            PAL01 => self.set_palettes(0, 1, packets),
+            PAL23 => self.set_palettes(2, 3, packets),
+            PAL03 => self.set_palettes(0, 3, packets),
+            PAL12 => self.set_palettes(1, 2, packets),
+            ATTR_BLK => self.attr_blk(packets),
+            ATTR_LIN => self.attr_lin(packets),
+            ATTR_DIV => self.attr_div(packets),
+            ATTR_CHR => self.attr_chr(packets),
+            MLT_REQ => {
+                self.players = match packets[1] & 0x03 {
+                    1 => 2,
+                    3 => 4,
+                    _ => 1,
+                };
+                self.player = 0;
+            }
+            CHR_TRN => self.transfer = Some(Transfer::Chr((packets[1] & 0x01) != 0)),
+            PCT_TRN => self.transfer = Some(Transfer::Pct),
+            MASK_EN => {
+                self.mask = match packets[1] & 0x03 {
+                    1 => ScreenMask::Freeze,
+                    2 => ScreenMask::Black,
+                    3 => ScreenMask::Color0,
+                    _ => ScreenMask::Off,
+                };
+            }
+            _ => debug!("SGB command {:02X} not supported", code),
+        }
+    }
+
+    fn set_palettes(&mut self, a: usize, b: usize, packets: &[u8]) {
+        let color = |i: usize| u16::from_le_bytes([packets[i], packets[i + 1]]) & 0x7FFF;
+        for palette in self.palettes.iter_mut() {
+            palette[0] = color(1);
+        }
+        for n in 1..4 {
+            self.palettes[a][n] = color(1 + n * 2);
+            self.palettes[b][n] = color(7 + n * 2);
+        }
+    }
+
+    fn attr_blk(&mut self, packets: &[u8]) {
+        let sets = (packets[1] & 0x1F) as usize;
+        for set in packets[2..].chunks_exact(6).take(sets) {
+            let ctrl = set[0] & 0x07;
+            let inside = set[1] & 0x03;
+            let on = (set[1] >> 2) & 0x03;
+            let outside = (set[1] >> 4) & 0x03;
+            // Only inside or only outside also changes the line between them.
+            let (ctrl, on) = match ctrl {
+                0x01 => (0x03, inside),
+                0x04 => (0x06, outside),
+                _ => (ctrl, on),
+            };
+            let (x1, y1) = (set[2] as usize & 0x1F, set[3] as usize & 0x1F);
+            let (x2, y2) = (set[4] as usize & 0x1F, set[5] as usize & 0x1F);
+            for y in 0..CELLS_Y {
+                for x in 0..CELLS_X {
+                    let within = (x1..=x2).contains(&x) && (y1..=y2).contains(&y);
+                    let edge = x == x1 || x == x2 || y == y1 || y == y2;
+                    let (bit, palette) = match (within, edge) {
+                        (true, false) => (0x01, inside),
+                        (true, true) => (0x02, on),
+                        (false, _) => (0x04, outside),
+                    };
+                    if (ctrl & bit) != 0 {
+                        self.attrs[y * CELLS_X + x] = palette;
+                    }
+                }
+            }
+        }
+    }
+
+    fn attr_lin(&mut self, packets: &[u8]) {
+        let sets = packets[1] as usize;
+        for &line in packets[2..].iter().take(sets) {
+            let n = (line & 0x1F) as usize;
+            let palette = (line >> 5) & 0x03;
+            if (line & 0x80) != 0 {
+                if n < CELLS_Y {
+                    self.attrs[n * CELLS_X..(n + 1) * CELLS_X].fill(palette);
+                }
+            } else if n < CELLS_X {
+                for y in 0..CELLS_Y {
+                    self.attrs[y * CELLS_X + n] = palette;
+                }
+            }
+        }
+    }
+
+    fn attr_div(&mut self, packets: &[u8]) {
+        let after = packets[1] & 0x03;
+        let before = (packets[1] >> 2) & 0x03;
+        let on = (packets[1] >> 4) & 0x03;
+        let rows = (packets[1] & 0x40) != 0;
+        let split = packets[2] as usize & 0x1F;
+        for y in 0..CELLS_Y {
+            for x in 0..CELLS_X {
+                let pos = if rows { y } else { x };
+                self.attrs[y * CELLS_X + x] = match pos.cmp(&split) {
+                    std::cmp::Ordering::Less => before,
+                    std::cmp::Ordering::Equal => on,
+                    std::cmp::Ordering::Greater => after,
+                };
+            }
+        }
+    }
+
+    fn attr_chr(&mut self, packets: &[u8]) {
+        let (mut x, mut y) = (packets[1] as usize, packets[2] as usize);
+        let count = u16::from_le_bytes([packets[3], packets[4]]) as usize;
+        let vertical = (packets[5] & 0x01) != 0;
+        let palettes = packets[6..]
+            .iter()
+            .flat_map(|b| [b >> 6, (b >> 4) & 0x03, (b >> 2) & 0x03, b & 0x03]);
+        for palette in palettes.take(count.min(CELLS_X * CELLS_Y)) {
+            if x >= CELLS_X || y >= CELLS_Y {
+                break;
+            }
+            self.attrs[y * CELLS_X + x] = palette;
+            if vertical {
+                y += 1;
+                if y == CELLS_Y {
+                    y = 0;
+                    x += 1;
+                }
+            } else {
+                x += 1;
+                if x == CELLS_X {
+                    x = 0;
+                    y += 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sgb::tests::send;
+
+    #[test]
+    fn test_pal_shares_color_0() {
+        let mut sgb = Sgb::new();
+        let mut packet = [0u8; 16];
+        packet[0] = PAL12 << 3 | 1;
+        for n in 0..7 {
+            packet[1 + n * 2] = n as u8 + 1;
+        }
+        send(&mut sgb, &packet);
+        assert!(sgb.palettes.iter().all(|p| p[0] == 1));
+        assert_eq!(sgb.palettes[1], [1, 2, 3, 4]);
+        assert_eq!(sgb.palettes[2], [1, 5, 6, 7]);
+        assert_eq!(sgb.palettes[0][1..], [0x265B, 0x10B5, 0x2866], "Untouched");
+    }
+
+    #[test]
+    fn test_attr_blk_inside_also_sets_border() {
+        let mut sgb = Sgb::new();
+        // Inside only, palette 2, cells (1,1)-(3,3)
+        send(&mut sgb, &[ATTR_BLK << 3 | 1, 1, 0x01, 0x02, 1, 1, 3, 3]);
+        assert_eq!(sgb.attrs[2 * CELLS_X + 2], 2);
+        assert_eq!(sgb.attrs[CELLS_X + 1], 2, "Border line follows inside");
+        assert_eq!(sgb.attrs[0], 0, "Outside untouched");
+    }
+
+    #[test]
+    fn test_attr_div_and_chr() {
+        let mut sgb = Sgb::new();
+        // Column 10 palette 1, left 2, right 3
+        send(&mut sgb, &[ATTR_DIV << 3 | 1, 0x10 | 0x08 | 0x03, 10]);
+        assert_eq!(sgb.attrs[5 * CELLS_X + 9], 2);
+        assert_eq!(sgb.attrs[5 * CELLS_X + 10], 1);
+        assert_eq!(sgb.attrs[5 * CELLS_X + 11], 3);
+
+        // 5 cells from (18,0) left to right wrap onto the next row.
+        send(
+            &mut sgb,
+            &[ATTR_CHR << 3 | 1, 18, 0, 5, 0, 0, 0b0001_1011, 0],
+        );
+        assert_eq!(sgb.attrs[18..20], [0, 1]);
+        assert_eq!(sgb.attrs[CELLS_X..CELLS_X + 4], [2, 3, 0, 2]);
+    }
+}
@@ -0,0 +1,322 @@
+/*
+Super Game Boy, source: https://gbdev.io/pandocs/SGB_Functions.html
+
+The game talks to the SNES side through the joypad register, one packet of
+16 bytes at a time. Only games with SGB flag 0x03 and old licensee 0x33 are heard.
+
+JOYP write,Meaning
+0x00,Reset pulse, starts a packet
+0x20,Bit 0 (P14 low)
+0x10,Bit 1 (P15 low)
+0x30,Between pulses
+
+Bits come LSB first, 128 bits make a packet, a 0 stop bit follows. The first
+byte of a command is its code << 3 | packets (1-7), the data of the following
+packets continues the first one.
+
+Code,Command,Content
+0x00-0x03,PAL01/PAL23/PAL03/PAL12,Color 0 shared by all palettes, then 3 colors for each of the two palettes
+0x04,ATTR_BLK,Palettes inside, on and outside of rectangles
+0x05,ATTR_LIN,Palette of rows or columns
+0x06,ATTR_DIV,Palettes on both sides of and on a row or column
+0x07,ATTR_CHR,2 bits per cell from a starting cell
+0x11,MLT_REQ,1, 2 or 4 joypads
+0x13,CHR_TRN,Border tiles from the next frame
+0x14,PCT_TRN,Border map and palettes from the next frame
+0x17,MASK_EN,Freeze, blank or fill the game screen
+
+With several joypads, JOYP reads 0xF minus the joypad number in bits 0-3
+while P14 and P15 are high, the next joypad is selected when P15 goes high.
+*/
+
+mod border;
+mod commands;
+
+use log::{debug, trace};
+
+use crate::state::{SaveState, StateError, StateReader, StateWriter};
+
+pub const SGB_WIDTH: usize = 256;
+pub const SGB_HEIGHT: usize = 224;
+/// Top left corner of the game screen in the bordered frame.
+pub const SGB_SCREEN_X: usize = 48;
+pub const SGB_SCREEN_Y: usize = 40;
+
+const PACKET_LEN: usize = 16;
+const MAX_PACKETS: usize = 7;
+/// Palette 0 as left by the SGB boot ROM.
+const DEFAULT_PALETTE: [u16; 4] = [0x67BF, 0x265B, 0x10B5, 0x2866];
+
+/// What MASK_EN does to the game screen.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenMask {
+    #[default]
+    Off,
+    /// Keeps showing the last frame.
+    Freeze,
+    Black,
+    /// Filled with color 0.
+    Color0,
+}
+
+/// Data a *_TRN command takes from the next frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transfer {
+    /// Border tiles 0x00-0x7F, or 0x80-0xFF if set.
+    Chr(bool),
+    Pct,
+}
+
+pub struct Sgb {
+    /// Packets received so far of the current command.
+    packets: Vec<u8>,
+    /// Bits received of the current packet, `None` until a reset pulse.
+    bit: Option<usize>,
+    packet: [u8; PACKET_LEN],
+    /// Last P14/P15 written, a pulse only counts after 0x30.
+    last_sel: u8,
+
+    /// Game screen palettes 0-3, color 0 is shared.
+    pub palettes: [[u16; 4]; 4],
+    /// Palette of each 8x8 cell of the game screen.
+    pub attrs: [u8; 20 * 18],
+    pub mask: ScreenMask,
+    /// 1, 2 or 4 joypads.
+    pub players: u8,
+    pub player: u8,
+    transfer: Option<Transfer>,
+
+    /// 256 border tiles, 4 bits per pixel in SNES format.
+    border_tiles: Box<[u8; 0x2000]>,
+    /// 32x32 map entries followed by palettes 4-7, as sent by PCT_TRN.
+    border_map: Box<[u8; 0x880]>,
+    /// The game screen shown, only updated while not frozen.
+    screen: Box<[u8; 160 * 144]>,
+    frame: Box<[u16; SGB_WIDTH * SGB_HEIGHT]>,
+}
+
+impl Default for Sgb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sgb {
+    pub fn new() -> Self {
+        Self {
+            packets: Vec::with_capacity(PACKET_LEN * MAX_PACKETS),
+            bit: None,
+            packet: [0; PACKET_LEN],
+            last_sel: 0x30,
+            palettes: [DEFAULT_PALETTE; 4],
+            attrs: [0; 20 * 18],
+            mask: ScreenMask::Off,
+            players: 1,
+            player: 0,
+            transfer: None,
+            border_tiles: Box::new([0; 0x2000]),
+            border_map: Box::new([0; 0x880]),
+            screen: Box::new([0; 160 * 144]),
+            frame: Box::new([0; SGB_WIDTH * SGB_HEIGHT]),
+        }
+    }
+
+    /// The 256x224 RGB555 frame, game screen in the middle of the border.
+    pub fn get_frame(&self) -> &[u16; SGB_WIDTH * SGB_HEIGHT] {
+        &self.frame
+    }
+
+    /// Sees every write to JOYP, `sel` holds P14/P15 in bits 4-5.
+    pub fn write_joypad(&mut self, sel: u8) {
+        let sel = sel & 0x30;
+        let last = std::mem::replace(&mut self.last_sel, sel);
+        match sel {
+            0x00 => {
+                self.bit = Some(0);
+                self.packet = [0; PACKET_LEN];
+            }
+            0x10 | 0x20 if last == 0x30 => self.receive_bit(sel == 0x10),
+            0x30 if (last & 0x20) == 0 && self.players > 1 => {
+                self.player = (self.player + 1) % self.players;
+            }
+            _ => {}
+        }
+    }
+
+    /// Joypad number in the low nibble while both lines are high.
+    pub fn read_joypad_id(&self) -> u8 {
+        0x0F - self.player
+    }
+
+    fn receive_bit(&mut self, set: bool) {
+        let Some(bit) = self.bit else {
+            return;
+        };
+        if set {
+            self.packet[bit / 8] |= 1 << (bit % 8);
+        }
+        if bit + 1 < PACKET_LEN * 8 {
+            self.bit = Some(bit + 1);
+            return;
+        }
+        // The stop bit finds `bit` cleared and is dropped.
+        self.bit = None;
+        trace!("SGB packet {:02X?}", self.packet);
+        self.packets.extend_from_slice(&self.packet);
+        let expected = (self.packets[0] & 0x07) as usize;
+        if expected == 0 {
+            debug!("SGB packet without length dropped");
+            self.packets.clear();
+        } else if self.packets.len() >= expected * PACKET_LEN {
+            let packets = std::mem::take(&mut self.packets);
+            self.command(&packets);
+        }
+    }
+
+    /// Called once a frame is complete, with its 2-bit shades.
+    pub fn on_vblank(&mut self, frame_buffer: &[u8; 160 * 144]) {
+        if let Some(transfer) = self.transfer.take() {
+            self.vram_transfer(transfer, frame_buffer);
+        }
+        if self.mask != ScreenMask::Freeze {
+            self.screen.copy_from_slice(frame_buffer);
+        }
+        self.render();
+    }
+}
+
+impl SaveState for Sgb {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.packets);
+        w.u8(self.bit.map_or(0xFF, |bit| bit as u8));
+        w.bytes(&self.packet);
+        w.u8(self.last_sel);
+        for color in self.palettes.as_flattened() {
+            w.u16(*color);
+        }
+        w.bytes(&self.attrs);
+        w.u8(self.mask as u8);
+        w.u8(self.players);
+        w.u8(self.player);
+        w.u8(match self.transfer {
+            None => 0,
+            Some(Transfer::Chr(false)) => 1,
+            Some(Transfer::Chr(true)) => 2,
+            Some(Transfer::Pct) => 3,
+        });
+        w.bytes(self.border_tiles.as_slice());
+        w.bytes(self.border_map.as_slice());
+        w.bytes(self.screen.as_slice());
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        let packets = r.bytes()?;
+        if packets.len() > PACKET_LEN * MAX_PACKETS {
+            return Err(StateError::Corrupt("SGB packets"));
+        }
+        self.packets = packets.to_vec();
+        self.bit = match r.u8()? {
+            0xFF => None,
+            bit if (bit as usize) < PACKET_LEN * 8 => Some(bit as usize),
+            _ => return Err(StateError::Corrupt("SGB packet bit")),
+        };
+        r.bytes_into(&mut self.packet)?;
+        self.last_sel = r.u8()? & 0x30;
+        for color in self.palettes.as_flattened_mut() {
+            *color = r.u16()? & 0x7FFF;
+        }
+        r.bytes_into(&mut self.attrs)?;
+        for attr in self.attrs.iter_mut() {
+            *attr &= 0x03;
+        }
+        self.mask = match r.u8()? {
+            0 => ScreenMask::Off,
+            1 => ScreenMask::Freeze,
+            2 => ScreenMask::Black,
+            3 => ScreenMask::Color0,
+            _ => return Err(StateError::Corrupt("SGB mask")),
+        };
+        self.players = match r.u8()? {
+            players @ (1 | 2 | 4) => players,
+            _ => return Err(StateError::Corrupt("SGB players")),
+        };
+        self.player = r.u8()? % self.players;
+        self.transfer = match r.u8()? {
+            0 => None,
+            1 => Some(Transfer::Chr(false)),
+            2 => Some(Transfer::Chr(true)),
+            3 => Some(Transfer::Pct),
+            _ => return Err(StateError::Corrupt("SGB transfer")),
+        };
+        r.bytes_into(self.border_tiles.as_mut_slice())?;
+        r.bytes_into(self.border_map.as_mut_slice())?;
+        r.bytes_into(self.screen.as_mut_slice())?;
+        self.render();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sends packets the way games do, pulses separated by 0x30.
+    pub(super) fn send(sgb: &mut Sgb, data: &[u8]) {
+        for packet in data.chunks(PACKET_LEN) {
+            sgb.write_joypad(0x00);
+            sgb.write_joypad(0x30);
+            for i in 0..PACKET_LEN * 8 {
+                let byte = packet.get(i / 8).copied().unwrap_or(0);
+                let bit = (byte >> (i % 8)) & 1;
+                sgb.write_joypad(if bit == 1 { 0x10 } else { 0x20 });
+                sgb.write_joypad(0x30);
+            }
+            // Stop bit
+            sgb.write_joypad(0x20);
+            sgb.write_joypad(0x30);
+        }
+    }
+
+    #[test]
+    fn test_packet_needs_reset_pulse() {
+        let mut sgb = Sgb::new();
+        sgb.write_joypad(0x10);
+        sgb.write_joypad(0x30);
+        assert_eq!(sgb.bit, None);
+
+        send(&mut sgb, &[0x11 << 3 | 1, 0x01]);
+        assert_eq!(sgb.players, 2, "MLT_REQ");
+        assert!(sgb.packets.is_empty());
+    }
+
+    #[test]
+    fn test_multi_packet_command_waits_for_all() {
+        let mut sgb = Sgb::new();
+        let mut data = [0u8; 32];
+        data[0] = 0x05 << 3 | 2; // ATTR_LIN over 2 packets
+        data[1] = 16;
+        data[2..18].copy_from_slice(&[0x80 | 0x20; 16]); // Rows 0, palette 1
+        data[17] = 0x80 | 0x40 | 17; // Last row, palette 2
+        send(&mut sgb, &data[..16]);
+        assert_eq!(sgb.attrs[0], 0, "Waits for the second packet");
+        send(&mut sgb, &data[16..]);
+        assert_eq!(sgb.attrs[0], 1);
+        assert_eq!(sgb.attrs[17 * 20 + 5], 2);
+    }
+
+    #[test]
+    fn test_mlt_req_cycles_joypads() {
+        let mut sgb = Sgb::new();
+        send(&mut sgb, &[0x11 << 3 | 1, 0x03]);
+        let start = sgb.read_joypad_id();
+        let mut ids = Vec::new();
+        for _ in 0..4 {
+            sgb.write_joypad(0x10);
+            sgb.write_joypad(0x30);
+            ids.push(sgb.read_joypad_id());
+        }
+        assert_eq!(ids.last(), Some(&start), "Back to the same joypad");
+        ids.sort();
+        assert_eq!(ids, [0x0C, 0x0D, 0x0E, 0x0F]);
+    }
+}
@@ -0,0 +1,311 @@
+/*
+Save state format, all integers little endian.
+
+Offset,Size,Content
+0x00,4,Magic "GBRS"
+0x04,u16,Format version
+0x06,u16,Global checksum of the ROM the state was taken from (0x014E–0x014F)
+0x08,...,Sections: 4 byte tag, u32 payload length, payload
+
+Unknown sections are skipped and trailing bytes in a known section are
+ignored, so a state written by a newer build still loads in an older one.
+The version is only bumped for changes older builds can't read that way,
+they reject any version newer than their own.
+*/
+
+mod rewind;
+mod state_trait;
+
+pub use rewind::RewindBuffer;
+pub use state_trait::SaveState;
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::cpu::Cpu;
+use crate::input::InputDevice;
+use crate::mmu::Bus;
+
+const MAGIC: &[u8; 4] = b"GBRS";
+pub const STATE_VERSION: u16 = 1;
+
+const TAG_CPU: &[u8; 4] = b"CPU ";
+const TAG_BUS: &[u8; 4] = b"BUS ";
+const TAG_PPU: &[u8; 4] = b"PPU ";
+const TAG_APU: &[u8; 4] = b"APU ";
+const TAG_TIMER: &[u8; 4] = b"TIMR";
+const TAG_MBC: &[u8; 4] = b"MBC ";
+/// Only written for the SGB model.
+const TAG_SGB: &[u8; 4] = b"SGB ";
+
+#[derive(Debug, PartialEq)]
+pub enum StateError {
+    BadMagic,
+    /// Written by a newer build in a format this one can't read.
+    UnsupportedVersion(u16),
+    /// The state was taken from another ROM.
+    RomMismatch {
+        expected: u16,
+        found: u16,
+    },
+    MissingSection(&'static str),
+    Truncated,
+    Corrupt(&'static str),
+}
+
+impl std::error::Error for StateError {}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "Not a save state"),
+            StateError::UnsupportedVersion(version) => write!(
+                f,
+                "Save state version {} is newer than the supported {}",
+                version, STATE_VERSION
+            ),
+            StateError::RomMismatch { expected, found } => write!(
+                f,
+                "Save state is for another ROM: expected checksum {:04X}, found {:04X}",
+                expected, found
+            ),
+            StateError::MissingSection(tag) => write!(f, "Save state has no '{}' section", tag),
+            StateError::Truncated => write!(f, "Save state ended unexpectedly"),
+            StateError::Corrupt(what) => write!(f, "Save state is corrupt: {}", what),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct StateWriter {
+    buf: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+
+    pub fn u8(&mut self, val: u8) {
+        self.buf.push(val);
+    }
+
+    pub fn bool(&mut self, val: bool) {
+        self.u8(val as u8);
+    }
+
+    pub fn u16(&mut self, val: u16) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, val: u32) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, val: u64) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+
+    /// Length prefixed, so a size mismatch is caught on load.
+    pub fn bytes(&mut self, val: &[u8]) {
+        self.u32(val.len() as u32);
+        self.buf.extend_from_slice(val);
+    }
+
+    fn section(&mut self, tag: &[u8; 4], component: &dyn SaveState) {
+        let mut payload = StateWriter::new();
+        component.save_state(&mut payload);
+        self.buf.extend_from_slice(tag);
+        self.bytes(&payload.buf);
+    }
+}
+
+pub struct StateReader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        if self.buf.len() < len {
+            return Err(StateError::Truncated);
+        }
+        let (head, tail) = self.buf.split_at(len);
+        self.buf = tail;
+        Ok(head)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, StateError> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, StateError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn bytes(&mut self) -> Result<&'a [u8], StateError> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    /// Reads a length prefixed blob that must exactly fill `dst`.
+    pub fn bytes_into(&mut self, dst: &mut [u8]) -> Result<(), StateError> {
+        let src = self.bytes()?;
+        if src.len() != dst.len() {
+            return Err(StateError::Corrupt("buffer size mismatch"));
+        }
+        dst.copy_from_slice(src);
+        Ok(())
+    }
+}
+
+/// Snapshots the whole machine: CPU, bus memory, PPU, APU, timer and mapper,
+/// plus the SNES side on the SGB model.
+pub fn save_state<I: InputDevice + Default>(cpu: &Cpu, bus: &Bus<I>) -> Vec<u8> {
+    let mut w = StateWriter::new();
+    w.buf.extend_from_slice(MAGIC);
+    w.u16(STATE_VERSION);
+    w.u16(bus.cartridge.headers.checksum_global);
+
+    w.section(TAG_CPU, cpu);
+    w.section(TAG_BUS, bus);
+    w.section(TAG_PPU, bus.ppu.as_ref());
+    w.section(TAG_APU, &bus.apu);
+    w.section(TAG_TIMER, &bus.timer);
+    w.section(TAG_MBC, bus.cartridge.mbc.as_ref());
+    if let Some(sgb) = &bus.sgb {
+        w.section(TAG_SGB, sgb);
+    }
+    w.into_inner()
+}
+
+/// Restores a state produced by `save_state`. The header and section table
+/// are validated before anything is touched, and if a section then fails to
+/// load the machine is put back as it was, so an error never leaves it half
+/// restored.
+pub fn load_state<I: InputDevice + Default>(
+    cpu: &mut Cpu,
+    bus: &mut Bus<I>,
+    data: &[u8],
+) -> Result<(), StateError> {
+    let sections = parse_sections(data, bus.cartridge.headers.checksum_global)?;
+    let backup = save_state(cpu, bus);
+    restore_sections(cpu, bus, &sections).inspect_err(|_| {
+        let backup = parse_sections(&backup, bus.cartridge.headers.checksum_global)
+            .expect("a state we just saved parses");
+        restore_sections(cpu, bus, &backup).expect("a state we just saved loads");
+    })
+}
+
+/// Checks the header and splits the rest into sections by tag.
+fn parse_sections(data: &[u8], expected: u16) -> Result<HashMap<[u8; 4], &[u8]>, StateError> {
+    let mut r = StateReader::new(data);
+    if r.take(MAGIC.len()).map_err(|_| StateError::BadMagic)? != MAGIC {
+        return Err(StateError::BadMagic);
+    }
+    let version = r.u16()?;
+    if version > STATE_VERSION {
+        return Err(StateError::UnsupportedVersion(version));
+    }
+    let checksum = r.u16()?;
+    if checksum != expected {
+        return Err(StateError::RomMismatch {
+            expected,
+            found: checksum,
+        });
+    }
+
+    let mut sections = HashMap::new();
+    while !r.buf.is_empty() {
+        let tag: [u8; 4] = r.take(4)?.try_into().unwrap();
+        sections.insert(tag, r.bytes()?);
+    }
+    for tag in [TAG_CPU, TAG_BUS, TAG_PPU, TAG_APU, TAG_TIMER, TAG_MBC] {
+        if !sections.contains_key(tag) {
+            return Err(StateError::MissingSection(
+                std::str::from_utf8(tag).unwrap(),
+            ));
+        }
+    }
+    Ok(sections)
+}
+
+fn restore_sections<I: InputDevice + Default>(
+    cpu: &mut Cpu,
+    bus: &mut Bus<I>,
+    sections: &HashMap<[u8; 4], &[u8]>,
+) -> Result<(), StateError> {
+    let section = |tag: &[u8; 4]| StateReader::new(sections[tag]);
+    cpu.load_state(&mut section(TAG_CPU))?;
+    bus.load_state(&mut section(TAG_BUS))?;
+    bus.ppu.load_state(&mut section(TAG_PPU))?;
+    bus.apu.load_state(&mut section(TAG_APU))?;
+    bus.timer.load_state(&mut section(TAG_TIMER))?;
+    bus.cartridge.mbc.load_state(&mut section(TAG_MBC))?;
+    if let Some(sgb) = &mut bus.sgb
+        && let Some(payload) = sections.get(TAG_SGB)
+    {
+        sgb.load_state(&mut StateReader::new(payload))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reader_writer_round_trip() {
+        let mut w = StateWriter::new();
+        w.u8(0x12);
+        w.bool(true);
+        w.u16(0x3456);
+        w.u32(0x789A_BCDE);
+        w.u64(u64::MAX - 1);
+        w.bytes(&[1, 2, 3]);
+        let buf = w.into_inner();
+
+        let mut r = StateReader::new(&buf);
+        assert_eq!(r.u8(), Ok(0x12));
+        assert_eq!(r.bool(), Ok(true));
+        assert_eq!(r.u16(), Ok(0x3456));
+        assert_eq!(r.u32(), Ok(0x789A_BCDE));
+        assert_eq!(r.u64(), Ok(u64::MAX - 1));
+        let mut dst = [0; 3];
+        assert_eq!(r.bytes_into(&mut dst), Ok(()));
+        assert_eq!(dst, [1, 2, 3]);
+        assert_eq!(r.u8(), Err(StateError::Truncated));
+    }
+
+    #[test]
+    fn test_bytes_into_size_mismatch() {
+        let mut w = StateWriter::new();
+        w.bytes(&[1, 2, 3]);
+        let buf = w.into_inner();
+
+        let mut dst = [0; 4];
+        assert!(matches!(
+            StateReader::new(&buf).bytes_into(&mut dst),
+            Err(StateError::Corrupt(_))
+        ));
+    }
+}
@@ -0,0 +1,264 @@
+use std::collections::VecDeque;
+
+use crate::cpu::Cpu;
+use crate::input::InputDevice;
+use crate::mmu::Bus;
+use crate::state::{StateError, load_state, save_state};
+
+const ENCODING_RAW: u8 = 0;
+const ENCODING_XOR_RLE: u8 = 1;
+
+/// Ring buffer of machine states, taken every `interval` frames.
+///
+/// Only the newest state is kept as-is, every older one is stored as the
+/// XOR against its newer neighbour with the zero runs packed. Consecutive
+/// frames differ in few bytes, so this is small, and since nothing depends
+/// on the oldest entry it can be evicted for free once the cap is hit.
+pub struct RewindBuffer {
+    interval: u32,
+    max_bytes: usize,
+    frames: u32,
+    newest: Option<Vec<u8>>,
+    /// Oldest first, `older[i]` decodes against `older[i + 1]` (or `newest`).
+    older: VecDeque<Vec<u8>>,
+    used_bytes: usize,
+}
+
+impl RewindBuffer {
+    pub fn new(interval: u32, max_bytes: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+            max_bytes,
+            frames: 0,
+            newest: None,
+            older: VecDeque::new(),
+            used_bytes: 0,
+        }
+    }
+
+    /// Number of states that can be rewound to.
+    pub fn len(&self) -> usize {
+        self.older.len() + self.newest.is_some() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.used_bytes
+    }
+
+    /// Called once per frame from the main loop, captures every `interval` frames.
+    pub fn on_frame<I: InputDevice + Default>(&mut self, cpu: &Cpu, bus: &Bus<I>) {
+        self.frames += 1;
+        if self.frames >= self.interval {
+            self.frames = 0;
+            self.push(save_state(cpu, bus));
+        }
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        if let Some(previous) = self.newest.take() {
+            self.used_bytes -= previous.len();
+            let delta = encode(&previous, &state);
+            self.used_bytes += delta.len();
+            self.older.push_back(delta);
+        }
+        self.used_bytes += state.len();
+        self.newest = Some(state);
+
+        while self.used_bytes > self.max_bytes {
+            match self.older.pop_front() {
+                Some(oldest) => self.used_bytes -= oldest.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Restores the machine to `frames` frames ago, rounded down to the
+    /// capture interval and limited by what is still buffered. Newer states
+    /// are dropped so emulation resumes from there. Returns how many frames
+    /// were actually rewound, 0 when `frames` doesn't reach back to the
+    /// newest capture, which leaves the machine alone. On error nothing is
+    /// dropped.
+    pub fn rewind<I: InputDevice + Default>(
+        &mut self,
+        frames: u32,
+        cpu: &mut Cpu,
+        bus: &mut Bus<I>,
+    ) -> Result<u32, StateError> {
+        let Some(newest) = &self.newest else {
+            return Ok(0);
+        };
+        if frames < self.frames {
+            return Ok(0);
+        }
+
+        // The newest state is `self.frames` frames back from the live one,
+        // each delta is one interval further.
+        let steps = (((frames - self.frames) / self.interval) as usize).min(self.older.len());
+        let mut decoded: Option<Vec<u8>> = None;
+        for delta in self.older.iter().rev().take(steps) {
+            decoded = Some(decode(delta, decoded.as_deref().unwrap_or(newest))?);
+        }
+        load_state(cpu, bus, decoded.as_deref().unwrap_or(newest))?;
+
+        if let Some(state) = decoded {
+            self.used_bytes -= newest.len();
+            self.used_bytes += state.len();
+            self.newest = Some(state);
+        }
+        for delta in self.older.drain(self.older.len() - steps..) {
+            self.used_bytes -= delta.len();
+        }
+        let rewound = self.frames + steps as u32 * self.interval;
+        self.frames = 0;
+        Ok(rewound)
+    }
+}
+
+/// Encodes `state` relative to `reference`. The XOR is stored as pairs of
+/// (zero run, literal run) lengths followed by the literal bytes.
+fn encode(state: &[u8], reference: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    if state.len() != reference.len() {
+        out.push(ENCODING_RAW);
+        out.extend_from_slice(state);
+        return out;
+    }
+
+    out.push(ENCODING_XOR_RLE);
+    let xor: Vec<u8> = state.iter().zip(reference).map(|(a, b)| a ^ b).collect();
+    let mut i = 0;
+    while i < xor.len() {
+        let zeros = xor[i..].iter().take_while(|&&b| b == 0).count();
+        i += zeros;
+        let literals = xor[i..].iter().take_while(|&&b| b != 0).count();
+        write_varint(&mut out, zeros);
+        write_varint(&mut out, literals);
+        out.extend_from_slice(&xor[i..i + literals]);
+        i += literals;
+    }
+    out
+}
+
+fn decode(delta: &[u8], reference: &[u8]) -> Result<Vec<u8>, StateError> {
+    let (&encoding, mut data) = delta.split_first().ok_or(StateError::Truncated)?;
+    match encoding {
+        ENCODING_RAW => Ok(data.to_vec()),
+        ENCODING_XOR_RLE => {
+            let mut out = reference.to_vec();
+            let mut i = 0;
+            while !data.is_empty() {
+                i += read_varint(&mut data)?;
+                let literals = read_varint(&mut data)?;
+                if literals > data.len() || i + literals > out.len() {
+                    return Err(StateError::Corrupt("rewind delta out of bounds"));
+                }
+                for (dst, src) in out[i..i + literals].iter_mut().zip(&data[..literals]) {
+                    *dst ^= src;
+                }
+                data = &data[literals..];
+                i += literals;
+            }
+            Ok(out)
+        }
+        _ => Err(StateError::Corrupt("unknown rewind encoding")),
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut val: usize) {
+    while val >= 0x80 {
+        out.push((val as u8) | 0x80);
+        val >>= 7;
+    }
+    out.push(val as u8);
+}
+
+fn read_varint(data: &mut &[u8]) -> Result<usize, StateError> {
+    let mut val = 0usize;
+    let mut shift = 0;
+    loop {
+        let (&byte, rest) = data.split_first().ok_or(StateError::Truncated)?;
+        *data = rest;
+        val |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(val);
+        }
+        shift += 7;
+        if shift >= usize::BITS {
+            return Err(StateError::Corrupt("varint too long"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::DummyInput;
+
+    #[test]
+    fn test_delta_round_trip() {
+        let reference: Vec<u8> = (0..=255).cycle().take(4096).collect();
+        let mut state = reference.clone();
+        state[0] ^= 0xFF;
+        state[1000] = 0;
+        state[4095] = 7;
+
+        let delta = encode(&state, &reference);
+        assert!(delta.len() < 32, "Sparse changes should pack down");
+        assert_eq!(decode(&delta, &reference).unwrap(), state);
+    }
+
+    #[test]
+    fn test_delta_size_change_falls_back_to_raw() {
+        let delta = encode(&[1, 2, 3], &[1, 2]);
+        assert_eq!(delta[0], ENCODING_RAW);
+        assert_eq!(decode(&delta, &[1, 2]).unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_memory_cap_evicts_oldest() {
+        let mut buffer = RewindBuffer::new(1, 3000);
+        for i in 0..100usize {
+            // Every byte changes, so each delta is about as big as a state.
+            let state: Vec<u8> = (0..1024).map(|j| (i * 7 + j) as u8).collect();
+            buffer.push(state);
+        }
+        assert!(buffer.memory_usage() <= 3000);
+        assert!(
+            buffer.len() > 1,
+            "Deltas should fit next to the newest state"
+        );
+        assert!(buffer.len() < 100);
+    }
+
+    #[test]
+    fn test_failed_rewind_keeps_history() {
+        let mut cpu = Cpu::new();
+        let mut bus: Bus<DummyInput> = Bus::new(vec![0; 0x8000]);
+        let mut buffer = RewindBuffer::new(1, 1024 * 1024);
+        // Not save states, so decoding works but loading fails.
+        for i in 0..3u8 {
+            buffer.push(vec![i; 64]);
+        }
+        let usage = buffer.memory_usage();
+
+        assert_eq!(
+            buffer.rewind(2, &mut cpu, &mut bus),
+            Err(StateError::BadMagic)
+        );
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.memory_usage(), usage);
+    }
+
+    #[test]
+    fn test_varint_round_trip() {
+        for val in [0, 1, 127, 128, 300, 65536, usize::MAX] {
+            let mut out = Vec::new();
+            write_varint(&mut out, val);
+            assert_eq!(read_varint(&mut out.as_slice()).unwrap(), val);
+        }
+    }
+}
@@ -0,0 +1,9 @@
+use crate::state::{StateError, StateReader, StateWriter};
+
+/// Implemented by every component that is part of a save state.
+/// Fields are written in a fixed order, new fields must only ever be
+/// appended so older builds can still read the start of a section.
+pub trait SaveState {
+    fn save_state(&self, w: &mut StateWriter);
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError>;
+}
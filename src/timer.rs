@@ -1,3 +1,6 @@
+use crate::model::Model;
+use crate::state::{SaveState, StateError, StateReader, StateWriter};
+
 pub struct Timer {
     pub internal_counter: u16, // Increments every T-cycle
     pub tima: u8,              // 0xFF05
@@ -12,6 +15,25 @@ impl Default for Timer {
     }
 }
 
+impl SaveState for Timer {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u16(self.internal_counter);
+        w.u8(self.tima);
+        w.u8(self.tma);
+        w.u8(self.tac);
+        w.u8(self.div);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.internal_counter = r.u16()?;
+        self.tima = r.u8()?;
+        self.tma = r.u8()?;
+        self.tac = r.u8()?;
+        self.div = r.u8()?;
+        Ok(())
+    }
+}
+
 impl Timer {
     pub fn new() -> Self {
         Self {
@@ -23,6 +45,17 @@ impl Timer {
         }
     }
 
+    /// DIV keeps counting while the boot ROM runs, so it is well into a
+    /// cycle when the cartridge starts. Models without a measured value
+    /// start at 0, the frontend runs their boot ROM instead.
+    pub fn init_post_boot(&mut self, model: Model) {
+        self.internal_counter = model.post_boot_div().unwrap_or(0);
+        self.div = (self.internal_counter >> 8) as u8;
+        self.tima = 0;
+        self.tma = 0;
+        self.tac = 0;
+    }
+
     pub fn read_byte(&self, addr: u16) -> u8 {
         match addr {
             0xFF04 => self.div,
@@ -247,4 +280,14 @@ mod test {
             "TIMA should have reloaded from TMA (0xAA)"
         );
     }
+
+    #[test]
+    fn test_post_boot_div() {
+        let mut timer = Timer::new();
+        timer.init_post_boot(Model::Dmg);
+        assert_eq!(timer.read_byte(0xFF04), 0xAB);
+
+        timer.init_post_boot(Model::Dmg0);
+        assert_eq!(timer.read_byte(0xFF04), 0x18);
+    }
 }
@@ -1,4 +1,4 @@
-use gameboy_rs::apu::Apu;
+use gameboy_rs::apu::{APU_CLOCK, Apu, AudioRecorder};
 
 // #[test]
 // fn test_apu_power_state() {
@@ -71,3 +71,203 @@ use gameboy_rs::apu::Apu;
 //         "Channel 1 failed to turn off automatically via tick"
 //     );
 // }
+
+fn powered_apu() -> Apu {
+    let mut apu = Apu::new();
+    apu.write_byte(0xFF26, 0x80); // NR52
+    apu
+}
+
+/// Counts the T-cycles the output is high over `cycles`.
+fn high_cycles(apu: &mut Apu, output: impl Fn(&Apu) -> u8, cycles: u32) -> u32 {
+    let mut high = 0;
+    for _ in 0..cycles {
+        apu.tick(1);
+        if output(apu) > 0 {
+            high += 1;
+        }
+    }
+    high
+}
+
+#[test]
+fn test_square_duty_cycles() {
+    // Frequency 0x7F8 gives 32 T-cycles per step, 256 per waveform.
+    for (duty, steps) in [(0, 1), (1, 2), (2, 4), (3, 6)] {
+        let mut apu = powered_apu();
+        apu.write_byte(0xFF16, duty << 6); // NR21
+        apu.write_byte(0xFF17, 0xF0); // NR22, volume 15
+        apu.write_byte(0xFF18, 0xF8);
+        apu.write_byte(0xFF19, 0x80 | 0x07); // Trigger
+        let high = high_cycles(&mut apu, |apu| apu.ch2.output(), 256 * 4);
+        assert_eq!(high, steps * 32 * 4, "Duty {duty}");
+    }
+}
+
+#[test]
+fn test_wave_plays_wave_ram_nibbles() {
+    let mut apu = powered_apu();
+    for i in 0..16 {
+        apu.write_byte(0xFF30 + i, 0x0F);
+    }
+    apu.write_byte(0xFF1A, 0x80); // NR30, DAC on
+    apu.write_byte(0xFF1C, 0x20); // NR32, full volume
+    apu.write_byte(0xFF1D, 0x00);
+    apu.write_byte(0xFF1E, 0x80 | 0x07); // Trigger, 512 T-cycles per sample
+    let mut samples = Vec::new();
+    for _ in 0..4 {
+        apu.tick(512);
+        samples.push(apu.ch3.output());
+    }
+    assert_eq!(samples, [0x0F, 0x00, 0x0F, 0x00]);
+
+    apu.write_byte(0xFF1C, 0x60); // 25%
+    assert_eq!(apu.ch3.output(), 0x00);
+    apu.tick(512);
+    assert_eq!(apu.ch3.output(), 0x03);
+}
+
+#[test]
+fn test_noise_7_bit_repeats() {
+    let mut apu = powered_apu();
+    apu.write_byte(0xFF21, 0xF0); // NR42
+    apu.write_byte(0xFF22, 0x08); // NR43, 7-bit, divisor 8, shift 0
+    apu.write_byte(0xFF23, 0x80);
+    let mut pattern = Vec::new();
+    for _ in 0..254 {
+        apu.tick(8);
+        pattern.push(apu.ch4.output());
+    }
+    assert_eq!(pattern[..127], pattern[127..], "127 step sequence");
+    assert!(pattern.contains(&0) && pattern.contains(&15));
+}
+
+#[test]
+fn test_dac_off_disables_channel() {
+    let mut apu = powered_apu();
+    apu.write_byte(0xFF12, 0x00); // NR12, DAC off
+    apu.write_byte(0xFF14, 0x80);
+    assert_eq!(apu.read_byte(0xFF26) & 0x01, 0, "Trigger can't enable it");
+    assert_eq!(apu.channel_outputs()[0], 0.0);
+
+    apu.write_byte(0xFF12, 0xF0);
+    apu.write_byte(0xFF14, 0x80);
+    assert_eq!(apu.read_byte(0xFF26) & 0x01, 0x01);
+    apu.write_byte(0xFF12, 0x00);
+    assert_eq!(
+        apu.read_byte(0xFF26) & 0x01,
+        0,
+        "Turning the DAC off stops it"
+    );
+}
+
+#[test]
+fn test_mixer_panning_and_volume() {
+    let mut apu = powered_apu();
+    apu.write_byte(0xFF17, 0x08); // NR22, DAC on at volume 0: analog 1.0
+    apu.write_byte(0xFF19, 0x80);
+    apu.write_byte(0xFF25, 0x20); // NR51, channel 2 left only
+    apu.write_byte(0xFF24, 0x70); // NR50, left full, right 1/8
+    let (left, right) = apu.mix();
+    assert_eq!(left, 0.25);
+    assert_eq!(right, 0.0);
+
+    apu.write_byte(0xFF25, 0x22);
+    let (_, right) = apu.mix();
+    assert_eq!(right, 0.25 / 8.0);
+}
+
+/// Runs `seconds` of APU time in M-cycles.
+fn run_seconds(apu: &mut Apu, seconds: f64) {
+    for _ in 0..(APU_CLOCK as f64 * seconds) as u32 / 4 {
+        apu.tick(4);
+    }
+}
+
+/// Square wave on channel 2, both sides at full volume.
+fn play_square(apu: &mut Apu, frequency: u16) {
+    apu.write_byte(0xFF24, 0x77); // NR50
+    apu.write_byte(0xFF25, 0x22); // NR51
+    apu.write_byte(0xFF16, 0x80); // NR21, 50%
+    apu.write_byte(0xFF17, 0xF0);
+    apu.write_byte(0xFF18, frequency as u8);
+    apu.write_byte(0xFF19, 0x80 | (frequency >> 8) as u8);
+}
+
+#[test]
+fn test_drain_samples_at_host_rate() {
+    for rate in [32_000, 44_100, 48_000] {
+        let mut apu = powered_apu();
+        apu.set_sample_rate(rate);
+        run_seconds(&mut apu, 0.25);
+        let mut out: Vec<i16> = Vec::new();
+        let frames = apu.drain_samples(&mut out);
+        assert!(
+            frames.abs_diff(rate as usize / 4) <= 1,
+            "{rate} Hz: {frames}"
+        );
+        assert_eq!(out.len(), frames * 2, "Stereo");
+        assert_eq!(apu.drain_samples(&mut out), 0, "Nothing new");
+    }
+}
+
+#[test]
+fn test_square_wave_survives_resampling() {
+    let mut apu = powered_apu();
+    play_square(&mut apu, 2048 - 512); // 2048 T-cycles per step, 256 Hz
+    run_seconds(&mut apu, 0.1);
+    let mut out: Vec<f32> = Vec::new();
+    apu.drain_samples(&mut out);
+    let left: Vec<f32> = out.iter().step_by(2).copied().collect();
+    let peak = left.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+    assert!(peak > 0.1, "Audible, peak {peak}");
+    let crossings = left[1000..]
+        .windows(2)
+        .filter(|w| (w[0] < 0.0) != (w[1] < 0.0))
+        .count();
+    // 256 Hz over the last 3800 samples is ~20 periods, 2 crossings each.
+    assert!((36..=44).contains(&crossings), "{crossings} crossings");
+}
+
+#[test]
+fn test_ultrasonic_tone_does_not_alias() {
+    let mut apu = powered_apu();
+    play_square(&mut apu, 2047); // 131 kHz
+    run_seconds(&mut apu, 0.1);
+    let mut out: Vec<f32> = Vec::new();
+    apu.drain_samples(&mut out);
+    let tail = &out[out.len() / 2..];
+    let rms = (tail.iter().map(|s| s * s).sum::<f32>() / tail.len() as f32).sqrt();
+    assert!(rms < 0.01, "RMS {rms}");
+}
+
+#[test]
+fn test_recording_writes_mix_and_stems() {
+    let path = std::env::temp_dir().join(format!("gameboy_rs_{}.wav", std::process::id()));
+    let mut apu = powered_apu();
+    play_square(&mut apu, 2048 - 512);
+    let mut recorder = AudioRecorder::create(&path, true, &mut apu).unwrap();
+    for _ in 0..2 {
+        run_seconds(&mut apu, 0.05);
+        recorder.record(&mut apu).unwrap();
+    }
+    recorder.finish().unwrap();
+
+    let data_len = |path: &std::path::Path| {
+        let wav = std::fs::read(path).unwrap();
+        let len = u32::from_le_bytes(wav[40..44].try_into().unwrap()) as usize;
+        assert_eq!(wav.len(), 44 + len, "{:?}", path);
+        (len, wav)
+    };
+    let (mix_len, _) = data_len(&path);
+    assert!(mix_len / 4 >= 4700, "0.1 s at 48 kHz, stereo");
+    for ch in 1..=4 {
+        let stem = AudioRecorder::stem_path(&path, ch);
+        let (len, wav) = data_len(&stem);
+        assert_eq!(len, mix_len / 2, "Mono, as many frames");
+        let silent = wav[44..].iter().all(|b| *b == 0);
+        assert_eq!(silent, ch != 2, "Only channel 2 plays");
+        std::fs::remove_file(stem).unwrap();
+    }
+    std::fs::remove_file(path).unwrap();
+}
@@ -1,5 +1,6 @@
 use gameboy_rs::input::DummyInput;
 use gameboy_rs::mmu::{Bus, Memory};
+use gameboy_rs::model::Model;
 
 fn bus() -> Bus<DummyInput> {
     let bus: Bus<DummyInput> = Bus::new(Vec::new()); // Your memory/system component
@@ -191,7 +192,8 @@ fn diagnostic_timer_signal() {
 fn test_oam_access_during_mode2() {
     let mut bus = bus();
 
-    // 1. Advance PPU to Mode 2 (LY 0, Dot 0-79)
+    // 1. Advance PPU to Mode 2 (LY 0, Dot 0-79), only reachable with the LCD on
+    bus.ppu.enable_ldc();
     bus.ppu.ly = 0;
     bus.ppu.dot_counter = 10;
     assert_eq!(bus.ppu.get_mode(), 2);
@@ -211,3 +213,462 @@ fn test_oam_access_during_mode2() {
         "OAM should be inaccessible/locked during Mode 2"
     );
 }
+
+#[test]
+fn test_mbc1_bank_switch_through_bus() {
+    // 128 KiB MBC1 cart, the first byte of each bank holds its bank number.
+    let mut rom = vec![0; 8 * 0x4000];
+    for bank in 1..8 {
+        rom[bank * 0x4000] = bank as u8;
+    }
+    rom[0x0147] = 0x01; // MBC1
+    let mut bus: Bus<DummyInput> = Bus::new(rom);
+
+    assert_eq!(bus.read_byte(0x4000), 1, "Bank 1 should be mapped on boot");
+
+    bus.write_byte(0x2000, 0x05);
+    assert_eq!(bus.read_byte(0x4000), 5, "ROM writes should reach the MBC");
+
+    bus.write_byte(0x2000, 0x00);
+    assert_eq!(bus.read_byte(0x4000), 1, "Bank 0 should map to bank 1");
+}
+
+#[test]
+fn test_mbc1_external_ram_through_bus() {
+    let mut rom = vec![0; 2 * 0x4000];
+    rom[0x0147] = 0x03; // MBC1+RAM+BATTERY
+    rom[0x0149] = 0x02; // 8 KiB
+    let mut bus: Bus<DummyInput> = Bus::new(rom);
+
+    bus.write_byte(0xA000, 0x55);
+    assert_eq!(bus.read_byte(0xA000), 0xFF, "RAM should start disabled");
+
+    bus.write_byte(0x0000, 0x0A);
+    bus.write_byte(0xA000, 0x55);
+    assert_eq!(bus.read_byte(0xA000), 0x55);
+}
+
+#[test]
+fn test_mbc5_rumble_through_bus() {
+    let mut rom = vec![0; 2 * 0x4000];
+    rom[0x0147] = 0x1C; // MBC5+RUMBLE
+    let mut bus: Bus<DummyInput> = Bus::new(rom);
+
+    assert!(!bus.rumble_active());
+    bus.write_byte(0x4000, 0x08);
+    assert!(
+        bus.rumble_active(),
+        "Bit 3 of the RAM bank register drives the motor"
+    );
+    bus.write_byte(0x4000, 0x00);
+    assert!(!bus.rumble_active());
+}
+
+#[test]
+fn test_boot_rom_overlay_until_ff50() {
+    let mut rom = vec![0; 0x8000];
+    rom[0x0000] = 0xAA;
+    rom[0x0100] = 0xBB;
+    let mut boot_rom = vec![0x00; 0x100];
+    boot_rom[0x0000] = 0x31;
+    let mut bus: Bus<DummyInput> = Bus::new(rom).with_boot_rom(boot_rom);
+
+    assert_eq!(
+        bus.read_byte(0x0000),
+        0x31,
+        "Boot ROM should shadow the cart"
+    );
+    assert_eq!(
+        bus.read_byte(0x0100),
+        0xBB,
+        "Header area is always the cart"
+    );
+
+    bus.write_byte(0xFF50, 0x01);
+    assert_eq!(bus.read_byte(0x0000), 0xAA);
+    assert!(!bus.boot_rom_mapped);
+}
+
+#[test]
+fn test_cgb_boot_rom_leaves_header_visible() {
+    let mut rom = vec![0; 0x8000];
+    rom[0x0150] = 0xBB;
+    let boot_rom = vec![0x11; 0x900];
+    let bus: Bus<DummyInput> = Bus::new(rom).with_boot_rom(boot_rom);
+
+    assert_eq!(bus.read_byte(0x00FF), 0x11);
+    assert_eq!(bus.read_byte(0x0150), 0xBB);
+    assert_eq!(bus.read_byte(0x0200), 0x11);
+    assert_eq!(bus.read_byte(0x08FF), 0x11);
+    assert_eq!(bus.read_byte(0x0900), 0x00);
+}
+
+#[test]
+fn test_vram_and_oam_blocked_by_ppu_mode() {
+    let mut bus = bus();
+    bus.write_byte(0x8000, 0x11);
+    bus.write_byte(0xFE00, 0x22);
+
+    bus.ppu.enable_ldc();
+    bus.tick_components(80); // Mode 3
+    assert_eq!(bus.ppu.get_mode(), 3);
+    assert_eq!(bus.read_byte(0x8000), 0xFF, "VRAM is locked in mode 3");
+    assert_eq!(bus.read_byte(0xFE00), 0xFF, "OAM is locked in mode 3");
+    bus.write_byte(0x8000, 0x33);
+    bus.write_byte(0xFE00, 0x44);
+
+    bus.tick_components(200); // Mode 0
+    assert_eq!(bus.ppu.get_mode(), 0);
+    assert_eq!(bus.read_byte(0x8000), 0x11, "Mode 3 VRAM write was dropped");
+    assert_eq!(bus.read_byte(0xFE00), 0x22, "Mode 3 OAM write was dropped");
+
+    bus.tick_components(200); // Mode 2 of the next line
+    assert_eq!(bus.ppu.get_mode(), 2);
+    bus.write_byte(0x8000, 0x55);
+    assert_eq!(bus.read_byte(0x8000), 0x55, "VRAM is open in mode 2");
+    assert_eq!(bus.read_byte(0xFE00), 0xFF);
+}
+
+#[test]
+fn test_debug_access_bypasses_mode_locks() {
+    let mut bus = bus();
+    bus.write_byte(0xFE00, 0x22);
+    bus.ppu.enable_ldc();
+    bus.tick_components(80);
+
+    assert_eq!(bus.read_byte_raw(0xFE00), 0x22, "Raw reads peek");
+    bus.force_write_byte(0x8000, 0x66);
+
+    bus.ppu.debug_access = true;
+    assert_eq!(bus.read_byte(0x8000), 0x66);
+    bus.write_byte(0xFE00, 0x77);
+    assert_eq!(bus.read_byte(0xFE00), 0x77);
+}
+
+fn tick_m_cycles(bus: &mut Bus<DummyInput>, m_cycles: usize) {
+    for _ in 0..m_cycles {
+        bus.tick_components(4);
+    }
+}
+
+// Fills 0xC000.. with `base + i` and starts an OAM DMA from there.
+fn start_dma(bus: &mut Bus<DummyInput>, base: u8, high: u8) {
+    for i in 0..160u16 {
+        bus.write_byte(((high as u16) << 8) + i, base.wrapping_add(i as u8));
+    }
+    bus.write_byte(0xFF46, high);
+    bus.tick_components(12); // The LDH (n), A that wrote it
+}
+
+#[test]
+fn test_oam_dma_runs_over_160_m_cycles() {
+    let mut bus = bus();
+    start_dma(&mut bus, 0x10, 0xC0);
+    assert!(!bus.dma.active(), "One M-cycle of setup first");
+    assert_eq!(bus.read_byte(0xFF46), 0xC0);
+
+    bus.tick_components(4);
+    assert!(bus.dma.active());
+    tick_m_cycles(&mut bus, 80);
+    assert_eq!(bus.ppu.oam[79], 0x10 + 79);
+    assert_eq!(bus.ppu.oam[80], 0, "Second half not copied yet");
+
+    tick_m_cycles(&mut bus, 79);
+    assert!(bus.dma.active());
+    bus.tick_components(4);
+    assert!(!bus.dma.active());
+    assert!((0..160).all(|i| bus.ppu.oam[i] == 0x10u8.wrapping_add(i as u8)));
+}
+
+#[test]
+fn test_oam_dma_blocks_all_but_hram() {
+    let mut bus = bus();
+    bus.write_byte(0xFF80, 0x42);
+    start_dma(&mut bus, 0x00, 0xC0);
+    bus.tick_components(8);
+
+    assert_eq!(bus.read_byte(0xC005), 0xFF, "WRAM is cut off");
+    assert_eq!(bus.read_byte(0x0000), 0xFF, "ROM is cut off");
+    assert_eq!(bus.read_byte(0xFE00), 0xFF, "OAM is cut off");
+    bus.write_byte(0xC005, 0x99);
+    assert_eq!(bus.read_byte(0xFF80), 0x42, "HRAM stays reachable");
+    bus.write_byte(0xFF81, 0x43);
+    assert_eq!(bus.read_byte(0xFF81), 0x43);
+
+    tick_m_cycles(&mut bus, 160);
+    assert!(!bus.dma.active());
+    assert_eq!(bus.read_byte(0xC005), 0x05, "Blocked write was dropped");
+}
+
+#[test]
+fn test_oam_dma_restart() {
+    let mut bus = bus();
+    for i in 0..160u16 {
+        bus.write_byte(0xC100 + i, 0x80);
+    }
+    start_dma(&mut bus, 0x00, 0xC0);
+    tick_m_cycles(&mut bus, 11); // Setup and 10 bytes
+
+    // Restart from HRAM code, the old transfer keeps going through the setup.
+    bus.write_byte(0xFF46, 0xC1);
+    bus.tick_components(12);
+    assert_eq!(bus.ppu.oam[12], 12);
+    bus.tick_components(4);
+    assert!(bus.dma.active());
+    assert_eq!(bus.ppu.oam[13], 13);
+
+    tick_m_cycles(&mut bus, 160);
+    assert!(!bus.dma.active());
+    assert!(bus.ppu.oam.iter().all(|&b| b == 0x80));
+}
+
+#[test]
+fn test_oam_dma_from_echo_ram() {
+    let mut bus = bus();
+    bus.write_byte(0xDE00, 0x77);
+    bus.write_byte(0xFF46, 0xFE);
+    bus.tick_components(12);
+    tick_m_cycles(&mut bus, 161);
+    assert_eq!(bus.ppu.oam[0], 0x77);
+}
+
+#[test]
+fn test_stat_write_glitch_only_on_dmg() {
+    for (model, expected) in [(Model::Dmg, 0x02), (Model::Cgb, 0x00)] {
+        let mut bus: Bus<DummyInput> = Bus::new(Vec::new()).with_model(model);
+        bus.ppu.enable_ldc();
+        bus.ppu.lyc = 99;
+        tick_m_cycles(&mut bus, 80); // H-Blank of line 0
+        bus.write_if(0x00);
+
+        bus.write_byte(0xFF41, 0x00); // No sources enabled
+        tick_m_cycles(&mut bus, 1);
+        assert_eq!(bus.read_if() & 0x02, expected, "{model:?}");
+    }
+}
+
+#[test]
+fn test_lyc_write_raises_stat() {
+    let mut bus = bus();
+    bus.ppu.enable_ldc();
+    bus.ppu.lyc = 99;
+    bus.write_byte(0xFF41, 0x40); // LYC source
+    tick_m_cycles(&mut bus, 10);
+    bus.write_if(0x00);
+
+    bus.write_byte(0xFF45, 0x00);
+    tick_m_cycles(&mut bus, 1);
+    assert_eq!(bus.read_if() & 0x02, 0x02);
+}
+
+fn cgb_bus(cgb_flag: u8) -> Bus<DummyInput> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x0143] = cgb_flag;
+    Bus::new(rom).with_model(Model::Cgb)
+}
+
+#[test]
+fn test_cgb_wram_banks() {
+    let mut bus = cgb_bus(0x80);
+    assert!(bus.cgb_mode());
+    for bank in 1..8u8 {
+        bus.write_byte(0xFF70, bank);
+        bus.write_byte(0xD000, bank);
+    }
+    bus.write_byte(0xFF70, 0x00);
+    assert_eq!(bus.read_byte(0xFF70), 0xF8);
+    assert_eq!(bus.read_byte(0xD000), 1, "Bank 0 selects bank 1");
+    for bank in 2..8u8 {
+        bus.write_byte(0xFF70, bank);
+        assert_eq!(bus.read_byte(0xD000), bank);
+        assert_eq!(bus.read_byte(0xF000), bank, "Echo follows the bank");
+    }
+}
+
+#[test]
+fn test_cgb_vram_banks() {
+    let mut bus = cgb_bus(0xC0);
+    bus.write_byte(0x8000, 0x11);
+    bus.write_byte(0xFF4F, 0x01);
+    bus.write_byte(0x8000, 0x22);
+    assert_eq!(bus.read_byte(0x8000), 0x22);
+    bus.write_byte(0xFF4F, 0x00);
+    assert_eq!(bus.read_byte(0x8000), 0x11);
+    assert_eq!(bus.ppu.vram1[0], 0x22);
+}
+
+#[test]
+fn test_dmg_game_on_cgb_has_no_banks() {
+    let mut bus = cgb_bus(0x00);
+    assert!(!bus.cgb_mode());
+    bus.write_byte(0xD000, 0x11);
+    bus.write_byte(0xFF70, 0x02);
+    bus.write_byte(0xFF4F, 0x01);
+    assert_eq!(bus.read_byte(0xFF70), 0xFF);
+    assert_eq!(bus.read_byte(0xFF4F), 0xFF);
+    assert_eq!(bus.read_byte(0xD000), 0x11);
+}
+
+fn setup_hdma(bus: &mut Bus<DummyInput>, source: u16, dest: u16) {
+    for i in 0..0x80u16 {
+        bus.write_byte(source + i, i as u8 + 1);
+    }
+    bus.write_byte(0xFF51, (source >> 8) as u8);
+    bus.write_byte(0xFF52, source as u8);
+    bus.write_byte(0xFF53, (dest >> 8) as u8);
+    bus.write_byte(0xFF54, dest as u8);
+}
+
+#[test]
+fn test_gdma_copies_everything_and_stalls() {
+    let mut bus = cgb_bus(0x80);
+    setup_hdma(&mut bus, 0xC000, 0x8800);
+    let counter = bus.timer.internal_counter;
+
+    bus.write_byte(0xFF55, 0x01); // 2 blocks, general purpose
+    bus.tick_components(4);
+
+    assert_eq!(bus.read_byte(0x8800), 1);
+    assert_eq!(bus.read_byte(0x881F), 0x20);
+    assert_eq!(bus.read_byte(0x8820), 0x00, "Only 2 blocks");
+    assert_eq!(bus.read_byte(0xFF55), 0xFF);
+    assert_eq!(
+        bus.timer.internal_counter.wrapping_sub(counter),
+        4 + 2 * 32,
+        "CPU stopped for 8 M-cycles per block"
+    );
+}
+
+#[test]
+fn test_hdma_copies_one_block_per_hblank() {
+    let mut bus = cgb_bus(0x80);
+    setup_hdma(&mut bus, 0xC000, 0x9000);
+    bus.ppu.enable_ldc();
+    bus.write_byte(0xFF55, 0x80 | 0x02); // 3 blocks, H-Blank
+    assert_eq!(bus.hdma.remaining(), 48);
+
+    tick_m_cycles(&mut bus, 40); // Mode 2 and 3 of line 0
+    assert_eq!(bus.ppu.vram[0x1000], 0x00);
+    tick_m_cycles(&mut bus, 30); // H-Blank
+    assert_eq!(bus.ppu.vram[0x1000], 1);
+    assert_eq!(bus.ppu.vram[0x1010], 0x00);
+    assert_eq!(bus.read_byte(0xFF55), 0x01);
+
+    tick_m_cycles(&mut bus, 114);
+    assert_eq!(bus.ppu.vram[0x1010], 0x11);
+    assert_eq!(bus.hdma.remaining(), 16);
+
+    bus.write_byte(0xFF55, 0x00); // Cancel
+    assert_eq!(bus.read_byte(0xFF55), 0x80);
+    tick_m_cycles(&mut bus, 114);
+    assert_eq!(bus.ppu.vram[0x1020], 0x00);
+}
+
+#[test]
+fn test_hdma_needs_cgb_mode() {
+    let mut bus = cgb_bus(0x00);
+    setup_hdma(&mut bus, 0xC000, 0x8000);
+    bus.write_byte(0xFF55, 0x00);
+    bus.tick_components(4);
+    assert_eq!(bus.read_byte(0xFF55), 0xFF);
+    assert_eq!(bus.read_byte(0x8000), 0x00);
+}
+
+#[test]
+fn test_key1_needs_cgb_mode() {
+    let mut bus = cgb_bus(0x00);
+    bus.write_byte(0xFF4D, 0x01);
+    assert_eq!(bus.read_byte(0xFF4D), 0xFF);
+    assert!(!bus.speed_switch(), "DMG game on a CGB");
+
+    let mut bus = cgb_bus(0x80);
+    assert_eq!(bus.read_byte(0xFF4D), 0x7E);
+    bus.write_byte(0xFF4D, 0xFF);
+    assert_eq!(
+        bus.read_byte(0xFF4D),
+        0x7F,
+        "Only the prepare bit is writable"
+    );
+    assert!(bus.speed_switch());
+    assert_eq!(bus.read_byte(0xFF4D), 0xFE);
+    assert!(!bus.speed_switch(), "Needs arming again");
+}
+
+#[test]
+fn test_double_speed_keeps_ppu_rate() {
+    let mut bus = cgb_bus(0x80);
+    bus.write_byte(0xFF4D, 0x01);
+    bus.speed_switch();
+    bus.ppu.enable_ldc();
+    let counter = bus.timer.internal_counter;
+    let dots = bus.ppu.dot_counter;
+
+    tick_m_cycles(&mut bus, 10);
+    assert_eq!(bus.timer.internal_counter, counter.wrapping_add(40));
+    assert_eq!(
+        bus.ppu.dot_counter,
+        dots + 20,
+        "PPU runs at half the CPU rate"
+    );
+}
+
+fn sgb_bus(flagged: bool) -> Bus<DummyInput> {
+    let mut rom = vec![0; 0x8000];
+    if flagged {
+        rom[0x0146] = 0x03;
+        rom[0x014B] = 0x33;
+    }
+    Bus::new(rom).with_model(Model::Sgb)
+}
+
+// Sends one SGB packet through JOYP.
+fn send_sgb_packet(bus: &mut Bus<DummyInput>, packet: &[u8]) {
+    bus.write_byte(0xFF00, 0x00);
+    bus.write_byte(0xFF00, 0x30);
+    for i in 0..128 {
+        let bit = (packet.get(i / 8).copied().unwrap_or(0) >> (i % 8)) & 1;
+        bus.write_byte(0xFF00, if bit == 1 { 0x10 } else { 0x20 });
+        bus.write_byte(0xFF00, 0x30);
+    }
+    bus.write_byte(0xFF00, 0x20);
+    bus.write_byte(0xFF00, 0x30);
+}
+
+#[test]
+fn test_sgb_mlt_req_through_joypad() {
+    const MLT_REQ_2P: [u8; 2] = [0x11 << 3 | 1, 0x01];
+    let mut bus = sgb_bus(true);
+    assert_eq!(bus.read_byte(0xFF00) & 0x0F, 0x0F);
+    send_sgb_packet(&mut bus, &MLT_REQ_2P);
+    // The stop bit's pulse on P15 already moved on to joypad 2.
+    let first = bus.read_byte(0xFF00) & 0x0F;
+    bus.write_byte(0xFF00, 0x10);
+    bus.write_byte(0xFF00, 0x30);
+    let second = bus.read_byte(0xFF00) & 0x0F;
+    assert_ne!(first, second);
+    assert!([first, second].contains(&0x0E));
+
+    let mut bus = sgb_bus(false);
+    send_sgb_packet(&mut bus, &MLT_REQ_2P);
+    assert_eq!(
+        bus.sgb.as_ref().unwrap().players,
+        1,
+        "Game not flagged for SGB"
+    );
+}
+
+#[test]
+fn test_sgb_frame_follows_palettes() {
+    let mut bus = sgb_bus(true);
+    let mut pal01 = [0u8; 16];
+    pal01[0] = 1; // PAL01, one packet
+    pal01[7..9].copy_from_slice(&0x001Fu16.to_le_bytes()); // Palette 0, color 3
+    send_sgb_packet(&mut bus, &pal01);
+
+    bus.write_byte(0xFF47, 0xFF); // BGP, every pixel shade 3
+    bus.write_byte(0xFF40, 0x91);
+    while !bus.tick_components(4) {}
+    let frame = bus.sgb.as_ref().unwrap().get_frame();
+    let origin = 40 * 256 + 48;
+    assert_eq!(frame[origin], 0x001F);
+}
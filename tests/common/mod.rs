@@ -1,4 +1,5 @@
 pub mod doctor_session;
+pub mod mooneye_evaluator;
 pub mod ring_buffer_doctor;
 pub mod ring_logger;
 pub mod runtime_builder;
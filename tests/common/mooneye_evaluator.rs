@@ -0,0 +1,54 @@
+use gameboy_rs::{cpu::Cpu, input::DummyInput, mmu::Bus, mmu::Memory};
+
+use crate::common::EvaluationSpec;
+
+/// Mooneye ROMs finish by executing `LD B, B` (a software breakpoint).
+const LD_B_B: u8 = 0x40;
+/// On success, the registers B, C, D, E, H, L hold the Fibonacci numbers.
+const PASSED_REGS: [u8; 6] = [3, 5, 8, 13, 21, 34];
+/// On failure, every register holds 0x42.
+const FAILED_REGS: [u8; 6] = [0x42; 6];
+
+pub struct MooneyeEvaluator {
+    max_cycles: u64,
+    cycles: u64,
+    registers: Option<[u8; 6]>,
+}
+
+impl MooneyeEvaluator {
+    pub fn new() -> Self {
+        Self {
+            cycles: 0,
+            // Mooneye ROMs are short, this is mostly a guard against hangs.
+            max_cycles: 5_000_000,
+            registers: None,
+        }
+    }
+}
+
+impl EvaluationSpec for MooneyeEvaluator {
+    fn evaluate(&mut self, cpu: &Cpu, bus: &mut Bus<DummyInput>) -> bool {
+        self.cycles += 1;
+
+        if bus.read_byte(cpu.pc) == LD_B_B {
+            let regs = [cpu.b, cpu.c, cpu.d, cpu.e, cpu.h, cpu.l];
+            if regs == PASSED_REGS || regs == FAILED_REGS {
+                self.registers = Some(regs);
+                return false;
+            }
+        }
+
+        self.cycles < self.max_cycles
+    }
+
+    fn report(&self, cpu: &Cpu, _bus: &Bus<DummyInput>) {
+        match self.registers {
+            Some(PASSED_REGS) => {}
+            Some(_) => panic!("Mooneye test reported failure: {}", cpu),
+            None => panic!(
+                "Mooneye test timed out after {}/{} steps: {}",
+                self.cycles, self.max_cycles, cpu
+            ),
+        }
+    }
+}
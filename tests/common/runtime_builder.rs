@@ -4,6 +4,7 @@ use gameboy_rs::{
     cartridge::{self, Headers},
     cpu::Cpu,
     mmu::Bus,
+    model::Model,
     ppu::Ppu,
 };
 
@@ -13,6 +14,7 @@ pub struct RuntimeBuilder<E: EvaluationSpec> {
     cpu: Option<Cpu>,
     ppu: Option<Ppu>,
     rom_data: Option<Vec<u8>>,
+    model: Option<Model>,
     evaluator: E,
 }
 
@@ -21,6 +23,7 @@ impl RuntimeBuilder<NoopEvaluator> {
         Self {
             cpu: None,
             rom_data: None,
+            model: None,
             evaluator: NoopEvaluator,
             ppu: None,
         }
@@ -51,11 +54,19 @@ impl<E: EvaluationSpec> RuntimeBuilder<E> {
         self
     }
 
+    /// Start from the post-boot state of the given model, a custom CPU or
+    /// PPU still takes precedence.
+    pub fn with_model(mut self, model: Model) -> Self {
+        self.model = Some(model);
+        self
+    }
+
     /// Swap the current evaluator for a different one
     pub fn with_evaluator<NewE: EvaluationSpec>(self, eval: NewE) -> RuntimeBuilder<NewE> {
         RuntimeBuilder {
             cpu: self.cpu,
             rom_data: self.rom_data,
+            model: self.model,
             evaluator: eval,
             ppu: self.ppu,
         }
@@ -67,10 +78,16 @@ impl<E: EvaluationSpec> RuntimeBuilder<E> {
             .expect("ROM data is required to build a session");
         let headers = Headers::new(&rom);
         let mut memory = Bus::new(rom);
+        let mut cpu = Cpu::new();
+        if let Some(model) = self.model {
+            memory = memory.with_model(model);
+            memory.init_post_boot();
+            cpu.reset_post_boot(model);
+        }
         if let Some(ppu) = self.ppu {
             memory.ppu = Box::new(ppu);
         };
-        let cpu = self.cpu.unwrap_or_else(Cpu::new);
+        let cpu = self.cpu.unwrap_or(cpu);
 
         RuntimeSession {
             cpu,
@@ -3,6 +3,7 @@ use gameboy_rs::cpu::{Cpu, StepFlowController};
 use gameboy_rs::input::DummyInput;
 use gameboy_rs::mmu::Bus;
 use gameboy_rs::mmu::Memory;
+use gameboy_rs::model::Model;
 use gameboy_rs::opcodes::{Condition, InstructionSet, Mnemonic, OPCODES, Target};
 use gameboy_rs::ppu::Ppu;
 
@@ -1396,3 +1397,183 @@ fn test_stat_interrupt_storm_prevention() {
         main_loop_hits
     );
 }
+
+#[test]
+fn test_boot_rom_hands_over_to_cartridge() {
+    // NOPs, then the same hand-over the real boot ROM ends with.
+    let mut boot_rom = vec![NOP; 0x100];
+    boot_rom[0xFC..].copy_from_slice(&[0x3E, 0x01, 0xE0, 0x50]); // LD A,1; LDH (0x50),A
+    let mut rom = vec![NOP; 0x8000];
+    rom[0x0100] = INC_A;
+    let mut bus: Bus<DummyInput> = Bus::new(rom).with_boot_rom(boot_rom);
+    let mut cpu = Cpu::new();
+    cpu.reset_pre_boot();
+    assert_eq!(cpu.pc, 0x0000);
+
+    while cpu.pc != 0x0100 {
+        cpu.step(&mut bus);
+    }
+    assert!(
+        !bus.boot_rom_mapped,
+        "Writing 0xFF50 should unmap the boot ROM"
+    );
+    cpu.step(&mut bus);
+    assert_eq!(cpu.a, 0x02, "Execution continues in the cartridge");
+}
+
+#[test]
+fn test_post_boot_registers_per_model() {
+    let mut cpu = Cpu::new();
+
+    // (model, A, F, BC, DE, HL)
+    let cases = [
+        (Model::Dmg0, 0x01, 0x00, 0xFF13, 0x00C1, 0x8403),
+        (Model::Dmg, 0x01, 0xB0, 0x0013, 0x00D8, 0x014D),
+        (Model::Mgb, 0xFF, 0xB0, 0x0013, 0x00D8, 0x014D),
+        (Model::Sgb, 0x01, 0x00, 0x0014, 0x0000, 0xC060),
+        (Model::Cgb, 0x11, 0x80, 0x0000, 0xFF56, 0x000D),
+    ];
+    for (model, a, f, bc, de, hl) in cases {
+        cpu.reset_post_boot(model);
+        let pair = |hi: u8, lo: u8| ((hi as u16) << 8) | lo as u16;
+        assert_eq!((cpu.a, cpu.f), (a, f), "{:?} AF", model);
+        assert_eq!(pair(cpu.b, cpu.c), bc, "{:?} BC", model);
+        assert_eq!(pair(cpu.d, cpu.e), de, "{:?} DE", model);
+        assert_eq!(pair(cpu.h, cpu.l), hl, "{:?} HL", model);
+        assert_eq!((cpu.pc, cpu.sp), (0x0100, 0xFFFE));
+    }
+}
+
+#[test]
+fn test_post_boot_io_per_model() {
+    let mut bus: Bus<DummyInput> = Bus::new(Vec::new()).with_model(Model::Dmg);
+    bus.init_post_boot();
+    assert_eq!(bus.read_byte(ADDR_TIMER_DIV), 0xAB);
+    assert_eq!(bus.read_byte(ADDR_PPU_LCDC), 0x91);
+    assert_eq!(
+        bus.read_byte(ADDR_APU_NR52),
+        0xF1,
+        "Channel 1 still on after the boot sound"
+    );
+    assert_eq!(bus.read_byte(ADDR_APU_NR50), 0x77);
+    assert_eq!(bus.read_byte(ADDR_APU_NR51), 0xF3);
+
+    let mut bus: Bus<DummyInput> = Bus::new(Vec::new()).with_model(Model::Sgb);
+    bus.init_post_boot();
+    assert_eq!(
+        bus.read_byte(ADDR_APU_NR52),
+        0xF0,
+        "The SGB boot ROM is silent"
+    );
+    // Not measured, these models have to run their boot ROM.
+    assert_eq!(Model::Sgb.post_boot_div(), None);
+    assert_eq!(Model::Cgb.post_boot_div(), None);
+
+    let mut bus: Bus<DummyInput> = Bus::new(Vec::new()).with_model(Model::Dmg0);
+    bus.init_post_boot();
+    assert_eq!(bus.read_byte(ADDR_TIMER_DIV), 0x18);
+    assert_eq!(bus.read_byte(ADDR_PPU_LY), 0x91);
+}
+
+// CPU at 0x0100 with `program`, LCD on and the PPU scanning OAM row 5.
+fn oam_bug_bootstrap(model: Model, program: &[u8]) -> (Cpu, Bus<DummyInput>) {
+    let mut bus: Bus<DummyInput> = Bus::new(vec![0; 0x8000]).with_model(model);
+    bus.force_write_bytes(0x0100, program);
+    for (i, b) in bus.ppu.oam.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    bus.ppu.enable_ldc();
+    bus.ppu.dot_counter = 5 * 4;
+    let mut cpu = Cpu::new();
+    cpu.pc = 0x0100;
+    (cpu, bus)
+}
+
+#[test]
+fn test_oam_bug_inc_dec_16() {
+    const INC_HL: u8 = 0x23;
+    const DEC_BC: u8 = 0x0B;
+    for opcode in [INC_HL, DEC_BC] {
+        let (mut cpu, mut bus) = oam_bug_bootstrap(Model::Dmg, &[opcode]);
+        let before = bus.ppu.oam;
+        (cpu.h, cpu.l, cpu.b, cpu.c) = (0xFE, 0x10, 0xFE, 0x10);
+        cpu.step(&mut bus);
+        assert_ne!(
+            bus.ppu.oam, before,
+            "Opcode {opcode:02X} should corrupt OAM"
+        );
+        assert_eq!(
+            bus.ppu.oam[42..48],
+            before[34..40],
+            "Row 5 takes over row 4"
+        );
+    }
+}
+
+#[test]
+fn test_oam_bug_needs_oam_address_and_dmg() {
+    const INC_HL: u8 = 0x23;
+    let (mut cpu, mut bus) = oam_bug_bootstrap(Model::Dmg, &[INC_HL]);
+    let before = bus.ppu.oam;
+    (cpu.h, cpu.l) = (0xC0, 0x00);
+    cpu.step(&mut bus);
+    assert_eq!(bus.ppu.oam, before, "HL outside OAM");
+
+    let (mut cpu, mut bus) = oam_bug_bootstrap(Model::Cgb, &[INC_HL]);
+    (cpu.h, cpu.l) = (0xFE, 0x10);
+    cpu.step(&mut bus);
+    assert_eq!(bus.ppu.oam, before, "Fixed on CGB");
+}
+
+#[test]
+fn test_oam_bug_pop_and_ldi() {
+    const LD_A_HLI: u8 = 0x2A;
+    for opcode in [POP_BC, LD_A_HLI] {
+        let (mut cpu, mut bus) = oam_bug_bootstrap(Model::Dmg, &[opcode]);
+        let before = bus.ppu.oam;
+        cpu.sp = 0xFE40;
+        (cpu.h, cpu.l) = (0xFE, 0x40);
+        cpu.step(&mut bus);
+        assert_ne!(
+            bus.ppu.oam, before,
+            "Opcode {opcode:02X} should corrupt OAM"
+        );
+        assert_eq!(
+            bus.ppu.oam[24..32],
+            bus.ppu.oam[32..40],
+            "Read during increase copies row 4 over row 3"
+        );
+    }
+}
+
+#[test]
+fn test_oam_bug_push() {
+    let (mut cpu, mut bus) = oam_bug_bootstrap(Model::Dmg, &[PUSH_BC]);
+    let before = bus.ppu.oam;
+    cpu.sp = 0xFE40;
+    cpu.step(&mut bus);
+    assert_ne!(bus.ppu.oam, before);
+    assert_eq!(bus.ppu.oam[42..48], before[34..40]);
+}
+
+#[test]
+fn test_stop_switches_speed_when_armed() {
+    const STOP: u8 = 0x10;
+    let mut rom = vec![0; 0x8000];
+    rom[0x0143] = 0x80;
+    let mut bus: Bus<DummyInput> = Bus::new(rom).with_model(Model::Cgb);
+    bus.force_write_bytes(0x0100, &[STOP, STOP]);
+    let mut cpu = Cpu::new();
+    cpu.pc = 0x0100;
+
+    bus.write_byte(ADDR_SYS_KEY1, 0x01);
+    cpu.step(&mut bus);
+    assert!(!cpu.halted, "A speed switch doesn't stop the CPU");
+    assert!(bus.double_speed);
+    assert_eq!(bus.read_byte(ADDR_SYS_KEY1), 0xFE, "Armed bit is cleared");
+    assert!(cpu.take_snapshot(&bus).double_speed);
+
+    cpu.step(&mut bus);
+    assert!(cpu.halted, "Not armed, plain STOP");
+    assert!(bus.double_speed);
+}
@@ -5,6 +5,7 @@ mod common;
 
 use std::path::Path;
 
+use gameboy_rs::model::Model;
 use gameboy_rs::{mmu::Bus, ppu::Ppu};
 
 use crate::common::{DoctorEvaluator, RuntimeBuilder, RuntimeSession};
@@ -28,7 +29,7 @@ fn run_doctor_test(rom_id: &str, rom_name: &str) {
     );
 
     let mut ppu = Ppu::new();
-    ppu.init_post_boot();
+    ppu.init_post_boot(Model::Dmg);
 
     let mut runtime: RuntimeSession<DoctorEvaluator> = RuntimeBuilder::new()
         .with_rom_path(Path::new(&rom_path))
@@ -0,0 +1,45 @@
+mod common;
+
+use std::path::Path;
+
+use gameboy_rs::model::Model;
+
+use crate::common::{RuntimeBuilder, RuntimeSession, mooneye_evaluator::MooneyeEvaluator};
+
+// Helper to run a mooneye test ROM until it hits its `LD B, B` breakpoint.
+fn run_test(rom_path: &str) {
+    assert!(
+        Path::new(rom_path).exists(),
+        "{} is missing, build the mooneye test suite and copy its ROMs to tests/tools/mooneye-test-suite/",
+        rom_path
+    );
+    let mut runtime: RuntimeSession<MooneyeEvaluator> = RuntimeBuilder::new()
+        .with_rom_path(Path::new(&rom_path))
+        .with_model(model_for_rom(rom_path))
+        .with_evaluator(MooneyeEvaluator::new())
+        .build();
+
+    runtime.run_to_completition();
+}
+
+/// Mooneye ROMs name the models they expect to pass on, e.g. `boot_regs-dmgABC.gb`.
+fn model_for_rom(rom_path: &str) -> Model {
+    let name = Path::new(rom_path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    let suffix = name.rsplit('-').next().unwrap_or_default();
+    if suffix.starts_with("dmg0") {
+        Model::Dmg0
+    } else if suffix.starts_with("mgb") {
+        Model::Mgb
+    } else if suffix.starts_with("sgb") {
+        Model::Sgb
+    } else if suffix.starts_with("cgb") {
+        Model::Cgb
+    } else {
+        Model::Dmg
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/generated_mooneye_tests.rs"));
@@ -112,10 +112,11 @@ fn test_ppu_vblank_wrap_around() {
     // Total dots to reach end of frame: 154 * 456 = 70,224
     // We are at 65,664. We need 4,560 more dots to finish line 153.
     ppu.tick(4559);
+    assert_eq!(ppu.ly, 153, "Should be on the last line of V-Blank");
     assert_eq!(
         ppu.read_byte(ADDR_PPU_LY),
-        153,
-        "Should be on the last line of V-Blank"
+        0,
+        "LY reads 0 after the first M-cycle of line 153"
     );
     assert_eq!(
         ppu.read_byte(ADDR_PPU_STAT) & 0x03,
@@ -229,9 +230,11 @@ fn test_ppu_lyc_flag_timing() {
         "LYC flag should be 0 (LY=0, LYC=1)"
     );
 
-    // Move to line 1
+    // Move to line 1, the comparator catches up one M-cycle later
     ppu.tick(1);
     assert_eq!(ppu.read_byte(ADDR_PPU_LY), 1);
+    assert_eq!(ppu.read_byte(ADDR_PPU_STAT) & 0x04, 0, "LY still changing");
+    ppu.tick(4);
     assert_eq!(
         ppu.read_byte(ADDR_PPU_STAT) & 0x04,
         0x04,
@@ -370,3 +373,527 @@ fn test_ppu_mode_transitions() {
         "Should be in Mode 0 at the end of a line"
     );
 }
+
+// Fills tile `id` (unsigned addressing) with a single color index.
+fn fill_tile(ppu: &mut Ppu, id: u8, color_idx: u8) {
+    let lo = if color_idx & 1 != 0 { 0xFF } else { 0x00 };
+    let hi = if color_idx & 2 != 0 { 0xFF } else { 0x00 };
+    for row in 0..8u16 {
+        let addr = 0x8000 + id as u16 * 16 + row * 2;
+        ppu.write_byte(addr, lo);
+        ppu.write_byte(addr + 1, hi);
+    }
+}
+
+fn window_ppu() -> Ppu {
+    let mut ppu = Ppu::new();
+    fill_tile(&mut ppu, 1, 3);
+    fill_tile(&mut ppu, 2, 1);
+    // BG map at 0x9800 stays tile 0 (color 0), window map at 0x9C00: row 0 tile 1, rest tile 2.
+    for i in 0..0x400u16 {
+        ppu.write_byte(0x9C00 + i, if i < 32 { 1 } else { 2 });
+    }
+    ppu.write_byte(ADDR_PPU_BGP, 0xE4); // Identity palette
+    ppu
+}
+
+fn pixel(ppu: &Ppu, x: usize, y: usize) -> u8 {
+    ppu.get_frame_buffer()[y * 160 + x]
+}
+
+#[test]
+fn test_window_draws_from_its_own_map() {
+    let mut ppu = window_ppu();
+    ppu.write_byte(ADDR_PPU_WY, 10);
+    ppu.write_byte(ADDR_PPU_WX, 80 + 7);
+    // LCD on, window map 0x9C00, window on, unsigned tiles, BG map 0x9800, BG on
+    ppu.write_byte(ADDR_PPU_LCDC, 0xF1);
+    ppu.tick(456 * 144);
+
+    assert_eq!(pixel(&ppu, 100, 9), 0, "Window must not start above WY");
+    assert_eq!(pixel(&ppu, 79, 10), 0, "Window must not start left of WX-7");
+    assert_eq!(pixel(&ppu, 80, 10), 3, "Window row 0 should start at WY");
+    assert_eq!(pixel(&ppu, 159, 17), 3);
+    assert_eq!(pixel(&ppu, 80, 18), 1, "Window row 1 should follow");
+}
+
+#[test]
+fn test_window_ignored_when_disabled() {
+    let mut ppu = window_ppu();
+    ppu.write_byte(ADDR_PPU_WX, 7);
+    ppu.write_byte(ADDR_PPU_LCDC, 0xD1); // Window map set, but bit 5 clear
+    ppu.tick(456 * 144);
+
+    assert!(ppu.get_frame_buffer().iter().all(|&c| c == 0));
+}
+
+#[test]
+fn test_window_line_counter_skips_hidden_lines() {
+    let mut ppu = window_ppu();
+    ppu.write_byte(ADDR_PPU_WX, 7);
+    ppu.write_byte(ADDR_PPU_LCDC, 0xF1);
+    ppu.tick(456 * 4); // Window drawn on lines 0-3
+
+    ppu.write_byte(ADDR_PPU_LCDC, 0xD1);
+    ppu.tick(456 * 20); // Hidden on lines 4-23
+
+    ppu.write_byte(ADDR_PPU_LCDC, 0xF1);
+    ppu.tick(456 * 10);
+
+    // Line 24 is only the 5th window line, so it still shows window row 0.
+    assert_eq!(pixel(&ppu, 0, 24), 3);
+    assert_eq!(pixel(&ppu, 0, 27), 3);
+    assert_eq!(pixel(&ppu, 0, 28), 1, "Window row 1 starts on its 9th line");
+    assert_eq!(ppu.window_line, 14);
+}
+
+#[test]
+fn test_window_line_counter_resets_each_frame() {
+    let mut ppu = window_ppu();
+    ppu.write_byte(ADDR_PPU_WX, 7);
+    ppu.write_byte(ADDR_PPU_LCDC, 0xF1);
+    ppu.tick(456 * 154); // One full frame
+    assert_eq!(ppu.window_line, 0);
+
+    ppu.tick(456 * 8);
+    assert_eq!(
+        pixel(&ppu, 0, 7),
+        3,
+        "Second frame restarts at window row 0"
+    );
+}
+
+// Turns the LCD on with `lcdc` and counts the dots line 0 spends in mode 3.
+fn mode3_length(ppu: &mut Ppu, lcdc: u8) -> u32 {
+    ppu.write_byte(ADDR_PPU_LCDC, lcdc);
+    ppu.tick(80);
+    let mut dots = 0;
+    while ppu.read_byte(ADDR_PPU_STAT) & 0x03 == 3 {
+        ppu.tick(1);
+        dots += 1;
+    }
+    dots
+}
+
+#[test]
+fn test_mode3_minimal_length() {
+    let mut ppu = Ppu::new();
+    assert_eq!(mode3_length(&mut ppu, 0x91), 172);
+}
+
+#[test]
+fn test_mode3_fine_scroll_penalty() {
+    for scx in [0u8, 1, 3, 7, 8, 13] {
+        let mut ppu = Ppu::new();
+        ppu.write_byte(ADDR_PPU_SCX, scx);
+        assert_eq!(
+            mode3_length(&mut ppu, 0x91),
+            172 + (scx % 8) as u32,
+            "SCX={scx}"
+        );
+    }
+}
+
+#[test]
+fn test_mode3_window_penalty() {
+    let mut ppu = Ppu::new();
+    ppu.write_byte(ADDR_PPU_WY, 0);
+    ppu.write_byte(ADDR_PPU_WX, 7 + 40);
+    assert_eq!(mode3_length(&mut ppu, 0xB1), 172 + 6);
+}
+
+#[test]
+fn test_mode3_sprite_penalty() {
+    for x in [0u8, 8, 12, 48, 100, 167] {
+        let mut ppu = Ppu::new();
+        ppu.write_byte(0xFE00, 16); // On line 0
+        ppu.write_byte(0xFE01, x);
+        let dots = mode3_length(&mut ppu, 0x93);
+        assert!(
+            (172 + 6..=172 + 12).contains(&dots),
+            "Sprite at X={x} took {dots} dots"
+        );
+    }
+
+    // Off-screen to the right, never fetched.
+    let mut ppu = Ppu::new();
+    ppu.write_byte(0xFE00, 16);
+    ppu.write_byte(0xFE01, 168);
+    assert_eq!(mode3_length(&mut ppu, 0x93), 172);
+}
+
+#[test]
+fn test_sprites_mixed_through_fifo() {
+    let mut ppu = window_ppu();
+    ppu.write_byte(ADDR_PPU_OBP0, 0xFF); // Every sprite color maps to 3
+    ppu.write_byte(0xFE00, 16);
+    ppu.write_byte(0xFE01, 8 + 20);
+    ppu.write_byte(0xFE02, 1);
+    ppu.write_byte(0x9800, 2); // BG color 1 under the first tile
+    mode3_length(&mut ppu, 0x93);
+
+    assert_eq!(pixel(&ppu, 0, 0), 1);
+    assert_eq!(pixel(&ppu, 19, 0), 0);
+    assert!((20..28).all(|x| pixel(&ppu, x, 0) == 3));
+    assert_eq!(pixel(&ppu, 28, 0), 0);
+
+    // Behind-BG sprite is hidden by non-zero BG.
+    let mut ppu = window_ppu();
+    ppu.write_byte(ADDR_PPU_OBP0, 0xFF);
+    ppu.write_byte(0xFE00, 16);
+    ppu.write_byte(0xFE01, 8 + 4);
+    ppu.write_byte(0xFE02, 1);
+    ppu.write_byte(0xFE03, 0x80);
+    ppu.write_byte(0x9800, 2);
+    mode3_length(&mut ppu, 0x93);
+    assert_eq!(pixel(&ppu, 4, 0), 1, "BG wins over a behind-BG sprite");
+    assert_eq!(pixel(&ppu, 8, 0), 3, "Sprite still draws over BG color 0");
+}
+
+#[test]
+fn test_mid_scanline_palette_write() {
+    let mut ppu = window_ppu();
+    for i in 0..32 {
+        ppu.write_byte(0x9800 + i, 1); // Color 3 along the whole line
+    }
+    ppu.write_byte(ADDR_PPU_LCDC, 0x91);
+    // Pixel n is shifted out on dot 80 + 12 + n
+    ppu.tick(80 + 12 + 40);
+    ppu.write_byte(ADDR_PPU_BGP, 0x24); // Color 3 now maps to 0
+    ppu.tick(456);
+
+    assert_eq!(pixel(&ppu, 40, 0), 3);
+    assert_eq!(pixel(&ppu, 41, 0), 0);
+    assert_eq!(pixel(&ppu, 159, 0), 0);
+}
+
+// Places sprite `i` on line 0 at `x`, using the solid color 3 tile.
+fn place_sprite(ppu: &mut Ppu, i: u16, x: u8, attrs: u8) {
+    ppu.write_byte(0xFE00 + i * 4, 16);
+    ppu.write_byte(0xFE01 + i * 4, x);
+    ppu.write_byte(0xFE02 + i * 4, 1);
+    ppu.write_byte(0xFE03 + i * 4, attrs);
+}
+
+#[test]
+fn test_only_ten_sprites_per_line() {
+    let mut ppu = window_ppu();
+    ppu.write_byte(ADDR_PPU_OBP0, 0xFF);
+    for i in 0..11 {
+        place_sprite(&mut ppu, i, 8 + i as u8 * 10, 0);
+    }
+    mode3_length(&mut ppu, 0x93);
+
+    assert_eq!(pixel(&ppu, 90, 0), 3, "10th sprite is drawn");
+    assert_eq!(pixel(&ppu, 100, 0), 0, "11th sprite is dropped");
+}
+
+#[test]
+fn test_offscreen_sprites_count_towards_the_limit() {
+    let mut ppu = window_ppu();
+    ppu.write_byte(ADDR_PPU_OBP0, 0xFF);
+    for i in 0..10 {
+        place_sprite(&mut ppu, i, 0, 0); // X=0 is hidden but still selected
+    }
+    place_sprite(&mut ppu, 10, 8 + 50, 0);
+    mode3_length(&mut ppu, 0x93);
+
+    assert_eq!(pixel(&ppu, 50, 0), 0);
+}
+
+#[test]
+fn test_sprite_x_priority() {
+    let mut ppu = window_ppu();
+    ppu.write_byte(ADDR_PPU_OBP0, 0xFF);
+    ppu.write_byte(ADDR_PPU_OBP1, 0x55); // Every color maps to 1
+    place_sprite(&mut ppu, 0, 8 + 24, 0);
+    place_sprite(&mut ppu, 1, 8 + 20, 0x10); // Further left, wins despite the higher index
+    place_sprite(&mut ppu, 2, 8 + 60, 0x10);
+    place_sprite(&mut ppu, 3, 8 + 60, 0); // Same X, lower index wins
+    mode3_length(&mut ppu, 0x93);
+
+    assert_eq!(pixel(&ppu, 24, 0), 1);
+    assert_eq!(pixel(&ppu, 27, 0), 1);
+    assert_eq!(pixel(&ppu, 28, 0), 3, "Uncovered part of sprite 0");
+    assert_eq!(pixel(&ppu, 60, 0), 1);
+}
+
+#[test]
+fn test_sprite_bg_priority_uses_color_index() {
+    let mut ppu = window_ppu();
+    ppu.write_byte(ADDR_PPU_BGP, 0x00); // BG colors 0 and 1 look the same
+    ppu.write_byte(ADDR_PPU_OBP0, 0xFF);
+    ppu.write_byte(0x9800, 2); // BG color 1 under the first tile
+    place_sprite(&mut ppu, 0, 8 + 4, 0x80);
+    mode3_length(&mut ppu, 0x93);
+
+    assert_eq!(pixel(&ppu, 4, 0), 0, "Hidden behind BG color index 1");
+    assert_eq!(pixel(&ppu, 8, 0), 3, "Drawn over BG color index 0");
+}
+
+#[test]
+fn test_lcdc_bit0_blanks_bg_and_window() {
+    let mut ppu = window_ppu();
+    ppu.write_byte(ADDR_PPU_OBP0, 0xFF);
+    for i in 0..32 {
+        ppu.write_byte(0x9800 + i, 1);
+    }
+    ppu.write_byte(ADDR_PPU_WX, 7 + 80);
+    place_sprite(&mut ppu, 0, 8 + 20, 0x80); // Behind BG, but BG is off
+    // LCD, window and sprites on, BG/window off
+    mode3_length(&mut ppu, 0xF2);
+
+    assert_eq!(pixel(&ppu, 0, 0), 0, "BG blanked");
+    assert_eq!(pixel(&ppu, 100, 0), 0, "Window blanked");
+    assert_eq!(pixel(&ppu, 20, 0), 3, "Sprites still draw");
+}
+
+#[test]
+fn test_lcdc_bit0_mid_line() {
+    let mut ppu = window_ppu();
+    for i in 0..32 {
+        ppu.write_byte(0x9800 + i, 1);
+    }
+    ppu.write_byte(ADDR_PPU_LCDC, 0x91);
+    ppu.tick(80 + 12 + 40);
+    ppu.write_byte(ADDR_PPU_LCDC, 0x90);
+    ppu.tick(456);
+
+    assert_eq!(pixel(&ppu, 40, 0), 3);
+    assert_eq!(pixel(&ppu, 41, 0), 0);
+}
+
+#[test]
+fn test_lcdc_bit1_toggles_sprites_mid_frame() {
+    let mut ppu = window_ppu();
+    ppu.write_byte(ADDR_PPU_OBP0, 0xFF);
+    ppu.write_byte(0xFE00, 16 + 2); // Lines 2-9
+    ppu.write_byte(0xFE01, 8);
+    ppu.write_byte(0xFE02, 1);
+    ppu.write_byte(ADDR_PPU_LCDC, 0x91);
+    ppu.tick(456 * 4); // Lines 0-3 without sprites
+    ppu.write_byte(ADDR_PPU_LCDC, 0x93);
+    ppu.tick(456 * 4);
+
+    assert_eq!(pixel(&ppu, 0, 3), 0);
+    assert_eq!(pixel(&ppu, 0, 4), 3);
+    assert_eq!(pixel(&ppu, 0, 7), 3);
+}
+
+#[test]
+fn test_lcdc_bit2_sprite_height_mid_line() {
+    let mut ppu = window_ppu();
+    fill_tile(&mut ppu, 3, 3);
+    ppu.write_byte(ADDR_PPU_OBP0, 0xE4);
+    for (i, x) in [(0u16, 8 + 10), (1, 8 + 100)] {
+        ppu.write_byte(0xFE00 + i * 4, 16); // Rows 0-15 in 8x16 mode
+        ppu.write_byte(0xFE01 + i * 4, x);
+        ppu.write_byte(0xFE02 + i * 4, 2);
+    }
+    ppu.write_byte(ADDR_PPU_LCDC, 0x97);
+    ppu.tick(456 * 8 + 80 + 12 + 50); // Line 8, past the first sprite
+    ppu.write_byte(ADDR_PPU_LCDC, 0x93);
+    ppu.tick(456);
+
+    assert_eq!(pixel(&ppu, 10, 8), 3, "Second tile of the 8x16 sprite");
+    assert_eq!(pixel(&ppu, 100, 8), 1, "Fetched as 8x8 after the switch");
+}
+
+#[test]
+fn test_lcdc_bit3_bg_tile_map() {
+    for (lcdc, expected) in [(0x91, 0), (0x99, 3)] {
+        let mut ppu = window_ppu();
+        ppu.write_byte(ADDR_PPU_LCDC, lcdc);
+        ppu.tick(456);
+        assert_eq!(pixel(&ppu, 0, 0), expected, "LCDC={lcdc:02X}");
+    }
+}
+
+#[test]
+fn test_lcdc_bit4_tile_data_area() {
+    for (lcdc, expected) in [(0x91, 3), (0x81, 2)] {
+        let mut ppu = window_ppu();
+        // Tile 1 is at 0x8010 in unsigned mode and at 0x9010 in signed mode.
+        for row in 0..8u16 {
+            ppu.write_byte(0x9010 + row * 2, 0x00);
+            ppu.write_byte(0x9011 + row * 2, 0xFF);
+        }
+        ppu.write_byte(0x9800, 1);
+        ppu.write_byte(ADDR_PPU_LCDC, lcdc);
+        ppu.tick(456);
+        assert_eq!(pixel(&ppu, 0, 0), expected, "LCDC={lcdc:02X}");
+    }
+}
+
+#[test]
+fn test_stat_blocking_between_hblank_and_oam_scan() {
+    let mut ppu = ppu();
+    ppu.write_byte(ADDR_PPU_LYC, 99);
+    ppu.enable_ldc();
+    ppu.write_byte(ADDR_PPU_STAT, 0x28); // Mode 0 and mode 2 sources
+
+    let (_, triggered) = ppu.tick(300); // Into H-Blank of line 0
+    assert!(triggered, "H-Blank raises the line");
+
+    let (_, triggered) = ppu.tick(160);
+    assert_eq!(ppu.ly, 1);
+    assert!(!triggered, "Line held high from H-Blank into mode 2");
+}
+
+#[test]
+fn test_stat_mode2_source_fires_on_line_144() {
+    let mut ppu = ppu();
+    ppu.write_byte(ADDR_PPU_LYC, 99);
+    ppu.enable_ldc();
+    ppu.write_byte(ADDR_PPU_STAT, 0x20);
+
+    ppu.tick(143 * 456 + 455);
+    let (vblank, stat) = ppu.tick(1);
+    assert!(vblank);
+    assert!(stat, "Mode 2 source also fires at the start of V-Blank");
+}
+
+fn cgb_ppu() -> Ppu {
+    let mut ppu = Ppu::new();
+    ppu.cgb_mode = true;
+    ppu
+}
+
+/// Writes one RGB555 color through BCPS/BCPD or OCPS/OCPD.
+fn set_color(ppu: &mut Ppu, spec_addr: u16, palette: u8, color_idx: u8, rgb: u16) {
+    ppu.write_byte(spec_addr, 0x80 | (palette * 8 + color_idx * 2));
+    for b in rgb.to_le_bytes() {
+        ppu.write_byte(spec_addr + 1, b);
+    }
+}
+
+fn rgb(ppu: &Ppu, x: usize, y: usize) -> u16 {
+    ppu.get_frame_buffer_rgb()[y * 160 + x]
+}
+
+#[test]
+fn test_cgb_registers_need_cgb_mode() {
+    let mut ppu = Ppu::new();
+    ppu.write_byte(ADDR_PPU_VBK, 0x01);
+    set_color(&mut ppu, ADDR_PPU_BCPS, 0, 0, 0x1234);
+    for addr in [ADDR_PPU_VBK, ADDR_PPU_BCPS, ADDR_PPU_BCPD] {
+        assert_eq!(ppu.read_byte(addr), 0xFF, "{addr:04X}");
+    }
+
+    let mut ppu = cgb_ppu();
+    ppu.write_byte(ADDR_PPU_VBK, 0x01);
+    assert_eq!(ppu.read_byte(ADDR_PPU_VBK), 0xFF);
+    ppu.write_byte(ADDR_PPU_VBK, 0x00);
+    assert_eq!(ppu.read_byte(ADDR_PPU_VBK), 0xFE);
+    set_color(&mut ppu, ADDR_PPU_OCPS, 7, 3, 0x1234);
+    ppu.write_byte(ADDR_PPU_OCPS, 0x3E);
+    assert_eq!(ppu.read_byte(ADDR_PPU_OCPD), 0x34);
+}
+
+#[test]
+fn test_cgb_palette_ram_blocked_in_mode3() {
+    let mut ppu = cgb_ppu();
+    ppu.write_byte(ADDR_PPU_BCPS, 0x80);
+    ppu.write_byte(ADDR_PPU_BCPD, 0x11);
+    ppu.write_byte(ADDR_PPU_LCDC, 0x91);
+    ppu.tick(100); // Mode 3
+
+    ppu.write_byte(ADDR_PPU_BCPD, 0x22);
+    assert_eq!(ppu.read_byte(ADDR_PPU_BCPD), 0xFF);
+    assert_eq!(ppu.read_byte(ADDR_PPU_BCPS), 0xC2, "Index still moved");
+    assert_eq!(ppu.bg_palettes.data[..2], [0x11, 0x00]);
+}
+
+#[test]
+fn test_cgb_bg_attributes_pick_palette_and_bank() {
+    let mut ppu = cgb_ppu();
+    ppu.write_byte(ADDR_PPU_VBK, 1);
+    fill_tile(&mut ppu, 1, 3);
+    ppu.write_byte(0x9800, 0x08 | 0x02); // Bank 1, palette 2
+    ppu.write_byte(ADDR_PPU_VBK, 0);
+    ppu.write_byte(0x9800, 1);
+    set_color(&mut ppu, ADDR_PPU_BCPS, 2, 3, 0x7C00);
+    mode3_length(&mut ppu, 0x91);
+
+    assert_eq!(rgb(&ppu, 0, 0), 0x7C00);
+    assert_eq!(pixel(&ppu, 0, 0), 3, "Darkest shade for the 2-bit buffer");
+    assert_eq!(rgb(&ppu, 8, 0), 0x0000, "Tile 0, palette 0 color 0");
+}
+
+#[test]
+fn test_cgb_bg_flips() {
+    let mut ppu = cgb_ppu();
+    ppu.write_byte(0x8010, 0x80); // Tile 1, row 0: leftmost pixel only
+    ppu.write_byte(0x9800, 1);
+    ppu.write_byte(0x9801, 1);
+    ppu.write_byte(ADDR_PPU_VBK, 1);
+    ppu.write_byte(0x9800, 0x20); // X flip
+    ppu.write_byte(0x9801, 0x40); // Y flip
+    ppu.write_byte(ADDR_PPU_VBK, 0);
+    set_color(&mut ppu, ADDR_PPU_BCPS, 0, 1, 0x001F);
+    ppu.write_byte(ADDR_PPU_LCDC, 0x91);
+    ppu.tick(456 * 8);
+
+    assert_eq!(rgb(&ppu, 0, 0), 0x0000);
+    assert_eq!(rgb(&ppu, 7, 0), 0x001F, "X flipped");
+    assert_eq!(rgb(&ppu, 8, 0), 0x0000);
+    assert_eq!(rgb(&ppu, 8, 7), 0x001F, "Y flipped");
+}
+
+#[test]
+fn test_cgb_lcdc_bit0_is_master_priority() {
+    for (lcdc, expected) in [(0x93, 0x001F), (0x92, 0x03E0)] {
+        let mut ppu = cgb_ppu();
+        fill_tile(&mut ppu, 1, 1);
+        ppu.write_byte(0x9800, 1);
+        ppu.write_byte(0x9801, 1);
+        ppu.write_byte(ADDR_PPU_VBK, 1);
+        ppu.write_byte(0x9800, 0x80); // BG over OBJ
+        ppu.write_byte(ADDR_PPU_VBK, 0);
+        set_color(&mut ppu, ADDR_PPU_BCPS, 0, 1, 0x001F);
+        set_color(&mut ppu, ADDR_PPU_OCPS, 0, 1, 0x03E0);
+        place_sprite(&mut ppu, 0, 8, 0);
+        mode3_length(&mut ppu, lcdc);
+
+        assert_eq!(rgb(&ppu, 0, 0), expected, "LCDC {lcdc:02X}");
+        assert_eq!(rgb(&ppu, 8, 0), 0x001F, "BG isn't blanked in CGB mode");
+    }
+}
+
+#[test]
+fn test_cgb_sprite_priority_by_oam_index() {
+    let mut ppu = cgb_ppu();
+    fill_tile(&mut ppu, 1, 1);
+    set_color(&mut ppu, ADDR_PPU_OCPS, 0, 1, 0x001F);
+    set_color(&mut ppu, ADDR_PPU_OCPS, 1, 1, 0x7C00);
+    place_sprite(&mut ppu, 0, 12, 0x01); // Right, palette 1
+    place_sprite(&mut ppu, 1, 8, 0x00); // Left, palette 0
+    mode3_length(&mut ppu, 0x93);
+
+    assert_eq!(rgb(&ppu, 0, 0), 0x001F);
+    assert_eq!(rgb(&ppu, 5, 0), 0x7C00, "Lower OAM index wins the overlap");
+}
+
+#[test]
+fn test_dmg_rgb_frame_buffer_uses_shades() {
+    let mut ppu = Ppu::new();
+    fill_tile(&mut ppu, 0, 2);
+    ppu.write_byte(ADDR_PPU_BGP, 0xE4);
+    mode3_length(&mut ppu, 0x91);
+
+    assert_eq!(pixel(&ppu, 0, 0), 2);
+    assert_eq!(rgb(&ppu, 0, 0), 0x294A);
+}
+
+#[test]
+fn test_cgb_sprite_tile_from_bank1() {
+    let mut ppu = cgb_ppu();
+    ppu.write_byte(ADDR_PPU_VBK, 1);
+    fill_tile(&mut ppu, 1, 2);
+    ppu.write_byte(ADDR_PPU_VBK, 0);
+    set_color(&mut ppu, ADDR_PPU_OCPS, 3, 2, 0x03E0);
+    place_sprite(&mut ppu, 0, 8, 0x08 | 0x03); // Bank 1, palette 3
+    mode3_length(&mut ppu, 0x93);
+
+    assert_eq!(rgb(&ppu, 0, 0), 0x03E0);
+}
@@ -0,0 +1,317 @@
+use gameboy_rs::cpu::Cpu;
+use gameboy_rs::input::DummyInput;
+use gameboy_rs::mmu::{Bus, Memory};
+use gameboy_rs::model::Model;
+use gameboy_rs::state::{RewindBuffer, STATE_VERSION, StateError, load_state, save_state};
+
+const INC_A: u8 = 0x3C;
+const LD_HL_A: u8 = 0x77; // LD (HL), A
+const INC_HL: u8 = 0x23;
+const JR: u8 = 0x18;
+
+/// A ROM-only cart with a valid global checksum field.
+fn rom(checksum: u16) -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x014E..0x0150].copy_from_slice(&checksum.to_be_bytes());
+    rom
+}
+
+/// Loops forever incrementing A and storing it to an incrementing address.
+fn bootstrap(checksum: u16) -> (Cpu, Bus<DummyInput>) {
+    let mut bus: Bus<DummyInput> = Bus::new(rom(checksum));
+    bus.force_write_bytes(0x100, &[INC_A, LD_HL_A, INC_HL, JR, 0xFB]);
+    let mut cpu = Cpu::new();
+    cpu.h = 0xC0;
+    cpu.l = 0x00;
+    (cpu, bus)
+}
+
+fn run(cpu: &mut Cpu, bus: &mut Bus<DummyInput>, steps: usize) {
+    for _ in 0..steps {
+        let cycles = cpu.step(bus);
+        bus.tick_components(cycles);
+    }
+}
+
+#[test]
+fn test_state_round_trip_is_deterministic() {
+    let (mut cpu, mut bus) = bootstrap(0x1234);
+    run(&mut cpu, &mut bus, 1000);
+    let state = save_state(&cpu, &bus);
+
+    run(&mut cpu, &mut bus, 5000);
+    let expected = save_state(&cpu, &bus);
+
+    // Rewind to the snapshot and replay the same steps.
+    load_state(&mut cpu, &mut bus, &state).unwrap();
+    run(&mut cpu, &mut bus, 5000);
+    assert_eq!(save_state(&cpu, &bus), expected);
+}
+
+#[test]
+fn test_state_restores_into_fresh_machine() {
+    let (mut cpu, mut bus) = bootstrap(0x1234);
+    run(&mut cpu, &mut bus, 3000);
+    bus.ppu.scx = 0x42;
+    let state = save_state(&cpu, &bus);
+
+    let (mut fresh_cpu, mut fresh_bus) = bootstrap(0x1234);
+    load_state(&mut fresh_cpu, &mut fresh_bus, &state).unwrap();
+    assert_eq!(fresh_cpu.pc, cpu.pc);
+    assert_eq!(fresh_cpu.a, cpu.a);
+    assert_eq!(fresh_bus.read_byte(0xC010), bus.read_byte(0xC010));
+    assert_eq!(fresh_bus.ppu.ly, bus.ppu.ly);
+    assert_eq!(fresh_bus.ppu.scx, 0x42);
+    assert_eq!(fresh_bus.timer.internal_counter, bus.timer.internal_counter);
+}
+
+#[test]
+fn test_state_rejects_other_rom() {
+    let (cpu, bus) = bootstrap(0x1234);
+    let state = save_state(&cpu, &bus);
+
+    let (mut other_cpu, mut other_bus) = bootstrap(0xBEEF);
+    other_cpu.a = 0x99;
+    assert_eq!(
+        load_state(&mut other_cpu, &mut other_bus, &state),
+        Err(StateError::RomMismatch {
+            expected: 0xBEEF,
+            found: 0x1234
+        })
+    );
+    assert_eq!(other_cpu.a, 0x99, "A rejected state must not be applied");
+}
+
+#[test]
+fn test_state_rejects_garbage() {
+    let (mut cpu, mut bus) = bootstrap(0x1234);
+    assert_eq!(
+        load_state(&mut cpu, &mut bus, b"not a state"),
+        Err(StateError::BadMagic)
+    );
+    assert_eq!(
+        load_state(&mut cpu, &mut bus, b"GB"),
+        Err(StateError::BadMagic)
+    );
+}
+
+#[test]
+fn test_state_skips_unknown_sections() {
+    let (mut cpu, mut bus) = bootstrap(0x1234);
+    run(&mut cpu, &mut bus, 100);
+    let mut state = save_state(&cpu, &bus);
+
+    // A section a newer build might add.
+    state.extend_from_slice(b"NEW!");
+    state.extend_from_slice(&3u32.to_le_bytes());
+    state.extend_from_slice(&[1, 2, 3]);
+
+    let (mut fresh_cpu, mut fresh_bus) = bootstrap(0x1234);
+    load_state(&mut fresh_cpu, &mut fresh_bus, &state).unwrap();
+    assert_eq!(fresh_cpu.pc, cpu.pc);
+}
+
+/// Rebuilds `state` with the payload of section `tag` passed through `edit`.
+fn edit_section(state: &[u8], tag: &[u8; 4], edit: impl Fn(&[u8]) -> Vec<u8>) -> Vec<u8> {
+    let mut out = state[..8].to_vec();
+    let mut rest = &state[8..];
+    while !rest.is_empty() {
+        let len = u32::from_le_bytes(rest[4..8].try_into().unwrap()) as usize;
+        let payload = &rest[8..8 + len];
+        let payload = if &rest[..4] == tag {
+            edit(payload)
+        } else {
+            payload.to_vec()
+        };
+        out.extend_from_slice(&rest[..4]);
+        out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        out.extend_from_slice(&payload);
+        rest = &rest[8 + len..];
+    }
+    out
+}
+
+#[test]
+fn test_state_failing_section_leaves_machine_as_it_was() {
+    let (mut cpu, mut bus) = bootstrap(0x1234);
+    run(&mut cpu, &mut bus, 1000);
+    // The CPU and bus sections come first and load fine, the timer doesn't.
+    let state = edit_section(&save_state(&cpu, &bus), b"TIMR", |_| Vec::new());
+
+    run(&mut cpu, &mut bus, 3000);
+    let (pc, a) = (cpu.pc, cpu.a);
+    let before = save_state(&cpu, &bus);
+    assert_eq!(
+        load_state(&mut cpu, &mut bus, &state),
+        Err(StateError::Truncated)
+    );
+    assert_eq!((cpu.pc, cpu.a), (pc, a), "CPU must not be half restored");
+    assert_eq!(save_state(&cpu, &bus), before);
+}
+
+#[test]
+fn test_state_rejects_newer_version() {
+    let (mut cpu, mut bus) = bootstrap(0x1234);
+    let mut state = save_state(&cpu, &bus);
+    state[4..6].copy_from_slice(&(STATE_VERSION + 1).to_le_bytes());
+
+    assert_eq!(
+        load_state(&mut cpu, &mut bus, &state),
+        Err(StateError::UnsupportedVersion(STATE_VERSION + 1))
+    );
+}
+
+fn run_frame(cpu: &mut Cpu, bus: &mut Bus<DummyInput>) {
+    // The LCD is off in these tests, so count a frame's worth of cycles.
+    let mut cycles = 0u32;
+    while cycles < 70224 {
+        let step = cpu.step(bus);
+        bus.tick_components(step);
+        cycles += step as u32;
+    }
+}
+
+#[test]
+fn test_rewind_and_resume() {
+    let (mut cpu, mut bus) = bootstrap(0x1234);
+    let mut rewind = RewindBuffer::new(2, 16 * 1024 * 1024);
+
+    let mut history = Vec::new();
+    for _ in 0..20 {
+        run_frame(&mut cpu, &mut bus);
+        rewind.on_frame(&cpu, &bus);
+        history.push(save_state(&cpu, &bus));
+    }
+    assert_eq!(rewind.len(), 10);
+
+    // The newest capture is the live frame 19, 6 frames back at an interval
+    // of 2 is three captures older, frame 13.
+    assert_eq!(rewind.rewind(6, &mut cpu, &mut bus), Ok(6));
+    assert_eq!(save_state(&cpu, &bus), history[13]);
+    assert_eq!(rewind.len(), 7, "Newer states are dropped on rewind");
+
+    // Resuming replays exactly what happened the first time.
+    for expected in &history[14..] {
+        run_frame(&mut cpu, &mut bus);
+        rewind.on_frame(&cpu, &bus);
+        assert_eq!(&save_state(&cpu, &bus), expected);
+    }
+}
+
+#[test]
+fn test_rewind_past_the_start() {
+    let (mut cpu, mut bus) = bootstrap(0x1234);
+    let mut rewind = RewindBuffer::new(1, 16 * 1024 * 1024);
+    assert_eq!(rewind.rewind(10, &mut cpu, &mut bus), Ok(0));
+
+    run_frame(&mut cpu, &mut bus);
+    rewind.on_frame(&cpu, &bus);
+    let first = save_state(&cpu, &bus);
+    for _ in 0..3 {
+        run_frame(&mut cpu, &mut bus);
+        rewind.on_frame(&cpu, &bus);
+    }
+
+    // The first capture is 3 frames before the live one.
+    assert_eq!(rewind.rewind(100, &mut cpu, &mut bus), Ok(3));
+    assert_eq!(save_state(&cpu, &bus), first);
+}
+
+#[test]
+fn test_rewind_between_captures() {
+    let (mut cpu, mut bus) = bootstrap(0x1234);
+    let mut rewind = RewindBuffer::new(5, 16 * 1024 * 1024);
+
+    // Captures after frames 4, 9, 14 and 19, then 3 more frames.
+    let mut history = Vec::new();
+    for _ in 0..23 {
+        run_frame(&mut cpu, &mut bus);
+        rewind.on_frame(&cpu, &bus);
+        history.push(save_state(&cpu, &bus));
+    }
+
+    assert_eq!(
+        rewind.rewind(2, &mut cpu, &mut bus),
+        Ok(0),
+        "Short of the newest capture"
+    );
+    assert_eq!(save_state(&cpu, &bus), history[22]);
+    assert_eq!(rewind.len(), 4);
+
+    // 3 frames to the newest capture, one interval more, rounded down.
+    assert_eq!(rewind.rewind(9, &mut cpu, &mut bus), Ok(8));
+    assert_eq!(save_state(&cpu, &bus), history[14]);
+    assert_eq!(rewind.len(), 3);
+}
+
+fn tick_m_cycles(bus: &mut Bus<DummyInput>, m_cycles: usize) {
+    for _ in 0..m_cycles {
+        bus.tick_components(4);
+    }
+}
+
+#[test]
+fn test_state_mid_oam_dma() {
+    let (mut cpu, mut bus) = bootstrap(0x1234);
+    run(&mut cpu, &mut bus, 200);
+    bus.write_byte(0xFF46, 0xC0);
+    bus.tick_components(12);
+    tick_m_cycles(&mut bus, 40);
+    assert!(bus.dma.active());
+    let state = save_state(&cpu, &bus);
+
+    tick_m_cycles(&mut bus, 130);
+    let expected = bus.ppu.oam;
+
+    let (mut fresh_cpu, mut fresh_bus) = bootstrap(0x1234);
+    load_state(&mut fresh_cpu, &mut fresh_bus, &state).unwrap();
+    assert!(fresh_bus.dma.active());
+    assert_eq!(fresh_bus.read_byte(0xFF46), 0xC0);
+    tick_m_cycles(&mut fresh_bus, 130);
+    assert!(!fresh_bus.dma.active());
+    assert_eq!(fresh_bus.ppu.oam, expected);
+}
+
+#[test]
+fn test_state_keeps_cgb_banks_and_palettes() {
+    let mut cgb_rom = rom(0x1234);
+    cgb_rom[0x0143] = 0x80;
+    let mut bus: Bus<DummyInput> = Bus::new(cgb_rom.clone()).with_model(Model::Cgb);
+    let cpu = Cpu::new();
+    bus.write_byte(0xFF70, 0x05);
+    bus.write_byte(0xD123, 0x55);
+    bus.write_byte(0xFF4F, 0x01);
+    bus.write_byte(0x8123, 0x66);
+    bus.write_byte(0xFF68, 0x80 | 0x10);
+    bus.write_byte(0xFF69, 0x77);
+    let state = save_state(&cpu, &bus);
+
+    let mut fresh_cpu = Cpu::new();
+    let mut fresh_bus: Bus<DummyInput> = Bus::new(cgb_rom).with_model(Model::Cgb);
+    load_state(&mut fresh_cpu, &mut fresh_bus, &state).unwrap();
+    assert_eq!(fresh_bus.read_byte(0xD123), 0x55);
+    assert_eq!(fresh_bus.read_byte(0x8123), 0x66);
+    assert_eq!(fresh_bus.ppu.bg_palettes.data[0x10], 0x77);
+    assert_eq!(fresh_bus.read_byte(0xFF68), 0xC0 | 0x11);
+}
+
+#[test]
+fn test_state_keeps_sgb_section() {
+    let mut bus: Bus<DummyInput> = Bus::new(rom(0x1234)).with_model(Model::Sgb);
+    let cpu = Cpu::new();
+    let sgb = bus.sgb.as_mut().unwrap();
+    sgb.palettes[2][1] = 0x1234;
+    sgb.attrs[10] = 2;
+    let state = save_state(&cpu, &bus);
+
+    let mut fresh_cpu = Cpu::new();
+    let mut fresh_bus: Bus<DummyInput> = Bus::new(rom(0x1234)).with_model(Model::Sgb);
+    load_state(&mut fresh_cpu, &mut fresh_bus, &state).unwrap();
+    let sgb = fresh_bus.sgb.as_ref().unwrap();
+    assert_eq!(sgb.palettes[2][1], 0x1234);
+    assert_eq!(sgb.attrs[10], 2);
+
+    // A DMG ignores the section.
+    let mut dmg_bus: Bus<DummyInput> = Bus::new(rom(0x1234));
+    load_state(&mut fresh_cpu, &mut dmg_bus, &state).unwrap();
+}